@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use outro_08::{
+    data::{Event, EventKind, Status, Ticket, TicketDescription, TicketDraft, TicketId, TicketPatch, TicketTitle},
+    error::Result,
+};
+
+pub const COLUMNS: [Status; 3] = [Status::ToDo, Status::InProgress, Status::Done];
+
+// What the board wants done on the server. Results come back through the live
+// feed like anyone else's changes, so nothing is applied to the board up front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Create(TicketDraft),
+    Patch(TicketPatch),
+    // Done tickets can't be patched back, the server reopens them where the workflow says.
+    Reopen { id: TicketId, version: u64 },
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Description,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Form {
+    // `None` when creating a ticket.
+    pub editing: Option<TicketId>,
+    version: Option<u64>,
+    pub title: String,
+    pub description: String,
+    pub focus: Field,
+    pub error: Option<String>,
+}
+
+impl Form {
+    fn create() -> Self {
+        Self {
+            editing: None,
+            version: None,
+            title: String::new(),
+            description: String::new(),
+            focus: Field::Title,
+            error: None,
+        }
+    }
+
+    fn edit(ticket: &Ticket) -> Self {
+        Self {
+            editing: Some(ticket.id),
+            version: Some(ticket.version),
+            title: ticket.title.to_string(),
+            description: ticket.description.to_string(),
+            ..Self::create()
+        }
+    }
+
+    fn input(&mut self) -> &mut String {
+        match self.focus {
+            Field::Title => &mut self.title,
+            Field::Description => &mut self.description,
+        }
+    }
+
+    fn checked(&self) -> Result<(TicketTitle, TicketDescription)> {
+        Ok((TicketTitle::try_from(self.title.trim())?, TicketDescription::try_from(self.description.trim())?))
+    }
+
+    // Only gives up the action once both fields pass, otherwise says what's wrong.
+    fn submit(&mut self) -> Option<Action> {
+        let (title, description) = match self.checked() {
+            Ok(fields) => fields,
+            Err(error) => {
+                self.error = Some(error.to_string());
+                return None
+            }
+        };
+
+        Some(match self.editing {
+            None => Action::Create(TicketDraft { title, description, priority: None, due_date: None }),
+            Some(id) => Action::Patch(TicketPatch {
+                id,
+                title: Some(title),
+                description: Some(description),
+                version: self.version,
+                ..Default::default()
+            }),
+        })
+    }
+}
+
+// Everything the board shows, kept apart from the terminal so it can be tested.
+#[derive(Debug, Default)]
+pub struct Board {
+    tickets: BTreeMap<TicketId, Ticket>,
+    pub column: usize,
+    selected: [usize; 3],
+    pub form: Option<Form>,
+    // Shown until the next key press.
+    pub message: Option<String>,
+}
+
+impl Board {
+    pub fn new(tickets: impl IntoIterator<Item = Ticket>) -> Self {
+        let mut board = Self::default();
+        for ticket in tickets {
+            board.merge(ticket);
+        }
+        board
+    }
+
+    // Oldest first, archived tickets are left off the board.
+    pub fn cards(&self, column: usize) -> Vec<&Ticket> {
+        self.tickets.values().filter(|ticket| ticket.status == COLUMNS[column]).collect()
+    }
+
+    pub fn selected(&self, column: usize) -> usize {
+        self.selected[column].min(self.cards(column).len().saturating_sub(1))
+    }
+
+    pub fn selected_ticket(&self) -> Option<&Ticket> {
+        self.cards(self.column).get(self.selected(self.column)).copied()
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match event.kind {
+            EventKind::Created | EventKind::Patched => self.merge(event.ticket),
+            EventKind::Deleted => {
+                self.tickets.remove(&event.ticket.id);
+            }
+        }
+    }
+
+    // The feed and the first listing can overlap, so an older version never
+    // replaces a newer one.
+    fn merge(&mut self, ticket: Ticket) {
+        if ticket.archived {
+            self.tickets.remove(&ticket.id);
+            return
+        }
+
+        match self.tickets.get(&ticket.id) {
+            Some(known) if known.version > ticket.version => {}
+            _ => {
+                self.tickets.insert(ticket.id, ticket);
+            }
+        }
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.message = None;
+
+        if let Some(form) = &mut self.form {
+            return match key.code {
+                KeyCode::Esc => {
+                    self.form = None;
+                    None
+                }
+                KeyCode::Enter => {
+                    let action = form.submit();
+                    if action.is_some() {
+                        self.form = None;
+                    }
+                    action
+                }
+                KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                    form.focus = match form.focus {
+                        Field::Title => Field::Description,
+                        Field::Description => Field::Title,
+                    };
+                    None
+                }
+                KeyCode::Backspace => {
+                    form.input().pop();
+                    form.error = None;
+                    None
+                }
+                KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    form.input().push(c);
+                    form.error = None;
+                    None
+                }
+                _ => None,
+            }
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Some(Action::Quit),
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(COLUMNS.len() - 1),
+            KeyCode::Up | KeyCode::Char('k') => self.selected[self.column] = self.selected(self.column).saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                let last = self.cards(self.column).len().saturating_sub(1);
+                self.selected[self.column] = (self.selected(self.column) + 1).min(last);
+            }
+            KeyCode::Char('<') | KeyCode::Char('H') => return self.shift(-1),
+            KeyCode::Char('>') | KeyCode::Char('L') => return self.shift(1),
+            KeyCode::Char('n') => self.form = Some(Form::create()),
+            KeyCode::Char('e') | KeyCode::Enter => self.form = self.selected_ticket().map(Form::edit),
+            _ => {}
+        }
+
+        None
+    }
+
+    // Moves the selected card to the next column over, as long as the ticket hasn't
+    // changed since it was last seen. Moving out of Done reopens the ticket.
+    fn shift(&self, by: isize) -> Option<Action> {
+        let ticket = self.selected_ticket()?;
+        let column = self.column.checked_add_signed(by).filter(|&column| column < COLUMNS.len())?;
+
+        if ticket.status == Status::Done {
+            return Some(Action::Reopen { id: ticket.id, version: ticket.version })
+        }
+
+        Some(Action::Patch(TicketPatch {
+            id: ticket.id,
+            status: Some(COLUMNS[column]),
+            version: Some(ticket.version),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use outro_08::data::title::MAX_TITLE_LEN;
+    use super::*;
+
+    fn ticket(id: u64, title: &str, status: Status) -> Ticket {
+        Ticket::with(TicketId(id), title, "On the board.", status).unwrap()
+    }
+
+    fn press(board: &mut Board, keys: &str) -> Option<Action> {
+        keys.chars().map(|c| board.key(KeyEvent::from(KeyCode::Char(c)))).last().flatten()
+    }
+
+    #[test]
+    fn check_if_moving_a_card_patches_its_status() {
+        let mut board = Board::new([ticket(0, "First", Status::ToDo), ticket(1, "Second", Status::ToDo)]);
+
+        let action = press(&mut board, "j>");
+        assert_eq!(action, Some(Action::Patch(TicketPatch {
+            id: TicketId(1),
+            status: Some(Status::InProgress),
+            version: Some(1),
+            ..Default::default()
+        })));
+
+        assert_eq!(press(&mut board, "<"), None);
+        assert_eq!(press(&mut board, "l"), None, "Nothing to move in an empty column");
+    }
+
+    #[test]
+    fn check_if_moving_a_card_out_of_done_reopens_it() {
+        let mut done = ticket(0, "Finished", Status::Done);
+        done.version = 3;
+        let mut board = Board::new([done]);
+
+        assert_eq!(press(&mut board, "ll>"), None, "Done is the last column");
+        assert_eq!(press(&mut board, "<"), Some(Action::Reopen { id: TicketId(0), version: 3 }));
+    }
+
+    #[test]
+    fn check_if_forms_only_submit_valid_tickets() {
+        let mut board = Board::default();
+
+        press(&mut board, "n");
+        press(&mut board, &"x".repeat(MAX_TITLE_LEN + 1));
+        board.key(KeyCode::Tab.into());
+        press(&mut board, "Plenty of room.");
+
+        assert_eq!(board.key(KeyCode::Enter.into()), None);
+        assert!(board.form.as_ref().is_some_and(|form| form.error.is_some()), "Form is: {:?}", board.form);
+
+        board.key(KeyCode::BackTab.into());
+        board.key(KeyCode::Backspace.into());
+
+        let Some(Action::Create(draft)) = board.key(KeyCode::Enter.into()) else { panic!("Expected a draft") };
+        assert_eq!(draft.title.to_string().len(), MAX_TITLE_LEN);
+        assert_eq!(draft.description.to_string(), "Plenty of room.");
+        assert_eq!(board.form, None);
+    }
+
+    #[test]
+    fn check_if_live_events_keep_the_columns_current() {
+        let mut board = Board::new([ticket(0, "Moving", Status::ToDo), ticket(1, "Going", Status::ToDo)]);
+
+        let mut moved = ticket(0, "Moving", Status::Done);
+        moved.version = 2;
+        board.apply(Event { epoch: 1, seq: 1, kind: EventKind::Patched, ticket: moved });
+        board.apply(Event { epoch: 1, seq: 0, kind: EventKind::Created, ticket: ticket(0, "Moving", Status::ToDo) });
+        board.apply(Event { epoch: 1, seq: 2, kind: EventKind::Deleted, ticket: ticket(1, "Going", Status::ToDo) });
+        board.apply(Event { epoch: 1, seq: 3, kind: EventKind::Created, ticket: ticket(2, "New", Status::InProgress) });
+
+        let titles = |column| board.cards(column).iter().map(|t| t.title.to_string()).collect::<Vec<_>>();
+        assert_eq!([titles(0), titles(1), titles(2)], [vec![], vec!["New".to_string()], vec!["Moving".to_string()]]);
+    }
+}
@@ -0,0 +1,151 @@
+// A kanban board for the ticket server, right in the terminal. Cards move between
+// To-do, In progress and Done, and changes made anywhere show up as they happen.
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use clap::Parser;
+use futures_util::{Stream, StreamExt};
+use ratatui::{
+    crossterm::event::{self, KeyEvent, KeyEventKind},
+    DefaultTerminal,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use outro_08::{
+    client::Client,
+    data::{Event, EventFilter},
+    error::Result,
+};
+
+mod board;
+mod ui;
+
+use board::{Action, Board};
+
+#[derive(Debug, Parser)]
+#[command(name = "ticketboard", version, about = "Kanban board for the ticket server")]
+struct Cli {
+    #[arg(long, default_value = Client::DEFAULT_URL, help = "Address of the server")]
+    addr: String,
+    #[arg(long, help = "Who to record changes as in the ticket history")]
+    actor: Option<String>,
+}
+
+// Everything the board reacts to comes through one channel.
+enum Message {
+    Key(KeyEvent),
+    Changed(Event),
+    Failed(String),
+    Redraw,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("ticketboard: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut client = Client::builder().addr(&cli.addr);
+    if let Some(actor) = &cli.actor {
+        client = client.actor(actor);
+    }
+    let client = Arc::new(client.build()?);
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    // Following the feed before the first listing means nothing falls in between,
+    // the board keeps whichever version of a ticket is newer.
+    tokio::spawn(follow(client.events(EventFilter::default()), sender.clone()));
+    let board = Board::new(client.list_all().await?);
+
+    let keys = sender.clone();
+    std::thread::spawn(move || read_keys(keys));
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, board, client, receiver, sender).await;
+    ratatui::restore();
+
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut board: Board,
+    client: Arc<Client>,
+    mut receiver: UnboundedReceiver<Message>,
+    sender: UnboundedSender<Message>,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| ui::draw(frame, &board))?;
+
+        let Some(message) = receiver.recv().await else {
+            return Ok(())
+        };
+
+        match message {
+            Message::Key(key) => match board.key(key) {
+                Some(Action::Quit) => return Ok(()),
+                Some(action) => {
+                    tokio::spawn(perform(client.clone(), action, sender.clone()));
+                }
+                None => {}
+            },
+            Message::Changed(event) => board.apply(event),
+            Message::Failed(error) => board.message = Some(error),
+            Message::Redraw => {}
+        }
+    }
+}
+
+// Only failures are reported back, successes arrive through the feed.
+async fn perform(client: Arc<Client>, action: Action, sender: UnboundedSender<Message>) {
+    let result = match action {
+        Action::Create(draft) => client.create(&draft).await.map(drop),
+        Action::Patch(patch) => client.patch(patch).await.map(drop),
+        Action::Reopen { id, version } => client.reopen(id, Some(version)).await.map(drop),
+        Action::Quit => Ok(()),
+    };
+
+    if let Err(error) = result {
+        let _ = sender.send(Message::Failed(error.to_string()));
+    }
+}
+
+async fn follow(events: impl Stream<Item = Result<Event>>, sender: UnboundedSender<Message>) {
+    let mut events = std::pin::pin!(events);
+
+    while let Some(event) = events.next().await {
+        let message = match event {
+            Ok(event) => Message::Changed(event),
+            Err(error) => Message::Failed(format!("Lost the live feed: {error}")),
+        };
+        if sender.send(message).is_err() {
+            return
+        }
+    }
+}
+
+// Crossterm only reads the terminal by blocking, so it gets a thread of its own.
+fn read_keys(sender: UnboundedSender<Message>) {
+    loop {
+        let message = match event::read() {
+            Ok(event::Event::Key(key)) if key.kind == KeyEventKind::Press => Message::Key(key),
+            Ok(event::Event::Resize(..)) => Message::Redraw,
+            Ok(_) => continue,
+            Err(error) => {
+                let _ = sender.send(Message::Failed(format!("Cannot read the terminal: {error}")));
+                return
+            }
+        };
+        if sender.send(message).is_err() {
+            return
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Position, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph},
+};
+
+use outro_08::data::{
+    description::MAX_DESCRIPTION_LEN,
+    title::MAX_TITLE_LEN,
+    Ticket,
+};
+
+use crate::board::{Board, Field, Form, COLUMNS};
+
+const HELP: &str = "←/→ column  ↑/↓ card  </> move card  n new  e edit  q quit";
+
+pub fn draw(frame: &mut Frame, board: &Board) {
+    let [columns, status] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let areas = Layout::horizontal([Constraint::Ratio(1, 3); 3]).split(columns);
+
+    for (column, area) in areas.iter().enumerate() {
+        let cards = board.cards(column);
+        let focused = column == board.column;
+
+        let block = Block::bordered()
+            .title(format!(" {} ({}) ", COLUMNS[column], cards.len()))
+            .border_style(if focused { Style::new().yellow() } else { Style::new() });
+        let list = List::new(cards.iter().map(|ticket| card(ticket)))
+            .block(block)
+            .highlight_style(Style::new().reversed());
+
+        let mut state = ListState::default().with_selected((focused && !cards.is_empty()).then(|| board.selected(column)));
+        frame.render_stateful_widget(list, *area, &mut state);
+    }
+
+    let status_line = match &board.message {
+        Some(message) => Line::from(message.as_str()).red(),
+        None => Line::from(HELP).dim(),
+    };
+    frame.render_widget(status_line, status);
+
+    if let Some(form) = &board.form {
+        draw_form(frame, form);
+    }
+}
+
+fn card(ticket: &Ticket) -> ListItem<'_> {
+    let mut spans = vec![Span::from(format!("#{} ", ticket.id)).dim(), Span::from(ticket.title.to_string())];
+
+    if let Some(priority) = ticket.priority {
+        spans.push(Span::from(format!(" !{priority}")).magenta());
+    }
+    if !ticket.labels.is_empty() {
+        let labels = ticket.labels.iter().map(|label| format!(" #{label}")).collect::<String>();
+        spans.push(Span::from(labels).cyan());
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let area = centered(frame.area(), 70, 9);
+    let title = match form.editing {
+        Some(id) => format!(" Edit ticket {id} "),
+        None => " New ticket ".to_string(),
+    };
+    let block = Block::bordered().title(title).border_style(Style::new().yellow());
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(&block, area);
+
+    let [title, description, footer] = Layout::vertical([Constraint::Length(3), Constraint::Length(3), Constraint::Length(1)])
+        .areas(block.inner(area));
+
+    input(frame, title, "Title", &form.title, MAX_TITLE_LEN, form.focus == Field::Title);
+    input(frame, description, "Description", &form.description, MAX_DESCRIPTION_LEN, form.focus == Field::Description);
+
+    let footer_line = match &form.error {
+        Some(error) => Line::from(error.as_str()).red(),
+        None => Line::from("Tab switch field  Enter save  Esc cancel").dim(),
+    };
+    frame.render_widget(footer_line, footer);
+}
+
+// A single line that scrolls to keep the end of the text, and the cursor, in view.
+fn input(frame: &mut Frame, area: Rect, label: &str, text: &str, limit: usize, focused: bool) {
+    let counter = Span::from(format!(" {}/{limit} ", text.len()));
+    let counter = if text.len() > limit { counter.red() } else { counter.dim() };
+
+    let block = Block::bordered()
+        .title(format!(" {label} "))
+        .title_bottom(Line::from(counter).right_aligned())
+        .border_style(if focused { Style::new().white() } else { Style::new().dim() });
+
+    let width = block.inner(area).width.saturating_sub(1) as usize;
+    let length = text.chars().count();
+    let scroll = length.saturating_sub(width);
+
+    frame.render_widget(Paragraph::new(text).scroll((0, scroll as u16)).block(block), area);
+
+    if focused {
+        frame.set_cursor_position(Position::new(area.x + 1 + (length - scroll) as u16, area.y + 1));
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+    area
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, crossterm::event::KeyCode, Terminal};
+    use outro_08::data::{Status, TicketId};
+    use super::*;
+
+    fn screen(board: &Board) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(90, 14)).unwrap();
+        terminal.draw(|frame| draw(frame, board)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        buffer.content.chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn check_if_cards_show_up_in_their_columns() {
+        let ticket = Ticket::with(TicketId(4), "Draw me", "On screen.", Status::InProgress).unwrap();
+        let mut board = Board::new([ticket]);
+
+        let shown = screen(&board);
+        assert!(shown.contains("To-do (0)") && shown.contains("In progress (1)") && shown.contains("Done (0)"), "{shown}");
+        assert!(shown.contains("#4 Draw me"), "{shown}");
+
+        board.key(KeyCode::Char('n').into());
+        board.key(KeyCode::Char('x').into());
+
+        let shown = screen(&board);
+        assert!(shown.contains("New ticket") && shown.contains("1/50"), "{shown}");
+    }
+}
@@ -0,0 +1,364 @@
+// Talks to a running ticket server from the shell, e.g.
+//
+//     ticketctl create --title "Fix login" --description "It hangs." --priority high
+//     ticketctl patch 3 --status "in progress"
+//     ticketctl list --status done --json
+//
+// Exit codes tell failures apart without parsing the message: see `exit_code`.
+
+use std::process::ExitCode;
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use reqwest::StatusCode;
+use serde::Serialize;
+
+use outro_08::{
+    client::Client,
+    data::{Event, EventFilter, Label, Priority, Status, Ticket, TicketDescription, TicketDraft, TicketId},
+    data::{TicketPatch, TicketTitle},
+    error::{Error, Result},
+};
+
+// Anything not covered below.
+const FAILURE: u8 = 1;
+// Bad arguments, as clap reports them.
+const USAGE: u8 = 2;
+// Input that was refused, either here or by the server.
+const VALIDATION: u8 = 3;
+const NOT_FOUND: u8 = 4;
+// The server couldn't be reached or didn't answer in time.
+const CONNECTION: u8 = 5;
+// The ticket changed in the meantime, or isn't in a state the change can start from.
+const CONFLICT: u8 = 6;
+
+#[derive(Debug, Parser)]
+#[command(name = "ticketctl", version, about = "Command-line client for the ticket server")]
+struct Cli {
+    #[arg(long, global = true, default_value = Client::DEFAULT_URL, help = "Address of the server")]
+    addr: String,
+    #[arg(long, global = true, help = "Who to record changes as in the ticket history")]
+    actor: Option<String>,
+    #[arg(long, global = true, help = "Print JSON instead of a table")]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(about = "List tickets, oldest first")]
+    List(ListArgs),
+    #[command(about = "Create a ticket and show it")]
+    Create(CreateArgs),
+    #[command(about = "Show a single ticket")]
+    Show { id: u64 },
+    #[command(about = "Change some fields of a ticket")]
+    Patch(PatchArgs),
+    #[command(about = "Delete a ticket for good")]
+    Delete { id: u64 },
+    #[command(about = "Print changes as they happen, until interrupted")]
+    Watch(WatchArgs),
+}
+
+#[derive(Debug, Args)]
+struct ListArgs {
+    #[arg(long)]
+    status: Option<String>,
+    #[arg(long)]
+    label: Option<String>,
+    #[arg(long, help = "Only tickets with this text in their title or description")]
+    search: Option<String>,
+    #[arg(long, help = "Include archived tickets")]
+    archived: bool,
+}
+
+#[derive(Debug, Args)]
+struct CreateArgs {
+    #[arg(long)]
+    title: String,
+    #[arg(long)]
+    description: String,
+    #[arg(long)]
+    priority: Option<String>,
+    #[arg(long, help = "As YYYY-MM-DD")]
+    due: Option<String>,
+}
+
+#[derive(Debug, Args)]
+struct PatchArgs {
+    id: u64,
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long)]
+    description: Option<String>,
+    #[arg(long)]
+    status: Option<String>,
+    #[arg(long, conflicts_with = "clear_priority")]
+    priority: Option<String>,
+    #[arg(long)]
+    clear_priority: bool,
+    #[arg(long, conflicts_with = "clear_due", help = "As YYYY-MM-DD")]
+    due: Option<String>,
+    #[arg(long)]
+    clear_due: bool,
+    #[arg(long, help = "Only patch if the ticket is still at this version")]
+    version: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+struct WatchArgs {
+    #[arg(long, help = "Only changes to this ticket")]
+    id: Option<u64>,
+    #[arg(long, help = "Only tickets that end up in this status")]
+    status: Option<String>,
+    #[arg(long, help = "Replay what the server still has after this sequence number, instead of only new changes")]
+    after: Option<u64>,
+    #[arg(long, requires = "after", help = "The epoch --after was counted in, fails if the server restarted since")]
+    epoch: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+            return ExitCode::from(if error.use_stderr() { USAGE } else { 0 })
+        }
+    };
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("ticketctl: {error}");
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let mut client = Client::builder().addr(&cli.addr);
+    if let Some(actor) = &cli.actor {
+        client = client.actor(actor);
+    }
+    let client = client.build()?;
+
+    match cli.command {
+        Command::List(args) => {
+            let mut list = client.list_all().archived(args.archived);
+            if let Some(status) = args.status {
+                list = list.status(Status::try_from(status)?);
+            }
+            if let Some(label) = args.label {
+                list = list.label(Label::try_from(label)?);
+            }
+            if let Some(text) = args.search {
+                list = list.matching(text);
+            }
+
+            let tickets = list.await?;
+            if cli.json {
+                print_json(&tickets)?;
+            } else {
+                print!("{}", table(&tickets));
+            }
+        }
+        Command::Create(args) => {
+            let draft = TicketDraft {
+                title: TicketTitle::try_from(args.title)?,
+                description: TicketDescription::try_from(args.description)?,
+                priority: args.priority.map(Priority::try_from).transpose()?,
+                due_date: args.due.as_deref().map(date).transpose()?,
+            };
+
+            let id = client.create(&draft).await?;
+            show(&client.retrieve(id).await?, cli.json)?;
+        }
+        Command::Show { id } => show(&client.retrieve(TicketId(id)).await?, cli.json)?,
+        Command::Patch(args) => {
+            let patch = TicketPatch {
+                id: TicketId(args.id),
+                title: args.title.map(TicketTitle::try_from).transpose()?,
+                description: args.description.map(TicketDescription::try_from).transpose()?,
+                status: args.status.map(Status::try_from).transpose()?,
+                priority: match args.priority {
+                    Some(priority) => Some(Some(Priority::try_from(priority)?)),
+                    None => args.clear_priority.then_some(None),
+                },
+                due_date: match args.due {
+                    Some(due) => Some(Some(date(&due)?)),
+                    None => args.clear_due.then_some(None),
+                },
+                version: args.version,
+            };
+
+            show(&client.patch(patch).await?, cli.json)?;
+        }
+        Command::Delete { id } => show(&client.delete(TicketId(id)).await?, cli.json)?,
+        Command::Watch(args) => {
+            let filter = EventFilter {
+                id: args.id.map(TicketId),
+                status: args.status.map(Status::try_from).transpose()?,
+                after: args.after,
+                epoch: args.epoch,
+                live: args.after.is_none(),
+            };
+
+            let mut events = std::pin::pin!(client.events(filter));
+            while let Some(event) = events.next().await {
+                let event = event?;
+                if cli.json {
+                    println!("{}", serde_json::to_string(&event)?);
+                } else {
+                    println!("{}", event_line(&event));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn exit_code(error: &Error) -> u8 {
+    // Errors from the server come back typed, so they're sorted the same way
+    // whether they were caught here or over there.
+    match error {
+        Error::Request(error) if error.is_connect() || error.is_timeout() => CONNECTION,
+        _ => match error.status() {
+            StatusCode::REQUEST_TIMEOUT | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => CONNECTION,
+            StatusCode::NOT_FOUND => NOT_FOUND,
+            StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => CONFLICT,
+            status if status.is_client_error() => VALIDATION,
+            _ => FAILURE,
+        },
+    }
+}
+
+fn date(text: &str) -> Result<NaiveDate> {
+    Ok(text.trim().parse()?)
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn show(ticket: &Ticket, json: bool) -> Result<()> {
+    if json {
+        return print_json(ticket)
+    }
+
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+
+    println!("Ticket {}: {}", ticket.id, ticket.title);
+    println!("  Status:      {}{}", ticket.status, if ticket.archived { " (archived)" } else { "" });
+    println!("  Priority:    {}", optional(ticket.priority.map(|p| p.to_string())));
+    println!("  Due:         {}", optional(ticket.due_date.map(|d| d.to_string())));
+    println!("  Labels:      {}", optional((!ticket.labels.is_empty()).then(|| labels(ticket))));
+    println!("  Version:     {}", ticket.version);
+    println!("  Updated:     {}", ticket.updated_at.format("%Y-%m-%d %H:%M:%S UTC"));
+    println!();
+    println!("{}", ticket.description);
+    Ok(())
+}
+
+fn labels(ticket: &Ticket) -> String {
+    ticket.labels.iter().map(Label::to_string).collect::<Vec<_>>().join(", ")
+}
+
+// Columns are padded to their widest cell, the title goes last since it's the longest.
+fn table(tickets: &[Ticket]) -> String {
+    if tickets.is_empty() {
+        return "No tickets.\n".into()
+    }
+
+    let header = ["ID", "STATUS", "PRIORITY", "DUE", "LABELS", "TITLE"].map(String::from);
+    let rows = tickets.iter().map(|ticket| [
+        ticket.id.to_string(),
+        ticket.status.to_string(),
+        ticket.priority.map(|p| p.to_string()).unwrap_or_default(),
+        ticket.due_date.map(|d| d.to_string()).unwrap_or_default(),
+        labels(ticket),
+        ticket.title.to_string(),
+    ]);
+    let rows = std::iter::once(header).chain(rows).collect::<Vec<_>>();
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for row in &rows {
+        let cells = row.iter().zip(widths).map(|(cell, width)| format!("{cell:width$}")).collect::<Vec<_>>();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn event_line(event: &Event) -> String {
+    let ticket = &event.ticket;
+    format!("#{} {} ticket {} [{}] {}", event.seq, event.kind, ticket.id, ticket.status, ticket.title)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use outro_08::data::EventKind;
+    use super::*;
+
+    #[test]
+    fn check_if_arguments_parse_into_commands() {
+        let cli = Cli::try_parse_from(["ticketctl", "patch", "3", "--status", "done", "--clear-due", "--json"]).unwrap();
+        assert_eq!(cli.addr, Client::DEFAULT_URL);
+        assert!(cli.json);
+        assert!(matches!(cli.command, Command::Patch(PatchArgs { id: 3, clear_due: true, .. })));
+
+        let error = Cli::try_parse_from(["ticketctl", "patch", "3", "--due", "2030-01-01", "--clear-due"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn check_if_errors_map_to_distinct_exit_codes() {
+        let title = TicketTitle::try_from("").unwrap_err();
+
+        assert_eq!(exit_code(&title.into()), VALIDATION);
+        assert_eq!(exit_code(&Error::HttpStatusCode(StatusCode::UNPROCESSABLE_ENTITY, "Nope.".into())), VALIDATION);
+        assert_eq!(exit_code(&Error::HttpStatusCode(StatusCode::CONFLICT, "Nope.".into())), CONFLICT);
+        assert_eq!(exit_code(&Error::VersionMismatch { id: TicketId(3), expected: 1, actual: 2 }), CONFLICT);
+        assert_eq!(exit_code(&Error::RequestTimeout(Duration::from_secs(30))), CONNECTION);
+        assert_eq!(exit_code(&Error::Timeout(Duration::from_secs(30))), CONNECTION);
+        assert_eq!(exit_code(&Error::HttpStatusCode(StatusCode::NOT_FOUND, "Gone.".into())), NOT_FOUND);
+        assert_eq!(exit_code(&Error::NotFound(TicketId(3))), NOT_FOUND);
+        assert_eq!(exit_code(&Error::HttpStatusCode(StatusCode::INTERNAL_SERVER_ERROR, "Oops.".into())), FAILURE);
+    }
+
+    #[tokio::test]
+    async fn check_if_unreachable_servers_are_connection_failures() {
+        let cli = Cli::try_parse_from(["ticketctl", "--addr", "127.0.0.1:1", "show", "0"]).unwrap();
+
+        let error = run(cli).await.unwrap_err();
+        assert_eq!(exit_code(&error), CONNECTION, "Got: {error}");
+    }
+
+    #[test]
+    fn check_if_tables_line_up() {
+        let mut long = Ticket::with(TicketId(10), "Long one", "Wide.", Status::InProgress).unwrap();
+        long.priority = Some(Priority::High);
+        let short = Ticket::with(TicketId(2), "Short", "Narrow.", Status::Done).unwrap();
+
+        assert_eq!(table(&[short, long.clone()]), "\
+ID  STATUS       PRIORITY  DUE  LABELS  TITLE
+2   Done                                Short
+10  In progress  high                   Long one
+");
+        assert_eq!(table(&[]), "No tickets.\n");
+
+        let event = Event { epoch: 1, seq: 7, kind: EventKind::Patched, ticket: long };
+        assert_eq!(event_line(&event), "#7 patched ticket 10 [In progress] Long one");
+    }
+}
@@ -1,13 +1,35 @@
+use std::collections::VecDeque;
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::time::Duration;
+use futures_util::{stream, Stream};
+use reqwest::{header, Method, StatusCode};
 use url::Url;
 use crate::{
-    error::Result,
-    data::{TicketId, Ticket, TicketDraft, TicketPatch},
+    error::{Error, Result},
+    data::etag,
+    data::{HistoryEntry, Page, SortBy, Status, TicketId, Ticket, TicketDraft, TicketPatch, TicketQuery},
+    data::{Comment, CommentDraft, CommentId, Label, Link, LinkDraft},
+    data::{Event, EventFilter},
+    data::{DeadLetter, Webhook, WebhookDraft, WebhookId},
+    data::{Batch, BatchOutcome},
+    problem::Problem,
+    transfer::{Format, ImportReport},
 };
 
+pub mod blocking;
+mod builder;
+
+pub use builder::{ClientBuilder, Retry};
+
 #[derive(Debug)]
 pub struct Client {
     client: reqwest::Client,
     base_url: Url,
+    // Applied per request rather than to `client`, which would cut the event feed short.
+    timeout: Option<Duration>,
+    retry: Retry,
+    reconnect: Retry,
 }
 
 impl Client {
@@ -19,41 +41,96 @@ impl Client {
     }
 
     pub fn with_addr(addr: impl AsRef<str>) -> Result<Self> {
-        Ok(Self{
-            client: reqwest::Client::new(),
-            base_url: Url::parse(&format!("http://{}/tickets", addr.as_ref()))?,
-        })
+        Self::builder().addr(addr.as_ref()).build()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    // Awaiting it straight away fetches every ticket, page by page. Use the builder
+    // methods to narrow the list down, or `page` to fetch a single page.
+    pub fn list_all(&self) -> ListTickets<'_> {
+        ListTickets { client: self, query: TicketQuery::default() }
     }
 
-    pub async fn list_all(&self) -> Result<Vec<Ticket>> {
-        Ok(
-            self.client
-                .get(self.base_url.as_ref())
-                .send().await?
-                .json().await?
-        )
+    // Open tickets past their due date, most urgent first.
+    pub async fn overdue(&self) -> Result<Vec<Ticket>> {
+
+        let url = Url::parse(&format!("{}/overdue", self.base_url))?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    // Follows the server's change feed. When the connection drops it reconnects on its
+    // own, backing off as the builder's `reconnect` says, and picks up after the last
+    // event it handed out. Only failing to reconnect ends the stream, with that error
+    // as its last item: `Error::MissedEvents` once the server no longer has what came
+    // after, e.g. because it restarted. Keep `Event::seq` and `Event::epoch` to resume
+    // later with `filter.after` and `filter.epoch`.
+    pub fn events(&self, filter: EventFilter) -> impl Stream<Item = Result<Event>> + Send + 'static {
+        let subscription = Subscription {
+            client: self.client.clone(),
+            url: format!("{}/events", self.base_url),
+            reconnect: self.reconnect,
+            filter,
+            response: None,
+            buffer: Vec::new(),
+            pending: VecDeque::new(),
+        };
+
+        stream::unfold(Some(subscription), |subscription| async move {
+            let mut subscription = subscription?;
+
+            match subscription.next().await {
+                Ok(event) => Some((Ok(event), Some(subscription))),
+                Err(error) => Some((Err(error), None)),
+            }
+        })
     }
 
     pub async fn create(&self, draft: &TicketDraft) -> Result<TicketId> {
-        Ok(
-            self.client
-                .post (self.base_url.as_ref())
-                .json(draft)
-                .send().await?
-                .json().await?
-        )
+        decode(self.send(self.client.post(self.base_url.as_ref()).json(draft)).await?).await
+    }
+
+    // One round-trip for the lot. An `Atomic` batch that fails comes back as the
+    // error of the item that failed, with nothing applied.
+    pub async fn batch(&self, batch: &Batch) -> Result<Vec<BatchOutcome>> {
+
+        let url = Url::parse(&format!("{}/batch", self.base_url))?;
+
+        decode(self.send(self.client.post(url).json(batch)).await?).await
+    }
+
+    // Every ticket, archived ones included, in one document.
+    pub async fn export(&self, format: Format) -> Result<String> {
+
+        let mut url = Url::parse(&format!("{}/export", self.base_url))?;
+        url.query_pairs_mut().append_pair("format", format.as_str());
+
+        let response = check(self.send(self.client.get(url)).await?).await?;
+
+        Ok(response.text().await?)
+    }
+
+    pub async fn import(&self, format: Format, input: impl Into<String>) -> Result<ImportReport> {
+
+        let mut url = Url::parse(&format!("{}/import", self.base_url))?;
+        url.query_pairs_mut().append_pair("format", format.as_str());
+
+        let request = self.client
+            .post(url)
+            .header(header::CONTENT_TYPE, format.content_type())
+            .body(input.into());
+
+        decode(self.send(request).await?).await
     }
 
     pub async fn retrieve(&self, TicketId(id): TicketId) -> Result<Ticket> {
 
         let url = Url::parse(&format!("{}/{}", self.base_url, id))?;
 
-        Ok(
-            self.client
-                .get(url)
-                .send().await?
-                .json().await?
-        )
+        decode(self.send(self.client.get(url)).await?).await
     }
 
     pub async fn patch(&self, patch: TicketPatch) -> Result<Ticket> {
@@ -75,16 +152,385 @@ impl Client {
             map.insert("status".into(), serde_json::to_value(status)?);
         }
 
-        Ok(
-            self.client
-                .patch(url)
-                .json(&Value::Object(map))
-                .send().await?
-                .json().await?
-        )
+        if let Some(priority) = patch.priority {
+            map.insert("priority".into(), serde_json::to_value(priority)?);
+        }
+
+        if let Some(due_date) = patch.due_date {
+            map.insert("due_date".into(), serde_json::to_value(due_date)?);
+        }
+
+        let mut request = self.client.patch(url).json(&Value::Object(map));
+
+        if let Some(version) = patch.version {
+            request = request.header(header::IF_MATCH, etag(version));
+        }
+
+        // Someone else got there first, that comes back as `Error::VersionMismatch`
+        // with the version they left behind.
+        decode(self.send(request).await?).await
+    }
+
+    pub async fn delete(&self, TicketId(id): TicketId) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}", self.base_url, id))?;
+
+        decode(self.send(self.client.delete(url)).await?).await
+    }
+
+    pub async fn archive(&self, TicketId(id): TicketId) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}/archive", self.base_url, id))?;
+
+        decode(self.send(self.client.post(url)).await?).await
+    }
+
+    pub async fn restore(&self, TicketId(id): TicketId) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}/restore", self.base_url, id))?;
+
+        decode(self.send(self.client.post(url)).await?).await
+    }
+
+    // Same as `patch`, with a version it only goes through if nobody got there first.
+    pub async fn reopen(&self, TicketId(id): TicketId, version: Option<u64>) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}/reopen", self.base_url, id))?;
+
+        let mut request = self.client.post(url);
+
+        if let Some(version) = version {
+            request = request.header(header::IF_MATCH, etag(version));
+        }
+
+        decode(self.send(request).await?).await
+    }
+
+    pub async fn add_label(&self, TicketId(id): TicketId, label: &Label) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}/labels/{}", self.base_url, id, label))?;
+
+        decode(self.send(self.client.put(url)).await?).await
+    }
+
+    pub async fn remove_label(&self, TicketId(id): TicketId, label: &Label) -> Result<Ticket> {
+
+        let url = Url::parse(&format!("{}/{}/labels/{}", self.base_url, id, label))?;
+
+        decode(self.send(self.client.delete(url)).await?).await
+    }
+
+    pub async fn link(&self, TicketId(id): TicketId, draft: &LinkDraft) -> Result<Link> {
+
+        let url = Url::parse(&format!("{}/{}/links", self.base_url, id))?;
+
+        decode(self.send(self.client.post(url).json(draft)).await?).await
+    }
+
+    pub async fn unlink(&self, link: Link) -> Result<Link> {
+
+        let url = Url::parse(&format!("{}/{}/links/{}/{}", self.base_url, link.from, link.kind, link.to))?;
+
+        decode(self.send(self.client.delete(url)).await?).await
+    }
+
+    pub async fn links(&self, TicketId(id): TicketId) -> Result<Vec<Link>> {
+
+        let url = Url::parse(&format!("{}/{}/links", self.base_url, id))?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    pub async fn history(&self, TicketId(id): TicketId) -> Result<Vec<HistoryEntry>> {
+
+        let url = Url::parse(&format!("{}/{}/history", self.base_url, id))?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    pub async fn add_webhook(&self, draft: &WebhookDraft) -> Result<Webhook> {
+
+        let url = self.base_url.join("/webhooks")?;
+
+        decode(self.send(self.client.post(url).json(draft)).await?).await
+    }
+
+    pub async fn webhooks(&self) -> Result<Vec<Webhook>> {
+
+        let url = self.base_url.join("/webhooks")?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    pub async fn remove_webhook(&self, id: WebhookId) -> Result<Webhook> {
+
+        let url = self.base_url.join(&format!("/webhooks/{id}"))?;
+
+        decode(self.send(self.client.delete(url)).await?).await
+    }
+
+    // Events that never made it to their webhook, oldest first.
+    pub async fn dead_letters(&self) -> Result<Vec<DeadLetter>> {
+
+        let url = self.base_url.join("/webhooks/dead_letters")?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    pub async fn add_comment(&self, TicketId(id): TicketId, draft: &CommentDraft) -> Result<Comment> {
+
+        let url = Url::parse(&format!("{}/{}/comments", self.base_url, id))?;
+
+        decode(self.send(self.client.post(url).json(draft)).await?).await
+    }
+
+    pub async fn comments(&self, TicketId(id): TicketId) -> Result<Vec<Comment>> {
+
+        let url = Url::parse(&format!("{}/{}/comments", self.base_url, id))?;
+
+        decode(self.send(self.client.get(url)).await?).await
+    }
+
+    pub async fn edit_comment(&self, TicketId(id): TicketId, comment: CommentId, draft: &CommentDraft)
+        -> Result<Comment>
+    {
+        let url = Url::parse(&format!("{}/{}/comments/{}", self.base_url, id, comment))?;
+
+        decode(self.send(self.client.patch(url).json(draft)).await?).await
+    }
+
+    pub async fn delete_comment(&self, TicketId(id): TicketId, comment: CommentId) -> Result<Comment> {
+
+        let url = Url::parse(&format!("{}/{}/comments/{}", self.base_url, id, comment))?;
+
+        decode(self.send(self.client.delete(url)).await?).await
+    }
+
+    // Tries again after a dropped connection, a timeout or a status that says to,
+    // as long as sending the request twice can't do anything it wouldn't once.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut request = request.build()?;
+        if let Some(timeout) = self.timeout {
+            *request.timeout_mut() = Some(timeout);
+        }
+
+        // The server turns down a versioned patch once the first one went through.
+        let idempotent = request.method() == Method::GET
+            || (request.method() == Method::PATCH && request.headers().contains_key(header::IF_MATCH));
+
+        let mut attempt = 1;
+        loop {
+            // Bodies that are streamed can't be sent again.
+            let next = (idempotent && attempt < self.retry.attempts).then(|| request.try_clone()).flatten();
+            let Some(next) = next else {
+                return Ok(self.client.execute(request).await?)
+            };
+
+            match self.client.execute(request).await {
+                Ok(response) if !is_transient(response.status()) => return Ok(response),
+                Err(error) if !error.is_connect() && !error.is_timeout() => return Err(error.into()),
+                _ => {}
+            }
+
+            tokio::time::sleep(self.retry.wait(attempt)).await;
+            request = next;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+async fn decode<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    Ok(check(response).await?.json().await?)
+}
+
+// Anything but a success becomes an error. Problem details turn back into the
+// error the server had, e.g. `Error::NotFound`, anything else comes back as the
+// status and whatever the server said.
+async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    if response.status().is_success() {
+        return Ok(response)
+    }
+
+    let status = response.status();
+    let body = response.text().await?;
+
+    match serde_json::from_str::<Problem>(&body) {
+        Ok(problem) => Err(Error::from_problem(problem)),
+        Err(_) => Err(Error::HttpStatusCode(status, body)),
+    }
+}
+
+struct Subscription {
+    client: reqwest::Client,
+    url: String,
+    reconnect: Retry,
+    filter: EventFilter,
+    response: Option<reqwest::Response>,
+    buffer: Vec<u8>,
+    pending: VecDeque<Event>,
+}
+
+impl Subscription {
+    async fn next(&mut self) -> Result<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                self.filter.after = Some(event.seq);
+                self.filter.epoch = Some(event.epoch);
+                return Ok(event)
+            }
+
+            let Some(response) = self.response.as_mut() else {
+                self.response = Some(self.connect().await?);
+                continue
+            };
+
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    self.buffer.extend_from_slice(&chunk);
+                    self.parse()?;
+                }
+                // Dropped, whatever half-sent event is left goes with it.
+                Ok(None) | Err(_) => {
+                    self.response = None;
+                    self.buffer.clear();
+                }
+            }
+        }
+    }
+
+    // Keeps trying while the server can't be reached or says to try again later,
+    // the way `Client::send` does, until `reconnect` runs out of attempts.
+    async fn connect(&self) -> Result<reqwest::Response> {
+        let mut attempt = 1;
+        loop {
+            let response = self.client.get(&self.url)
+                .query(&self.filter)
+                .header(header::ACCEPT, "text/event-stream")
+                .send().await;
+
+            let transient = match &response {
+                Ok(response) => is_transient(response.status()),
+                Err(error) => error.is_connect() || error.is_timeout() || error.is_request(),
+            };
+
+            if !transient || attempt >= self.reconnect.attempts {
+                return check(response?).await
+            }
+
+            tokio::time::sleep(self.reconnect.wait(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    // Takes every complete event out of the buffer. Keep-alive comments and the
+    // `id`/`event` fields are skipped, the data carries all of it anyway.
+    fn parse(&mut self) -> Result<()> {
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let frame = self.buffer.drain(..end + 2).collect::<Vec<_>>();
+
+            let data = String::from_utf8_lossy(&frame)
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(|data| data.strip_prefix(' ').unwrap_or(data))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if !data.is_empty() {
+                self.pending.push_back(serde_json::from_str(&data)?);
+            }
+        }
+
+        Ok(())
     }
 }
 
 impl Default for Client {
     fn default() -> Self { Self::new() }
 }
+
+#[derive(Debug)]
+#[must_use = "nothing is fetched until the request is awaited, or sent with `all` or `page`"]
+// Shared with the blocking client, which only brings its own `page` and `all`.
+pub struct ListTickets<'a, C = Client> {
+    client: &'a C,
+    query: TicketQuery,
+}
+
+impl<C> ListTickets<'_, C> {
+    pub fn status(mut self, status: Status) -> Self {
+        self.query.status = Some(status);
+        self
+    }
+
+    pub fn matching(mut self, text: impl Into<String>) -> Self {
+        self.query.text = Some(text.into());
+        self
+    }
+
+    pub fn sort_by(mut self, sort: SortBy) -> Self {
+        self.query.sort = sort;
+        self
+    }
+
+    pub fn after(mut self, cursor: TicketId) -> Self {
+        self.query.after = Some(cursor);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.query.limit = Some(limit);
+        self
+    }
+
+    pub fn label(mut self, label: Label) -> Self {
+        self.query.label = Some(label);
+        self
+    }
+
+    pub fn archived(mut self, archived: bool) -> Self {
+        self.query.archived = archived;
+        self
+    }
+}
+
+impl ListTickets<'_> {
+    pub async fn page(&self) -> Result<Page> {
+        let request = self.client.client.get(self.client.base_url.as_ref()).query(&self.query);
+
+        decode(self.client.send(request).await?).await
+    }
+
+    pub async fn all(mut self) -> Result<Vec<Ticket>> {
+        let mut tickets = Vec::new();
+
+        loop {
+            let page = self.page().await?;
+            tickets.extend(page.tickets);
+
+            match page.next_cursor {
+                Some(cursor) => self.query.after = Some(cursor),
+                None => return Ok(tickets),
+            }
+        }
+    }
+}
+
+impl<'a> IntoFuture for ListTickets<'a> {
+    type Output = Result<Vec<Ticket>>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + Send + 'a>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.all())
+    }
+}
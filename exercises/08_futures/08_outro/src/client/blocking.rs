@@ -0,0 +1,73 @@
+// The same client for programs that don't run tokio themselves. Every call is the
+// async one run to completion on a runtime of the client's own, like `reqwest::blocking`
+// does, so it panics when called from within an async context.
+
+use tokio::runtime::{self, Runtime};
+
+use crate::{
+    client::{self, ClientBuilder},
+    data::{Page, Ticket, TicketDraft, TicketId, TicketPatch, TicketQuery},
+    error::Result,
+};
+
+pub type ListTickets<'a> = client::ListTickets<'a, Client>;
+
+#[derive(Debug)]
+pub struct Client {
+    inner: client::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self::with_addr(client::Client::DEFAULT_URL).expect("Default url format is invalid!")
+    }
+
+    pub fn with_addr(addr: impl AsRef<str>) -> Result<Self> {
+        Self::builder().addr(addr.as_ref()).build_blocking()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    pub fn with_client(inner: client::Client) -> Result<Self> {
+        let runtime = runtime::Builder::new_current_thread().enable_all().build()?;
+        Ok(Self { inner, runtime })
+    }
+
+    // Call `all` to fetch every ticket, page by page, or `page` for a single page.
+    pub fn list_all(&self) -> ListTickets<'_> {
+        ListTickets { client: self, query: TicketQuery::default() }
+    }
+
+    pub fn create(&self, draft: &TicketDraft) -> Result<TicketId> {
+        self.runtime.block_on(self.inner.create(draft))
+    }
+
+    pub fn retrieve(&self, id: TicketId) -> Result<Ticket> {
+        self.runtime.block_on(self.inner.retrieve(id))
+    }
+
+    pub fn patch(&self, patch: TicketPatch) -> Result<Ticket> {
+        self.runtime.block_on(self.inner.patch(patch))
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self { Self::new() }
+}
+
+impl ListTickets<'_> {
+    pub fn page(&self) -> Result<Page> {
+        self.client.runtime.block_on(self.unblocked().page())
+    }
+
+    pub fn all(self) -> Result<Vec<Ticket>> {
+        self.client.runtime.block_on(self.unblocked().all())
+    }
+
+    fn unblocked(&self) -> client::ListTickets<'_> {
+        client::ListTickets { client: &self.client.inner, query: self.query.clone() }
+    }
+}
@@ -0,0 +1,198 @@
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use url::Url;
+
+use crate::{
+    client::{blocking, Client},
+    error::{Error, Result},
+    problem::FieldError,
+    store::actor,
+};
+
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How hard to try before giving up on a request. The wait after the n-th failed
+// attempt is a random share, between half and all, of `initial_backoff * 2^(n - 1)`
+// capped at `max_backoff`, so clients that failed together don't retry together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    // The first one included, so 1 never retries.
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl Retry {
+    // For the event feed, more patient than the default so it outlasts a server
+    // restart: about half a minute of trying in all.
+    pub const RECONNECT: Self = Self {
+        attempts: 10,
+        initial_backoff: Duration::from_millis(250),
+        max_backoff: Duration::from_secs(5),
+    };
+
+    pub fn never() -> Self {
+        Self { attempts: 1, ..Default::default() }
+    }
+
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+
+    pub fn wait(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        backoff / 2 + backoff.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+// Everything about a client that can be set before it's built. Unless told otherwise
+// it talks to `Client::DEFAULT_URL`, with `Retry::default()` for requests,
+// `Retry::RECONNECT` for the event feed and the timeouts above.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    addr: String,
+    actor: Option<String>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    retry: Retry,
+    reconnect: Retry,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            addr: Client::DEFAULT_URL.into(),
+            actor: None,
+            connect_timeout: Some(DEFAULT_CONNECT_TIMEOUT),
+            timeout: Some(DEFAULT_TIMEOUT),
+            retry: Retry::default(),
+            reconnect: Retry::RECONNECT,
+            // Same as reqwest's own defaults.
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn addr(mut self, addr: impl Into<String>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    // Who the changes are made on behalf of, as the ticket history will show it.
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    // How long to wait for the server to accept the connection, `None` waits for good.
+    pub fn connect_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    // How long a single attempt at a request gets, the response body included.
+    // The event feed isn't held to it, it's meant to stay open.
+    pub fn timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    // Only requests that can safely go twice are retried: GETs, and PATCHes sent with
+    // If-Match, which the server turns down once the first one went through.
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // How hard the event feed tries to get back after its connection dropped.
+    // Each drop starts over with the first attempt.
+    pub fn reconnect(mut self, reconnect: Retry) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    // How long an unused connection is kept around for the next request.
+    pub fn pool_idle_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.pool_idle_timeout = timeout.into();
+        self
+    }
+
+    // 0 opens a new connection for every request.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let mut client = reqwest::Client::builder()
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host);
+
+        if let Some(timeout) = self.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+
+        if let Some(actor) = self.actor {
+            let value = HeaderValue::from_str(&actor)
+                .map_err(|error| Error::Validation(vec![FieldError::new("actor", "invalid_value", error)]))?;
+            client = client.default_headers(HeaderMap::from_iter([(HeaderName::from_static(actor::HEADER), value)]));
+        }
+
+        Ok(Client {
+            client: client.build()?,
+            base_url: Url::parse(&format!("http://{}/tickets", self.addr))?,
+            timeout: self.timeout,
+            retry: self.retry,
+            reconnect: self.reconnect,
+        })
+    }
+
+    pub fn build_blocking(self) -> Result<blocking::Client> {
+        blocking::Client::with_client(self.build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_waits_are_jittered_within_the_backoff() {
+        let retry = Retry {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        let backoffs = (1..=5).map(|attempt| retry.backoff(attempt).as_millis()).collect::<Vec<_>>();
+        assert_eq!(backoffs, vec![100, 200, 400, 500, 500]);
+
+        for attempt in 1..=5 {
+            for _ in 0..100 {
+                let backoff = retry.backoff(attempt);
+                let wait = retry.wait(attempt);
+                assert!(backoff / 2 <= wait && wait <= backoff, "Waited {wait:?} for a backoff of {backoff:?}");
+            }
+        }
+    }
+}
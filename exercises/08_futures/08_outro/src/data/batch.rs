@@ -0,0 +1,95 @@
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::{
+    data::{Ticket, TicketDescription, TicketDraft, TicketPatch, TicketTitle},
+    error::{Error, Result},
+    problem::Problem,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchMode {
+    // All or nothing, the first failing item rolls the whole batch back.
+    #[default]
+    Atomic,
+    // Every item stands on its own, failures are reported next to the rest.
+    PerItem,
+}
+
+// Tagged by `op`, e.g. `{"op": "patch", "id": 3, "status": "Done"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOp {
+    Create(TicketDraft),
+    Patch(TicketPatch),
+}
+
+impl BatchOp {
+    // Titles and descriptions are plain strings on the wire, so they're checked again here.
+    pub fn validate(&self) -> Result<()> {
+        let (title, description) = match self {
+            Self::Create(draft) => (Some(&draft.title), Some(&draft.description)),
+            Self::Patch(patch) => (patch.title.as_ref(), patch.description.as_ref()),
+        };
+
+        if let Some(title) = title {
+            TicketTitle::try_from(title.to_string())?;
+        }
+        if let Some(description) = description {
+            TicketDescription::try_from(description.to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Batch {
+    #[serde(default)]
+    pub mode: BatchMode,
+    pub ops: Vec<BatchOp>,
+}
+
+// One per item, in the same order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOutcome {
+    Created(Ticket),
+    Patched(Ticket),
+    // Only ever in `PerItem` mode. The problem the item would've been turned
+    // down with on its own.
+    Failed(Problem),
+}
+
+impl BatchOutcome {
+    // Failures come back as the error the server had, e.g. `Error::NotFound`.
+    pub fn into_result(self) -> Result<Ticket> {
+        match self {
+            Self::Created(ticket) | Self::Patched(ticket) => Ok(ticket),
+            Self::Failed(problem) => Err(Error::from_problem(problem)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{Status, TicketId};
+    use super::*;
+
+    #[test]
+    fn check_if_batches_read_from_tagged_json() {
+        let batch: Batch = serde_json::from_str(r#"{
+            "ops": [
+                {"op": "create", "title": "New", "description": "Fresh."},
+                {"op": "patch", "id": 3, "status": "Done", "priority": null}
+            ]
+        }"#).unwrap();
+
+        assert_eq!(batch.mode, BatchMode::Atomic);
+        assert!(matches!(&batch.ops[0], BatchOp::Create(draft) if draft.title.to_string() == "New"));
+
+        let BatchOp::Patch(patch) = &batch.ops[1] else { panic!("Expected a patch, got {:?}", batch.ops[1]) };
+        assert_eq!((patch.id, patch.status, patch.priority), (TicketId(3), Some(Status::Done), Some(None)));
+    }
+}
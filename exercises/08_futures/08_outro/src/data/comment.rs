@@ -0,0 +1,158 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use thiserror;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::data::TicketId;
+
+pub const MAX_COMMENT_LEN: usize = 5000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ToSchema)]
+pub struct CommentId(pub u64);
+
+impl From<u64> for CommentId {
+    fn from(value: u64) -> Self {
+        CommentId(value)
+    }
+}
+
+impl Display for CommentId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct CommentBody(String);
+
+
+impl TryFrom<&str> for CommentBody {
+    type Error = CommentBodyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.to_string();
+        validate(&value)?;
+        Ok(CommentBody(value))
+    }
+}
+
+
+impl TryFrom<String> for CommentBody {
+    type Error = CommentBodyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        validate(&value)?;
+        Ok(CommentBody(value))
+    }
+}
+
+
+impl Display for CommentBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &self.0)
+    }
+}
+fn validate(value: &str) -> Result<(), CommentBodyError> {
+    if value.trim().is_empty() {
+        Err(CommentBodyError::NoBody)
+    } else if value.len() > MAX_COMMENT_LEN {
+        Err(CommentBodyError::BodyTooLong)
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+pub enum CommentBodyError {
+    #[error("Comment body is empty!")]
+    NoBody,
+    #[error("Comment body is too long! It must be {MAX_COMMENT_LEN} bytes!")]
+    BodyTooLong,
+}
+
+// What a client sends to post or edit a comment.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct CommentDraft {
+    pub body: CommentBody,
+}
+
+impl CommentDraft {
+    pub fn with<T: AsRef<str>>(body: T) -> Result<Self, CommentBodyError> {
+        Ok(Self { body: CommentBody::try_from(body.as_ref())? })
+    }
+
+    // Deserializing doesn't go through `TryFrom`, so stores check again on write.
+    pub fn validate(&self) -> Result<(), CommentBodyError> {
+        validate(&self.body.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct Comment {
+    pub id: CommentId,
+    pub ticket_id: TicketId,
+    pub body: CommentBody,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_its_possible_to_create_comment_body_from_str(){
+        assert_eq!(
+            CommentBody::try_from("Seen it twice").unwrap(),
+            CommentBody("Seen it twice".into()));
+    }
+
+    #[test]
+    fn check_if_blank_comment_body_errors(){
+        assert_eq!(
+            CommentBody::try_from(" \n ").unwrap_err().to_string(),
+            "Comment body is empty!".to_string()
+        )
+    }
+
+    #[test]
+    fn check_if_long_comment_body_errors(){
+        let value: String = (0..=MAX_COMMENT_LEN).map(|_| "a").collect();
+        assert_eq!(
+            CommentBody::try_from(value).unwrap_err().to_string(),
+            format!("Comment body is too long! It must be {MAX_COMMENT_LEN} bytes!")
+        )
+    }
+
+    #[test]
+    fn check_json_serde_for_comment() {
+        let c = Comment {
+            id: CommentId(2),
+            ticket_id: TicketId(9),
+            body: CommentBody::try_from("Needs more cats.").unwrap(),
+            created_at: "2024-03-01T09:00:00Z".parse().unwrap(),
+            updated_at: "2024-03-01T09:05:00Z".parse().unwrap(),
+        };
+
+        let ser = serde_json::to_string(&c).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"id":2,"ticket_id":9,"body":"Needs more cats.","#,
+                r#""created_at":"2024-03-01T09:00:00Z","updated_at":"2024-03-01T09:05:00Z"}"#,
+            ),
+            ser,
+            "Serialization failed for {c:?}"
+        );
+
+        let de: Comment = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, c, "Deserialization failed for {c:?}");
+    }
+
+    #[test]
+    fn check_if_deserialized_draft_can_still_be_validated() {
+        let draft: CommentDraft = serde_json::from_str(r#"{"body":"  "}"#).unwrap();
+        assert_eq!(draft.validate(), Err(CommentBodyError::NoBody));
+        assert!(CommentDraft::with("Fine.").unwrap().validate().is_ok());
+    }
+}
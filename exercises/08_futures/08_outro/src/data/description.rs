@@ -1,10 +1,11 @@
 use std::fmt::{Display, Formatter};
 use thiserror;
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
 
 pub const MAX_DESCRIPTION_LEN: usize = 500;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 pub struct TicketDescription(String);
 
 
@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::data::{Status, Ticket, TicketId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Created,
+    Patched,
+    Deleted,
+}
+
+impl Display for EventKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Created => "created",
+            Self::Patched => "patched",
+            Self::Deleted => "deleted",
+        })
+    }
+}
+
+// A change to a ticket as the server saw it. Sequence numbers start at 1 and go
+// up by one per event, so the last one seen is all a client needs to resume.
+// They start over whenever the server does, the epoch tells one run from the next.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Event {
+    pub epoch: u64,
+    pub seq: u64,
+    pub kind: EventKind,
+    // The ticket after the change, or as it was for a deletion.
+    pub ticket: Ticket,
+}
+
+impl Event {
+    // Sent as the SSE id, so a browser's `Last-Event-ID` carries the epoch too.
+    pub fn id(&self) -> String {
+        format!("{}:{}", self.epoch, self.seq)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<TicketId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    // Only events with a higher sequence number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<u64>,
+    // The epoch `after` was counted in. A cursor from another run of the server
+    // is turned down rather than read as a sequence number of this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<u64>,
+    // Skips the backlog and starts with the next change. Ignored along with `after`,
+    // which says where to start already.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub live: bool,
+}
+
+impl EventFilter {
+    // Resumes from the last event seen, i.e. its `Event::id`.
+    pub fn resume(&mut self, id: &str) {
+        let (epoch, seq) = match id.split_once(':') {
+            Some((epoch, seq)) => (epoch.parse().ok(), seq),
+            None => (None, id),
+        };
+
+        if let Ok(seq) = seq.parse() {
+            self.after = Some(seq);
+            self.epoch = epoch;
+        }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.after.is_none_or(|after| event.seq > after)
+            && self.id.is_none_or(|id| event.ticket.id == id)
+            && self.status.is_none_or(|status| event.ticket.status == status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_filters_match_on_every_field_given() {
+        let ticket = Ticket::with(TicketId(3), "Watched", "Closely.", Status::InProgress).unwrap();
+        let event = Event { epoch: 1, seq: 7, kind: EventKind::Patched, ticket };
+
+        assert!(EventFilter::default().matches(&event));
+        let every = EventFilter {
+            id: Some(TicketId(3)),
+            status: Some(Status::InProgress),
+            after: Some(6),
+            epoch: Some(1),
+            live: false,
+        };
+        assert!(every.matches(&event));
+
+        assert!(!EventFilter { id: Some(TicketId(4)), ..Default::default() }.matches(&event));
+        assert!(!EventFilter { status: Some(Status::Done), ..Default::default() }.matches(&event));
+        assert!(!EventFilter { after: Some(7), ..Default::default() }.matches(&event));
+    }
+
+    #[test]
+    fn check_if_filters_resume_from_an_event_id() {
+        let ticket = Ticket::with(TicketId(3), "Watched", "Closely.", Status::InProgress).unwrap();
+        let event = Event { epoch: 12, seq: 7, kind: EventKind::Patched, ticket };
+
+        let mut filter = EventFilter::default();
+        filter.resume(&event.id());
+        assert_eq!((filter.after, filter.epoch), (Some(7), Some(12)));
+
+        let mut filter = EventFilter::default();
+        filter.resume("5");
+        assert_eq!((filter.after, filter.epoch), (Some(5), None));
+
+        let mut filter = EventFilter::default();
+        filter.resume("garbage");
+        assert_eq!(filter, EventFilter::default());
+    }
+}
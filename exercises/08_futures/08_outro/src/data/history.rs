@@ -0,0 +1,181 @@
+use std::collections::BTreeSet;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::data::{Label, Priority, Status, Ticket, TicketDescription, TicketTitle};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    Patched,
+    Archived,
+    Restored,
+    Reopened,
+    Deleted,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct Change<T> {
+    pub old: Option<T>,
+    pub new: T,
+}
+
+// One change to a ticket, as recorded by the store. Only the fields that actually
+// changed are filled in, and entries are never edited once written.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct HistoryEntry {
+    pub version: u64,
+    pub at: DateTime<Utc>,
+    pub action: Action,
+    // Whoever the request was made on behalf of, if it said.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<Change<TicketTitle>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<Change<TicketDescription>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Change<Status>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Change<BTreeSet<Label>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Change<Option<Priority>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<Change<Option<NaiveDate>>>,
+}
+
+fn change<T: Clone + PartialEq>(old: Option<&T>, new: &T) -> Option<Change<T>> {
+    if old == Some(new) {
+        None
+    } else {
+        Some(Change { old: old.cloned(), new: new.clone() })
+    }
+}
+
+impl HistoryEntry {
+    // What it took to get from `old` to `new`, or from nothing if it was just created.
+    pub fn between(old: Option<&Ticket>, new: &Ticket, at: DateTime<Utc>) -> Self {
+        let action = match old {
+            None => Action::Created,
+            Some(old) if !old.archived && new.archived => Action::Archived,
+            Some(old) if old.archived && !new.archived => Action::Restored,
+            Some(_) => Action::Patched,
+        };
+
+        Self {
+            version: new.version,
+            at,
+            action,
+            actor: None,
+            title: change(old.map(|t| &t.title), &new.title),
+            description: change(old.map(|t| &t.description), &new.description),
+            status: change(old.map(|t| &t.status), &new.status),
+            // Fields a ticket can start out without are only recorded once they're set.
+            labels: match old {
+                None if new.labels.is_empty() => None,
+                _ => change(old.map(|t| &t.labels), &new.labels),
+            },
+            priority: match old {
+                None if new.priority.is_none() => None,
+                _ => change(old.map(|t| &t.priority), &new.priority),
+            },
+            due_date: match old {
+                None if new.due_date.is_none() => None,
+                _ => change(old.map(|t| &t.due_date), &new.due_date),
+            },
+        }
+    }
+
+    // The last entry a ticket gets. It outlives the ticket, so the history still
+    // says what happened to it.
+    pub fn deleted(old: &Ticket, at: DateTime<Utc>) -> Self {
+        Self {
+            version: old.version + 1,
+            at,
+            action: Action::Deleted,
+            actor: None,
+            title: None,
+            description: None,
+            status: None,
+            labels: None,
+            priority: None,
+            due_date: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn check_if_creation_records_every_field() {
+        let ticket = Ticket::with(3.into(), "New", "Brand new.", Status::ToDo).unwrap();
+
+        let entry = HistoryEntry::between(None, &ticket, at());
+
+        assert_eq!(entry.action, Action::Created);
+        assert_eq!(entry.version, 1);
+        assert_eq!(entry.title, Some(Change { old: None, new: ticket.title.clone() }));
+        assert_eq!(entry.status, Some(Change { old: None, new: Status::ToDo }));
+        assert_eq!(entry.labels, None);
+    }
+
+    #[test]
+    fn check_if_label_changes_are_recorded_as_whole_sets() {
+        let old = Ticket::with(3.into(), "Same", "Same.", Status::ToDo).unwrap();
+        let mut new = Ticket { version: 2, ..old.clone() };
+        new.labels.insert(Label::try_from("docs").unwrap());
+
+        let entry = HistoryEntry::between(Some(&old), &new, at());
+
+        assert_eq!(entry.action, Action::Patched);
+        assert_eq!(entry.labels, Some(Change { old: Some(BTreeSet::new()), new: new.labels.clone() }));
+        assert_eq!(entry.status, None);
+    }
+
+    #[test]
+    fn check_if_patch_records_only_changed_fields() {
+        let old = Ticket::with(3.into(), "Same", "Before.", Status::ToDo).unwrap();
+        let new = Ticket {
+            description: TicketDescription::try_from("After.").unwrap(),
+            status: Status::InProgress,
+            version: 2,
+            ..old.clone()
+        };
+
+        let entry = HistoryEntry::between(Some(&old), &new, at());
+
+        assert_eq!(entry.action, Action::Patched);
+        assert_eq!(entry.title, None);
+        assert_eq!(entry.status, Some(Change { old: Some(Status::ToDo), new: Status::InProgress }));
+        assert_eq!(
+            entry.description,
+            Some(Change { old: Some(old.description.clone()), new: new.description.clone() })
+        );
+    }
+
+    #[test]
+    fn check_json_serde_for_history_entry() {
+        let old = Ticket::with(3.into(), "Same", "Same.", Status::ToDo).unwrap();
+        let new = Ticket { status: Status::Done, version: 2, ..old.clone() };
+
+        let entry = HistoryEntry::between(Some(&old), &new, at());
+
+        let ser = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            r#"{"version":2,"at":"2023-11-14T22:13:20Z","action":"patched","status":{"old":"To-do","new":"Done"}}"#,
+            ser,
+            "Serialization failed for {entry:?}"
+        );
+
+        let de: HistoryEntry = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, entry, "Deserialization failed for {entry:?}");
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::{Display, Formatter};
+use thiserror;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+pub const MAX_LABEL_LEN: usize = 32;
+
+// Lowercase ASCII letters, digits, `-` and `_`, so a label is the same however it
+// was typed and can go in a URL as is.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(try_from = "String")]
+pub struct Label(String);
+
+
+impl TryFrom<&str> for Label {
+    type Error = LabelError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let value = value.trim().to_ascii_lowercase();
+        validate(&value)?;
+        Ok(Label(value))
+    }
+}
+
+
+impl TryFrom<String> for Label {
+    type Error = LabelError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+
+impl Display for Label {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &self.0)
+    }
+}
+fn validate(value: &str) -> Result<(), LabelError> {
+    if value.is_empty() {
+        Err(LabelError::NoLabel)
+    } else if value.len() > MAX_LABEL_LEN {
+        Err(LabelError::LabelTooLong)
+    } else if let Some(c) = value.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_')) {
+        Err(LabelError::InvalidCharacter(c))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+pub enum LabelError {
+    #[error("Label is empty!")]
+    NoLabel,
+    #[error("Label is too long! It must be {MAX_LABEL_LEN} bytes!")]
+    LabelTooLong,
+    #[error("Label contains {0:?}! Only letters, digits, '-' and '_' are allowed.")]
+    InvalidCharacter(char),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_labels_are_normalised_to_lowercase(){
+        assert_eq!(Label::try_from(" Backend ").unwrap(), Label("backend".into()));
+        assert_eq!(Label::try_from("good-first_issue2".to_string()).unwrap(), Label("good-first_issue2".into()));
+    }
+
+    #[test]
+    fn check_if_invalid_labels_error(){
+        assert_eq!(Label::try_from("  ").unwrap_err(), LabelError::NoLabel);
+        assert_eq!(Label::try_from("front end").unwrap_err(), LabelError::InvalidCharacter(' '));
+        assert_eq!(Label::try_from("a,b").unwrap_err(), LabelError::InvalidCharacter(','));
+
+        let value: String = (0..=MAX_LABEL_LEN).map(|_| "a").collect();
+        assert_eq!(
+            Label::try_from(value).unwrap_err().to_string(),
+            format!("Label is too long! It must be {MAX_LABEL_LEN} bytes!")
+        )
+    }
+
+    #[test]
+    fn check_json_serde_for_label() {
+        let ser = serde_json::to_string(&Label::try_from("Docs").unwrap()).unwrap();
+        assert_eq!(ser, r#""docs""#);
+
+        assert_eq!(serde_json::from_str::<Label>(r#""Docs""#).unwrap(), Label("docs".into()));
+        assert!(serde_json::from_str::<Label>(r#""no way""#).is_err(), "Labels are validated when deserialized");
+    }
+}
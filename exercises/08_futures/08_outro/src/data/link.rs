@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::{
+    data::TicketId,
+    error::{Error, Result},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    // `from` has to be done before `to` can be.
+    Blocks,
+    Duplicates,
+    // `to` is a sub-task of `from`.
+    ParentOf,
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Blocks => "blocks",
+            Self::Duplicates => "duplicates",
+            Self::ParentOf => "parent_of",
+        })
+    }
+}
+
+impl TryFrom<&str> for LinkKind {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "blocks" => Ok(Self::Blocks),
+            "duplicates" => Ok(Self::Duplicates),
+            "parent_of" => Ok(Self::ParentOf),
+            _ => Err(Error::InvalidLink(format!("Unknown kind of link: {value:?}."))),
+        }
+    }
+}
+
+// Reads as "`from` <kind> `to`", e.g. 3 blocks 5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct Link {
+    pub kind: LinkKind,
+    pub from: TicketId,
+    pub to: TicketId,
+}
+
+// What a client sends to link a ticket to another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+pub struct LinkDraft {
+    pub kind: LinkKind,
+    pub to: TicketId,
+}
+
+impl Link {
+    pub fn touches(&self, id: TicketId) -> bool {
+        self.from == id || self.to == id
+    }
+
+    // Everything that makes a new link invalid next to the ones already there, apart
+    // from either ticket not existing, which only the store can tell.
+    pub fn check<'a>(&self, existing: impl IntoIterator<Item = &'a Link>) -> Result<()> {
+        if self.from == self.to {
+            return Err(Error::InvalidLink(format!("Ticket {} cannot be linked to itself.", self.from)))
+        }
+
+        let mut edges: BTreeMap<TicketId, Vec<TicketId>> = BTreeMap::new();
+
+        for link in existing.into_iter().filter(|link| link.kind == self.kind) {
+            if self.kind == LinkKind::ParentOf && link.to == self.to && link.from != self.from {
+                return Err(Error::InvalidLink(format!(
+                    "Ticket {} already is a sub-task of ticket {}.", self.to, link.from
+                )))
+            }
+            edges.entry(link.from).or_default().push(link.to);
+        }
+
+        // Only blocking and parent chains have a direction that can loop back.
+        if self.kind == LinkKind::Duplicates {
+            return Ok(())
+        }
+
+        match path(&edges, self.to, self.from) {
+            Some(mut path) => {
+                path.insert(0, self.from);
+                Err(Error::LinkCycle { kind: self.kind, path })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// The rule for moving a ticket to done, given whichever of its blockers and
+// sub-tasks aren't done themselves.
+pub fn check_done(id: TicketId, blockers: Vec<TicketId>, children: Vec<TicketId>) -> Result<()> {
+    if blockers.is_empty() && children.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::OpenDependencies { id, blockers, children })
+    }
+}
+
+// Depth first, so the path found isn't necessarily the shortest one.
+fn path(edges: &BTreeMap<TicketId, Vec<TicketId>>, start: TicketId, goal: TicketId) -> Option<Vec<TicketId>> {
+    let mut seen = BTreeSet::from([start]);
+    let mut stack = vec![vec![start]];
+
+    while let Some(path) = stack.pop() {
+        let last = *path.last()?;
+
+        if last == goal {
+            return Some(path)
+        }
+
+        for next in edges.get(&last).into_iter().flatten() {
+            if seen.insert(*next) {
+                let mut longer = path.clone();
+                longer.push(*next);
+                stack.push(longer);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(kind: LinkKind, from: u64, to: u64) -> Link {
+        Link { kind, from: TicketId(from), to: TicketId(to) }
+    }
+
+    #[test]
+    fn check_if_blocking_cycles_are_refused_with_their_path() {
+        let existing = [link(LinkKind::Blocks, 1, 2), link(LinkKind::Blocks, 2, 3), link(LinkKind::ParentOf, 3, 1)];
+
+        match link(LinkKind::Blocks, 3, 1).check(&existing) {
+            Err(Error::LinkCycle { kind: LinkKind::Blocks, path }) => {
+                assert_eq!(path, vec![TicketId(3), TicketId(1), TicketId(2), TicketId(3)])
+            }
+            other => panic!("Expected a cycle, got {other:?}"),
+        }
+
+        assert!(link(LinkKind::Blocks, 1, 3).check(&existing).is_ok(), "Shortcuts aren't cycles");
+        assert!(link(LinkKind::Duplicates, 3, 1).check(&existing).is_ok());
+    }
+
+    #[test]
+    fn check_if_parent_chains_cannot_loop_or_share_children() {
+        let existing = [link(LinkKind::ParentOf, 1, 2), link(LinkKind::ParentOf, 2, 3)];
+
+        assert!(matches!(link(LinkKind::ParentOf, 3, 1).check(&existing), Err(Error::LinkCycle { .. })));
+        assert!(matches!(link(LinkKind::ParentOf, 4, 3).check(&existing), Err(Error::InvalidLink(_))));
+        assert!(link(LinkKind::ParentOf, 2, 3).check(&existing).is_ok(), "Linking twice changes nothing");
+        assert!(link(LinkKind::ParentOf, 3, 4).check(&existing).is_ok());
+    }
+
+    #[test]
+    fn check_if_self_links_are_refused() {
+        assert!(matches!(link(LinkKind::Duplicates, 1, 1).check(&[]), Err(Error::InvalidLink(_))));
+    }
+
+    #[test]
+    fn check_json_serde_for_link() {
+        let l = link(LinkKind::ParentOf, 1, 2);
+
+        let ser = serde_json::to_string(&l).unwrap();
+        assert_eq!(r#"{"kind":"parent_of","from":1,"to":2}"#, ser, "Serialization failed for {l:?}");
+
+        let de: Link = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, l, "Deserialization failed for {l:?}");
+    }
+}
@@ -1,16 +1,39 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use utoipa::ToSchema;
 pub mod title;
 pub mod description;
 pub mod status;
+pub mod query;
+pub mod history;
+pub mod workflow;
+pub mod comment;
+pub mod label;
+pub mod priority;
+pub mod link;
+pub mod event;
+pub mod webhook;
+pub mod batch;
 
 
 pub use title::TicketTitle;
 pub use description::TicketDescription;
 pub use status::Status;
+pub use query::{overdue, Page, SortBy, TicketQuery};
+pub use history::HistoryEntry;
+pub use workflow::Workflow;
+pub use comment::{Comment, CommentBody, CommentDraft, CommentId};
+pub use label::Label;
+pub use priority::Priority;
+pub use link::{Link, LinkDraft, LinkKind};
+pub use event::{Event, EventFilter, EventKind};
+pub use webhook::{DeadLetter, Missed, Webhook, WebhookDraft, WebhookId};
+pub use batch::{Batch, BatchMode, BatchOp, BatchOutcome};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default,Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default,Serialize, Deserialize, ToSchema)]
 pub struct TicketId(pub u64);
 
 impl From<u64> for TicketId {
@@ -19,12 +42,35 @@ impl From<u64> for TicketId {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+// Versions travel over HTTP as strong entity tags, e.g. `"3"`.
+pub fn etag(version: u64) -> String {
+    format!("\"{version}\"")
+}
+
+pub fn version_from_etag(etag: &str) -> Option<u64> {
+    etag.trim().strip_prefix('"')?.strip_suffix('"')?.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 pub struct Ticket {
     pub id: TicketId,
     pub title: TicketTitle,
     pub description: TicketDescription,
-    pub status: Status
+    pub status: Status,
+    // Bumped on every change, starting from 1.
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub labels: BTreeSet<Label>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
+    // Maintained by the store.
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub status_changed_at: DateTime<Utc>,
 }
 
 impl Ticket {
@@ -43,32 +89,100 @@ impl Ticket {
             title: TicketTitle::try_from(title.into())?,
             description: TicketDescription::try_from(description.into())?,
             status,
+            version: 1,
+            archived: false,
+            labels: BTreeSet::new(),
+            priority: None,
+            due_date: None,
+            created_at: DateTime::default(),
+            updated_at: DateTime::default(),
+            status_changed_at: DateTime::default(),
         })
     }
+
+    // Still open on a day after it was due.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.status != Status::Done && self.due_date.is_some_and(|due| due < today)
+    }
+
+    // Stamps a ticket that was just changed from `previous`.
+    pub fn touch(&mut self, previous: &Ticket, at: DateTime<Utc>) {
+        self.updated_at = at;
+
+        if self.status != previous.status {
+            self.status_changed_at = at;
+        }
+    }
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 pub struct TicketDraft {
     pub title: TicketTitle,
-    pub description: TicketDescription
+    pub description: TicketDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
 }
 
 impl TicketDraft {
     pub fn with<T: AsRef<str>>(title: T , desc: T) -> Result<Self> {
         Ok(Self {
             title: TicketTitle::try_from(title.as_ref())?,
-            description: TicketDescription::try_from(desc.as_ref())?
+            description: TicketDescription::try_from(desc.as_ref())?,
+            priority: None,
+            due_date: None,
         })
     }
+
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn due_on(mut self, due_date: NaiveDate) -> Self {
+        self.due_date = Some(due_date);
+        self
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ToSchema)]
 pub struct TicketPatch {
     pub id: TicketId,
     pub title: Option<TicketTitle>,
     pub description: Option<TicketDescription>,
-    pub status: Option<Status>
+    pub status: Option<Status>,
+    // `Some(None)` clears the field, sent as an explicit `null`.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub priority: Option<Option<Priority>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "present")]
+    pub due_date: Option<Option<NaiveDate>>,
+    // Only applied if the ticket is still at this version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+}
+
+// Tells a field that's `null` apart from one that's missing, which `default` takes care of.
+fn present<'de, T, D>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl TicketPatch {
+    pub fn check_version(&self, ticket: &Ticket) -> Result<()> {
+        match self.version {
+            Some(expected) if expected != ticket.version => Err(Error::VersionMismatch {
+                id: ticket.id,
+                expected,
+                actual: ticket.version,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -93,7 +207,9 @@ mod tests {
     fn check_json_serde_for_ticket_draft() {
         let t = TicketDraft {
             title: TicketTitle::try_from("Hello There!").unwrap(),
-            description: TicketDescription::try_from("A Star Wars Story.").unwrap()
+            description: TicketDescription::try_from("A Star Wars Story.").unwrap(),
+            priority: None,
+            due_date: None,
         };
 
         let ser = serde_json::to_string(&t).unwrap();
@@ -113,12 +229,46 @@ mod tests {
             id: TicketId(33),
             title: TicketTitle::try_from("Jimmy").unwrap(),
             description: TicketDescription::try_from("A Neutron Story.").unwrap(),
-            status: Status::InProgress
+            status: Status::InProgress,
+            version: 4,
+            archived: false,
+            labels: BTreeSet::new(),
+            priority: None,
+            due_date: None,
+            created_at: "2024-03-01T09:00:00Z".parse().unwrap(),
+            updated_at: "2024-03-04T17:30:00.250Z".parse().unwrap(),
+            status_changed_at: "2024-03-02T10:15:00Z".parse().unwrap(),
+        };
+
+        let ser = serde_json::to_string(&t).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"id":33,"title":"Jimmy","description":"A Neutron Story.","status":"In progress","version":4,"#,
+                r#""created_at":"2024-03-01T09:00:00Z","updated_at":"2024-03-04T17:30:00.250Z","#,
+                r#""status_changed_at":"2024-03-02T10:15:00Z"}"#,
+            ),
+            ser,
+            "Serialization failed for {t:?}"
+        );
+
+        let de: Ticket = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, t, "Deserialization failed for {t:?}");
+    }
+
+    #[test]
+    fn check_json_serde_for_archived_ticket() {
+        let t = Ticket {
+            archived: true,
+            ..Ticket::with(TicketId(7), "Old news", "Nobody cares anymore.", Status::Done).unwrap()
         };
 
         let ser = serde_json::to_string(&t).unwrap();
         assert_eq!(
-            r#"{"id":33,"title":"Jimmy","description":"A Neutron Story.","status":"In progress"}"#,
+            concat!(
+                r#"{"id":7,"title":"Old news","description":"Nobody cares anymore.","status":"Done","version":1,"#,
+                r#""archived":true,"created_at":"1970-01-01T00:00:00Z","updated_at":"1970-01-01T00:00:00Z","#,
+                r#""status_changed_at":"1970-01-01T00:00:00Z"}"#,
+            ),
             ser,
             "Serialization failed for {t:?}"
         );
@@ -133,7 +283,10 @@ mod tests {
             id: TicketId(33),
             title: None,
             description: None,
-            status: Some(Status::Done)
+            status: Some(Status::Done),
+            priority: None,
+            due_date: None,
+            version: None,
         };
 
         let ser = serde_json::to_string(&t).unwrap();
@@ -146,4 +299,50 @@ mod tests {
         let de: TicketPatch = serde_json::from_str(&ser).unwrap();
         assert_eq!(de, t, "Deserialization failed for {t:?}");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_etag_round_trip() {
+        assert_eq!(etag(7), r#""7""#);
+        assert_eq!(version_from_etag(&etag(7)), Some(7));
+        assert_eq!(version_from_etag("7"), None);
+        assert_eq!(version_from_etag(r#"W/"7""#), None);
+        assert_eq!(version_from_etag("*"), None);
+    }
+
+    #[test]
+    fn check_json_serde_for_ticket_patch_clearing_fields() {
+        let t = TicketPatch {
+            id: TicketId(33),
+            priority: Some(None),
+            due_date: Some(Some(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())),
+            ..Default::default()
+        };
+
+        let ser = serde_json::to_string(&t).unwrap();
+        assert_eq!(
+            r#"{"id":33,"title":null,"description":null,"status":null,"priority":null,"due_date":"2024-03-01"}"#,
+            ser,
+            "Serialization failed for {t:?}"
+        );
+
+        let de: TicketPatch = serde_json::from_str(&ser).unwrap();
+        assert_eq!(de, t, "Deserialization failed for {t:?}");
+
+        let de: TicketPatch = serde_json::from_str(r#"{"id":33,"title":null,"description":null,"status":null}"#).unwrap();
+        assert_eq!((de.priority, de.due_date), (None, None), "Missing fields are left alone");
+    }
+
+    #[test]
+    fn check_if_only_open_tickets_past_their_due_date_are_overdue() {
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let due = |status, day| Ticket {
+            due_date: NaiveDate::from_ymd_opt(2024, 3, day),
+            ..Ticket::with(TicketId(1), "Due", "Some day.", status).unwrap()
+        };
+
+        assert!(due(Status::InProgress, 9).is_overdue(today));
+        assert!(!due(Status::InProgress, 10).is_overdue(today), "Due today isn't late yet");
+        assert!(!due(Status::Done, 9).is_overdue(today));
+        assert!(!Ticket::with(TicketId(1), "Whenever", "No rush.", Status::ToDo).unwrap().is_overdue(today));
+    }
+}
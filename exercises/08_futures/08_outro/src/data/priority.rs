@@ -0,0 +1,86 @@
+use std::fmt::{Display, Formatter, Result};
+use thiserror;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+// Ordered from least to most urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        })
+    }
+}
+
+impl TryFrom<&str> for Priority {
+    type Error = PriorityError;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value.trim().to_lowercase().as_str() {
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            "critical" => Ok(Self::Critical),
+            _ => Err(PriorityError::ParseError(value.into()))
+        }
+    }
+}
+
+impl TryFrom<String> for Priority {
+    type Error = PriorityError;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq, Clone)]
+pub enum PriorityError{
+    #[error("Failed to parse \"{0}\" into Priority type.")]
+    ParseError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_priorities_are_ordered_by_urgency() {
+        assert!(Priority::Low < Priority::Medium);
+        assert!(Priority::Medium < Priority::High);
+        assert!(Priority::High < Priority::Critical);
+    }
+
+    #[test]
+    fn check_if_its_possible_to_create_priority_from_string() {
+        assert_eq!(Priority::try_from(" High "), Ok(Priority::High));
+        assert_eq!(Priority::try_from("CRITICAL".to_string()), Ok(Priority::Critical));
+        assert_eq!(
+            Priority::try_from("Urgent").unwrap_err().to_string(),
+            "Failed to parse \"Urgent\" into Priority type.".to_string()
+        );
+    }
+
+    #[test]
+    fn check_json_serde_for_priority() {
+        for priority in [Priority::Low, Priority::Medium, Priority::High, Priority::Critical] {
+            let ser = serde_json::to_string(&priority).unwrap();
+            assert_eq!(ser, format!(r#""{priority}""#), "Serialization failed for {priority:?}");
+
+            let de: Priority = serde_json::from_str(&ser).unwrap();
+            assert_eq!(de, priority, "Deserialization failed for {priority:?}");
+        }
+    }
+}
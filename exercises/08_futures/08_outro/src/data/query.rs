@@ -0,0 +1,262 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    data::{Label, Status, Ticket, TicketId},
+    error::{Error, Result},
+};
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Id,
+    Title,
+    Status,
+}
+
+// Filters, ordering and position of a page of tickets. The cursor is the id of
+// the last ticket of the previous page, which works for every ordering since ties
+// are always broken by id.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TicketQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    // Case-insensitive (ASCII only) substring of the title or description.
+    #[serde(default, rename = "q", skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Label>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub sort: SortBy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<TicketId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub archived: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Page {
+    pub tickets: Vec<Ticket>,
+    pub next_cursor: Option<TicketId>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl TicketQuery {
+    pub fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        (self.archived || !ticket.archived)
+            && self.status.is_none_or(|status| ticket.status == status)
+            && self.label.as_ref().is_none_or(|label| ticket.labels.contains(label))
+            && self.text.as_deref().is_none_or(|text| {
+                let text = text.to_ascii_lowercase();
+                ticket.title.to_string().to_ascii_lowercase().contains(&text)
+                    || ticket.description.to_string().to_ascii_lowercase().contains(&text)
+            })
+    }
+
+    pub fn compare(&self, a: &Ticket, b: &Ticket) -> Ordering {
+        let by_field = match self.sort {
+            SortBy::Id => Ordering::Equal,
+            SortBy::Title => a.title.cmp(&b.title),
+            SortBy::Status => a.status.cmp(&b.status),
+        };
+
+        by_field.then(a.id.cmp(&b.id))
+    }
+
+    // Evaluates the query against every ticket in a store, archived or not, so the
+    // cursor can be found even if it no longer matches the filters. Only the tickets
+    // that end up on the page get cloned.
+    pub fn paginate<T: Borrow<Ticket>>(&self, tickets: impl IntoIterator<Item = T>) -> Result<Page> {
+        let tickets: Vec<_> = tickets.into_iter().collect();
+
+        let cursor = self.after.map(|after| {
+            (after, tickets.iter().map(Borrow::borrow).find(|t: &&Ticket| t.id == after))
+        });
+
+        // Tickets sorted by id don't need the cursor to still be around, any other
+        // ordering needs its sort key.
+        if let Some((after, None)) = cursor {
+            if self.sort != SortBy::Id {
+                return Err(Error::InvalidCursor(after))
+            }
+        }
+
+        let mut matching: Vec<&Ticket> = tickets
+            .iter()
+            .map(Borrow::borrow)
+            .filter(|t| self.matches(t))
+            .filter(|t| match cursor {
+                None => true,
+                Some((_, Some(c))) => self.compare(t, c).is_gt(),
+                Some((after, None)) => t.id > after,
+            })
+            .collect();
+        matching.sort_by(|a, b| self.compare(a, b));
+        matching.truncate(self.page_size() + 1);
+
+        Ok(self.page(matching.into_iter().cloned().collect()))
+    }
+
+    // Turns up to `page_size() + 1` sorted tickets into a page.
+    pub fn page(&self, mut tickets: Vec<Ticket>) -> Page {
+        let next_cursor = if tickets.len() > self.page_size() {
+            tickets.truncate(self.page_size());
+            tickets.last().map(|t| t.id)
+        } else {
+            None
+        };
+
+        Page { tickets, next_cursor }
+    }
+}
+
+// Open, unarchived tickets past their due date. The most urgent come first, then
+// whichever has been late the longest, ties broken by id.
+pub fn overdue(tickets: impl IntoIterator<Item = Ticket>, today: NaiveDate) -> Vec<Ticket> {
+    let mut tickets: Vec<_> = tickets
+        .into_iter()
+        .filter(|t| !t.archived && t.is_overdue(today))
+        .collect();
+
+    tickets.sort_by(|a, b| {
+        b.priority.cmp(&a.priority)
+            .then(a.due_date.cmp(&b.due_date))
+            .then(a.id.cmp(&b.id))
+    });
+
+    tickets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tickets() -> Vec<Ticket> {
+        vec![
+            Ticket::with(0.into(), "Cats", "The musical.", Status::Done).unwrap(),
+            Ticket::with(1.into(), "Dogs", "Not a musical.", Status::ToDo).unwrap(),
+            Ticket::with(2.into(), "Birds", "About CATS, somehow.", Status::InProgress).unwrap(),
+            Ticket::with(3.into(), "Ants", "Tiny.", Status::ToDo).unwrap(),
+        ]
+    }
+
+    fn ids(page: &Page) -> Vec<u64> {
+        page.tickets.iter().map(|t| t.id.0).collect()
+    }
+
+    #[test]
+    fn check_if_tickets_are_filtered_by_status_and_text() {
+        let query = TicketQuery { status: Some(Status::ToDo), ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets()).unwrap()), vec![1, 3]);
+
+        let query = TicketQuery { text: Some("cats".into()), ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets()).unwrap()), vec![0, 2]);
+    }
+
+    #[test]
+    fn check_if_tickets_are_filtered_by_label() {
+        let mut tickets = tickets();
+        tickets[1].labels.insert(Label::try_from("pets").unwrap());
+        tickets[3].labels.insert(Label::try_from("pets").unwrap());
+        tickets[3].labels.insert(Label::try_from("tiny").unwrap());
+
+        let query = TicketQuery { label: Some(Label::try_from("pets").unwrap()), ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets.clone()).unwrap()), vec![1, 3]);
+
+        let query = TicketQuery { label: Some(Label::try_from("tiny").unwrap()), ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets).unwrap()), vec![3]);
+
+        assert!(serde_urlencoded::from_str::<TicketQuery>("label=not+valid").is_err());
+    }
+
+    #[test]
+    fn check_if_tickets_are_sorted_with_ties_broken_by_id() {
+        let query = TicketQuery { sort: SortBy::Title, ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets()).unwrap()), vec![3, 2, 0, 1]);
+
+        let query = TicketQuery { sort: SortBy::Status, ..Default::default() };
+        assert_eq!(ids(&query.paginate(tickets()).unwrap()), vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn check_if_cursor_walks_through_every_page() {
+        let mut query = TicketQuery { sort: SortBy::Title, limit: Some(3), ..Default::default() };
+
+        let page = query.paginate(tickets()).unwrap();
+        assert_eq!(ids(&page), vec![3, 2, 0]);
+        assert_eq!(page.next_cursor, Some(TicketId(0)));
+
+        query.after = page.next_cursor;
+        let page = query.paginate(tickets()).unwrap();
+        assert_eq!(ids(&page), vec![1]);
+        assert_eq!(page.next_cursor, None);
+    }
+
+    #[test]
+    fn check_if_unknown_cursor_only_errors_when_its_sort_key_is_needed() {
+        let query = TicketQuery { after: Some(TicketId(1)), ..Default::default() };
+        let remaining: Vec<_> = tickets().into_iter().filter(|t| t.id != TicketId(1)).collect();
+        assert_eq!(ids(&query.paginate(remaining).unwrap()), vec![2, 3]);
+
+        let query = TicketQuery { after: Some(TicketId(42)), sort: SortBy::Title, ..Default::default() };
+        assert!(matches!(query.paginate(tickets()), Err(Error::InvalidCursor(TicketId(42)))));
+    }
+
+    #[test]
+    fn check_url_encoding_for_ticket_query() {
+        let query = TicketQuery {
+            status: Some(Status::InProgress),
+            text: Some("cats".into()),
+            label: Some(Label::try_from("musicals").unwrap()),
+            sort: SortBy::Title,
+            after: Some(TicketId(4)),
+            limit: Some(10),
+            archived: false,
+        };
+
+        let ser = serde_urlencoded::to_string(&query).unwrap();
+        assert_eq!(ser, "status=In+progress&q=cats&label=musicals&sort=title&after=4&limit=10");
+
+        let de: TicketQuery = serde_urlencoded::from_str(&ser).unwrap();
+        assert_eq!(de, query);
+        assert_eq!(serde_urlencoded::from_str::<TicketQuery>("").unwrap(), TicketQuery::default());
+    }
+
+    #[test]
+    fn check_if_overdue_tickets_are_ordered_by_priority_then_due_date() {
+        use crate::data::Priority;
+
+        let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let mut tickets = tickets();
+        for (ticket, (priority, day)) in tickets.iter_mut().zip([
+            (Some(Priority::Critical), 1),
+            (None, 1),
+            (Some(Priority::Low), 2),
+            (Some(Priority::Low), 1),
+        ]) {
+            ticket.priority = priority;
+            ticket.due_date = NaiveDate::from_ymd_opt(2024, 3, day);
+        }
+
+        // Ticket 0 is done, so it's not late, whatever its priority.
+        let ids: Vec<_> = overdue(tickets, today).iter().map(|t| t.id.0).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+}
@@ -1,8 +1,9 @@
 use std::fmt::{Display, Formatter, Result};
 use thiserror;
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 pub enum Status {
     #[default]
     #[serde(rename = "To-do")]
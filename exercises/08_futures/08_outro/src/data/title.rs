@@ -1,10 +1,11 @@
 use std::fmt::{Display, Formatter};
 use thiserror;
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
 
 pub const MAX_TITLE_LEN: usize = 50;
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
 pub struct TicketTitle(String);
 
 impl TryFrom<&str> for TicketTitle {
@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use sha2::Sha256;
+use url::Url;
+
+use crate::{
+    data::Event,
+    error::{Error, Result},
+};
+
+// Carries `signature` of the request body, so receivers can check it came from us.
+pub const SIGNATURE_HEADER: &str = "x-ticket-signature";
+// The event's kind, e.g. `patched`.
+pub const EVENT_HEADER: &str = "x-ticket-event";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ToSchema)]
+pub struct WebhookId(pub u64);
+
+impl Display for WebhookId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDraft {
+    pub url: String,
+    // Shared with the receiver, it's never sent back by the server.
+    pub secret: String,
+}
+
+impl WebhookDraft {
+    pub fn with(url: impl Into<String>, secret: impl Into<String>) -> Result<Self> {
+        let draft = Self { url: url.into(), secret: secret.into() };
+        draft.validate()?;
+        Ok(draft)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let url = Url::parse(&self.url).map_err(|e| Error::InvalidWebhook(format!("{}: {e}", self.url)))?;
+
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::InvalidWebhook(format!("{} isn't an http or https URL.", self.url)))
+        }
+
+        if self.secret.is_empty() {
+            return Err(Error::InvalidWebhook("The secret can't be empty.".into()))
+        }
+
+        Ok(())
+    }
+}
+
+// Every ticket event is POSTed to `url` as JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    // What the server hands back, the secret stays with it.
+    pub fn redacted(self) -> Self {
+        Self { secret: String::new(), ..self }
+    }
+}
+
+// An event that never made it to a webhook, after every attempt failed. Or events
+// the webhooks fell too far behind on to ever get, which are gone for good, so only
+// their sequence numbers are left to say what's missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetter {
+    pub webhook: WebhookId,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<Event>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missed: Option<Missed>,
+    // 0 for missed events, there was nothing left to send.
+    pub attempts: u32,
+    // Why the last attempt failed.
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+// Both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Missed {
+    pub first: u64,
+    pub last: u64,
+}
+
+// `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the secret.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_signatures_match_the_reference_hmac() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn check_if_drafts_need_an_http_url_and_a_secret() {
+        assert!(WebhookDraft::with("https://example.com/hooks", "s3cret").is_ok());
+        assert!(matches!(WebhookDraft::with("example.com", "s3cret"), Err(Error::InvalidWebhook(_))));
+        assert!(matches!(WebhookDraft::with("ftp://example.com", "s3cret"), Err(Error::InvalidWebhook(_))));
+        assert!(matches!(WebhookDraft::with("http://example.com", ""), Err(Error::InvalidWebhook(_))));
+    }
+}
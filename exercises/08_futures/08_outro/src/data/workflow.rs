@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    data::{Status, Ticket},
+    error::{Error, Result},
+};
+
+// Which status changes the store lets through. Moves are what a patch can do,
+// reopening is kept apart so that finished work can only come back on purpose,
+// through `TicketRepository::reopen`, and only along the edges allowed here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workflow {
+    moves: BTreeMap<Status, BTreeSet<Status>>,
+    reopens: BTreeMap<Status, Status>,
+}
+
+impl Workflow {
+    // Nothing is allowed until it's added with `allow` or `allow_reopen`.
+    pub fn empty() -> Self {
+        Self { moves: BTreeMap::new(), reopens: BTreeMap::new() }
+    }
+
+    pub fn allow(mut self, from: Status, to: Status) -> Self {
+        self.moves.entry(from).or_default().insert(to);
+        self
+    }
+
+    pub fn allow_reopen(mut self, from: Status, to: Status) -> Self {
+        self.reopens.insert(from, to);
+        self
+    }
+
+    pub fn next(&self, from: Status) -> Vec<Status> {
+        self.moves.get(&from).into_iter().flatten().copied().collect()
+    }
+
+    // Staying put is always fine.
+    pub fn check_move(&self, ticket: &Ticket, to: Status) -> Result<()> {
+        if ticket.status == to || self.next(ticket.status).contains(&to) {
+            Ok(())
+        } else {
+            Err(Error::InvalidTransition {
+                id: ticket.id,
+                from: ticket.status,
+                to,
+                allowed: self.next(ticket.status),
+            })
+        }
+    }
+
+    pub fn reopen_target(&self, ticket: &Ticket) -> Result<Status> {
+        self.reopens.get(&ticket.status).copied().ok_or_else(|| Error::CannotReopen {
+            id: ticket.id,
+            from: ticket.status,
+            allowed: self.next(ticket.status),
+        })
+    }
+}
+
+// Work starts and stops freely and can be closed at any point, but once it's done
+// the only way back is to reopen it.
+impl Default for Workflow {
+    fn default() -> Self {
+        Self::empty()
+            .allow(Status::ToDo, Status::InProgress)
+            .allow(Status::InProgress, Status::ToDo)
+            .allow(Status::ToDo, Status::Done)
+            .allow(Status::InProgress, Status::Done)
+            .allow_reopen(Status::Done, Status::ToDo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticket(status: Status) -> Ticket {
+        Ticket::with(5.into(), "Flowing", "Through the workflow.", status).unwrap()
+    }
+
+    #[test]
+    fn check_if_default_workflow_only_leaves_done_by_reopening() {
+        let workflow = Workflow::default();
+
+        assert!(workflow.check_move(&ticket(Status::ToDo), Status::InProgress).is_ok());
+        assert!(workflow.check_move(&ticket(Status::InProgress), Status::Done).is_ok());
+        assert!(workflow.check_move(&ticket(Status::Done), Status::Done).is_ok());
+
+        match workflow.check_move(&ticket(Status::Done), Status::ToDo) {
+            Err(Error::InvalidTransition { from: Status::Done, to: Status::ToDo, allowed, .. }) => {
+                assert!(allowed.is_empty())
+            }
+            other => panic!("Expected an invalid transition, got {other:?}"),
+        }
+
+        assert_eq!(workflow.reopen_target(&ticket(Status::Done)).unwrap(), Status::ToDo);
+    }
+
+    #[test]
+    fn check_if_reopen_is_refused_for_open_tickets() {
+        match Workflow::default().reopen_target(&ticket(Status::InProgress)) {
+            Err(Error::CannotReopen { from: Status::InProgress, allowed, .. }) => {
+                assert_eq!(allowed, vec![Status::ToDo, Status::Done])
+            }
+            other => panic!("Expected reopening to fail, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn check_if_custom_workflow_is_enforced() {
+        let workflow = Workflow::empty()
+            .allow(Status::ToDo, Status::InProgress)
+            .allow(Status::InProgress, Status::Done);
+
+        assert!(workflow.check_move(&ticket(Status::ToDo), Status::Done).is_err());
+        assert!(workflow.reopen_target(&ticket(Status::Done)).is_err());
+        assert_eq!(workflow.next(Status::ToDo), vec![Status::InProgress]);
+    }
+}
@@ -1,10 +1,10 @@
+use std::borrow::Cow;
 use axum::{
     response::{IntoResponse, Response},
-    http::StatusCode,
-    Json,
+    http::{header, HeaderValue, StatusCode},
 };
-use serde_json::json;
 use crate::data::*;
+use crate::problem::{self, FieldError, Problem};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -16,32 +16,307 @@ pub enum Error{
     UrlParse(#[from] url::ParseError),
     #[error("JSON parse error: {0}")]
     JsonParse(#[from] serde_json::Error),
+    #[error("Malformed JSON: {0}")]
+    MalformedJson(serde_json::Error),
     #[error("{0}: {1}")]
     HttpStatusCode(StatusCode, String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Storage error: {0}")]
+    Storage(String),
+    #[error("The server couldn't respond within {0:?}.")]
+    Timeout(std::time::Duration),
+    #[error("The request didn't arrive within {0:?}.")]
+    RequestTimeout(std::time::Duration),
+    #[error("Background task error: {0}")]
+    Task(#[from] tokio::task::JoinError),
     #[error("Ticket title error: {0}")]
     Title(#[from] title::TicketTitleError),
     #[error("Ticket description error: {0}")]
     Description(#[from] description::TicketDescriptionError),
     #[error("Ticket status error: {0}")]
-    Status(#[from] status::StatusError)
+    Status(#[from] status::StatusError),
+    #[error("Ticket priority error: {0}")]
+    Priority(#[from] priority::PriorityError),
+    #[error("Ticket due date error: {0}")]
+    DueDate(#[from] chrono::ParseError),
+    #[error("Comment body error: {0}")]
+    CommentBody(#[from] comment::CommentBodyError),
+    #[error("Label error: {0}")]
+    Label(#[from] label::LabelError),
+    #[error("The request has {} invalid field(s): {}", .0.len(), fields(.0))]
+    Validation(Vec<FieldError>),
+    #[error("Cannot find ticket with id: {0}.")]
+    NotFound(TicketId),
+    #[error("Cannot find comment {comment} on ticket {ticket}.")]
+    CommentNotFound { ticket: TicketId, comment: CommentId },
+    #[error("Ticket {} has no {} link to ticket {}.", .0.from, .0.kind, .0.to)]
+    LinkNotFound(Link),
+    #[error("Cannot find webhook with id: {0}.")]
+    WebhookNotFound(WebhookId),
+    #[error("Item {index} of the batch failed, nothing was applied: {error}")]
+    BatchFailed { index: usize, error: Box<Error> },
+    #[error("Cannot continue after ticket {0}, it no longer exists.")]
+    InvalidCursor(TicketId),
+    #[error("Events after {after} are no longer available, list the tickets again and follow the feed from there.")]
+    MissedEvents { after: u64 },
+    #[error("Ticket {id} was expected at version {expected}, but it's at version {actual}.")]
+    VersionMismatch { id: TicketId, expected: u64, actual: u64 },
+    #[error("Ticket {id} cannot move from {from} to {to}. It can only move to: {}.", list(.allowed))]
+    InvalidTransition { id: TicketId, from: Status, to: Status, allowed: Vec<Status> },
+    #[error("Ticket {id} cannot be reopened while it's {from}. It can only move to: {}.", list(.allowed))]
+    CannotReopen { id: TicketId, from: Status, allowed: Vec<Status> },
+    #[error("Invalid webhook: {0}")]
+    InvalidWebhook(String),
+    #[error("Invalid link: {0}")]
+    InvalidLink(String),
+    #[error("Cannot add that link, it would close a cycle of {kind} links: {}.", ids(.path, " -> "))]
+    LinkCycle { kind: link::LinkKind, path: Vec<TicketId> },
+    #[error(
+        "Ticket {id} cannot be done yet. Still open blockers: [{}], sub-tasks: [{}].",
+        ids(.blockers, ", "), ids(.children, ", ")
+    )]
+    OpenDependencies { id: TicketId, blockers: Vec<TicketId>, children: Vec<TicketId> },
+}
+
+fn ids(ids: &[TicketId], separator: &str) -> String {
+    ids.iter().map(TicketId::to_string).collect::<Vec<_>>().join(separator)
+}
+
+fn fields(errors: &[FieldError]) -> String {
+    errors.iter().map(|error| format!("{}: {}", error.field, error.message)).collect::<Vec<_>>().join(" ")
+}
+
+fn list(statuses: &[Status]) -> String {
+    if statuses.is_empty() {
+        return "nothing".into()
+    }
+    statuses.iter().map(Status::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Request(_) => StatusCode::BAD_GATEWAY,
+            Self::MalformedJson(_) => StatusCode::BAD_REQUEST,
+            Self::HttpStatusCode(status, _) => *status,
+            Self::Sqlite(error) if is_busy(error) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Timeout(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::RequestTimeout(_) => StatusCode::REQUEST_TIMEOUT,
+            Self::Title(_) | Self::Description(_) | Self::Status(_) | Self::Priority(_) | Self::DueDate(_)
+                | Self::CommentBody(_) | Self::Label(_) | Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) | Self::CommentNotFound { .. } | Self::LinkNotFound(_) | Self::WebhookNotFound(_) => {
+                StatusCode::NOT_FOUND
+            }
+            // Fails the same way the item would have on its own.
+            Self::BatchFailed { error, .. } => error.status(),
+            Self::InvalidCursor(_) | Self::InvalidWebhook(_) | Self::InvalidLink(_) => StatusCode::BAD_REQUEST,
+            Self::MissedEvents { .. } => StatusCode::GONE,
+            Self::VersionMismatch { .. } => StatusCode::PRECONDITION_FAILED,
+            Self::InvalidTransition { .. } | Self::CannotReopen { .. } | Self::LinkCycle { .. }
+                | Self::OpenDependencies { .. } => StatusCode::CONFLICT,
+            Self::UrlParse(_) | Self::JsonParse(_) | Self::Io(_) | Self::Sqlite(_) | Self::Csv(_) | Self::Storage(_)
+                | Self::Task(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // Stable, unlike the messages, so clients can match on it.
+    pub fn code(&self) -> Cow<'static, str> {
+        let code = match self {
+            Self::Request(_) => "upstream_failed",
+            Self::UrlParse(_) => "invalid_url",
+            Self::JsonParse(_) => "json_failed",
+            Self::MalformedJson(_) => "malformed_json",
+            Self::HttpStatusCode(status, _) => return problem::status_code(*status).into(),
+            Self::Io(_) => "io_failed",
+            Self::Sqlite(error) if is_busy(error) => "storage_busy",
+            Self::Sqlite(_) | Self::Storage(_) => "storage_failed",
+            Self::Csv(_) => "csv_failed",
+            Self::Timeout(_) => "timeout",
+            Self::RequestTimeout(_) => "request_timeout",
+            Self::Task(_) => "task_failed",
+            Self::Title(_) | Self::Description(_) | Self::Status(_) | Self::Priority(_) | Self::DueDate(_)
+                | Self::CommentBody(_) | Self::Label(_) | Self::Validation(_) => "validation_failed",
+            Self::NotFound(_) => "not_found",
+            Self::CommentNotFound { .. } => "comment_not_found",
+            Self::LinkNotFound(_) => "link_not_found",
+            Self::WebhookNotFound(_) => "webhook_not_found",
+            Self::BatchFailed { error, .. } => return error.code(),
+            Self::InvalidCursor(_) => "invalid_cursor",
+            Self::MissedEvents { .. } => "missed_events",
+            Self::VersionMismatch { .. } => "version_mismatch",
+            Self::InvalidTransition { .. } => "invalid_transition",
+            Self::CannotReopen { .. } => "cannot_reopen",
+            Self::InvalidWebhook(_) => "invalid_webhook",
+            Self::InvalidLink(_) => "invalid_link",
+            Self::LinkCycle { .. } => "link_cycle",
+            Self::OpenDependencies { .. } => "open_dependencies",
+        };
+        code.into()
+    }
+
+    // The fields to blame, if it's down to what was sent.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        let error = match self {
+            Self::Title(error @ title::TicketTitleError::NoTitle) => FieldError::new("title", "required", error),
+            Self::Title(error) => FieldError::new("title", "too_long", error).with_limit(title::MAX_TITLE_LEN),
+            Self::Description(error @ description::TicketDescriptionError::NoDescription) => {
+                FieldError::new("description", "required", error)
+            }
+            Self::Description(error) => {
+                FieldError::new("description", "too_long", error).with_limit(description::MAX_DESCRIPTION_LEN)
+            }
+            Self::Status(error) => FieldError::new("status", "invalid_value", error),
+            Self::Priority(error) => FieldError::new("priority", "invalid_value", error),
+            Self::DueDate(error) => FieldError::new("due_date", "invalid_date", error),
+            Self::CommentBody(error @ comment::CommentBodyError::NoBody) => FieldError::new("body", "required", error),
+            Self::CommentBody(error) => FieldError::new("body", "too_long", error).with_limit(comment::MAX_COMMENT_LEN),
+            Self::Label(error @ label::LabelError::NoLabel) => FieldError::new("label", "required", error),
+            Self::Label(error @ label::LabelError::LabelTooLong) => {
+                FieldError::new("label", "too_long", error).with_limit(label::MAX_LABEL_LEN)
+            }
+            Self::Label(error) => FieldError::new("label", "invalid_character", error),
+            Self::Validation(errors) => return errors.clone(),
+            Self::BatchFailed { index, error } => {
+                let path = format!("ops[{index}]");
+                return error.field_errors().into_iter().map(|error| error.within(&path)).collect()
+            }
+            _ => return Vec::new(),
+        };
+        vec![error]
+    }
+
+    pub fn problem(&self) -> Problem {
+        let status = self.status();
+
+        // What went wrong inside is nobody else's business.
+        let detail = if status == StatusCode::INTERNAL_SERVER_ERROR {
+            "Internal server error".to_string()
+        } else {
+            match self {
+                Self::HttpStatusCode(_, message) => message.clone(),
+                _ => self.to_string(),
+            }
+        };
+
+        let problem = Problem::new(status, &self.code(), detail).with_errors(self.field_errors());
+
+        // Enough for `from_problem` to put the error back together on the other side.
+        match self {
+            Self::BatchFailed { index, error } => {
+                let inner = error.problem();
+                problem.with("index", index).with_extensions(inner.extensions)
+            }
+            Self::NotFound(id) | Self::InvalidCursor(id) => problem.with("id", id),
+            Self::CommentNotFound { ticket, comment } => problem.with("id", ticket).with("comment_id", comment),
+            Self::LinkNotFound(link) => problem.with("link", link),
+            Self::WebhookNotFound(id) => problem.with("webhook_id", id),
+            Self::MissedEvents { after } => problem.with("after", after),
+            Self::VersionMismatch { id, expected, actual } => {
+                problem.with("id", id).with("expected", expected).with("actual", actual)
+            }
+            // Tells the client where the ticket could've gone instead.
+            Self::InvalidTransition { id, from, to, allowed } => {
+                problem.with("id", id).with("from", from).with("to", to).with("allowed", allowed)
+            }
+            Self::CannotReopen { id, from, allowed } => problem.with("id", id).with("from", from).with("allowed", allowed),
+            Self::LinkCycle { kind, path } => problem.with("kind", kind).with("path", path),
+            // Says exactly what's in the way.
+            Self::OpenDependencies { id, blockers, children } => {
+                problem.with("id", id).with("blockers", blockers).with("children", children)
+            }
+            _ => problem,
+        }
+    }
+
+    // The other way round, for clients. Anything that can't be pinned down to one
+    // of the variants comes back as the status and the detail.
+    pub fn from_problem(problem: Problem) -> Self {
+        let index = problem.extension::<usize>("index");
+        let mut errors = problem.errors.clone();
+
+        // Field paths are relative to the item again.
+        if let Some(index) = index {
+            let path = format!("ops[{index}].");
+            for error in &mut errors {
+                if let Some(field) = error.field.strip_prefix(&path) {
+                    error.field = field.to_string();
+                }
+            }
+        }
+
+        let error = typed(&problem, errors)
+            .unwrap_or_else(|| Self::HttpStatusCode(problem.status(), problem.detail.clone()));
+
+        match index {
+            Some(index) => Self::BatchFailed { index, error: Box::new(error) },
+            None => error,
+        }
+    }
+}
+
+// The variant a problem came from, if it carries all that variant needs.
+fn typed(problem: &Problem, errors: Vec<FieldError>) -> Option<Error> {
+    let id = || problem.extension("id");
+
+    let error = match problem.code.as_str() {
+        "validation_failed" if !errors.is_empty() => Error::Validation(errors),
+        "not_found" => Error::NotFound(id()?),
+        "comment_not_found" => Error::CommentNotFound { ticket: id()?, comment: problem.extension("comment_id")? },
+        "link_not_found" => Error::LinkNotFound(problem.extension("link")?),
+        "webhook_not_found" => Error::WebhookNotFound(problem.extension("webhook_id")?),
+        "invalid_cursor" => Error::InvalidCursor(id()?),
+        "missed_events" => Error::MissedEvents { after: problem.extension("after")? },
+        "version_mismatch" => Error::VersionMismatch {
+            id: id()?,
+            expected: problem.extension("expected")?,
+            actual: problem.extension("actual")?,
+        },
+        "invalid_transition" => Error::InvalidTransition {
+            id: id()?,
+            from: problem.extension("from")?,
+            to: problem.extension("to")?,
+            allowed: problem.extension("allowed")?,
+        },
+        "cannot_reopen" => Error::CannotReopen {
+            id: id()?,
+            from: problem.extension("from")?,
+            allowed: problem.extension("allowed")?,
+        },
+        "link_cycle" => Error::LinkCycle { kind: problem.extension("kind")?, path: problem.extension("path")? },
+        "open_dependencies" => Error::OpenDependencies {
+            id: id()?,
+            blockers: problem.extension("blockers")?,
+            children: problem.extension("children")?,
+        },
+        _ => return None,
+    };
+
+    Some(error)
+}
+
+fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        
-        let (status, message) = match self {
-            Self::JsonParse(message) => (StatusCode::BAD_REQUEST, message.to_string()),
-            Self::HttpStatusCode(status, message) => (status, message),
-            Self::Title(message) => (StatusCode::BAD_REQUEST, message.to_string()),
-            Self::Description(message) => (StatusCode::BAD_REQUEST, message.to_string()),
-            Self::Status(message) => (StatusCode::BAD_REQUEST, message.to_string()),
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".into())
-        };
+        let mut response = self.problem().into_response();
 
-        let body = Json(json!({ "error" : message }));
+        // Lets the client know which version it should've asked for.
+        if let Self::VersionMismatch { actual, .. } = self {
+            if let Ok(value) = HeaderValue::from_str(&etag(actual)) {
+                response.headers_mut().insert(header::ETAG, value);
+            }
+        }
 
-        (status, body).into_response()
+        response
     }
-}
\ No newline at end of file
+}
@@ -13,27 +13,940 @@ pub mod data;
 pub mod store;
 pub mod client;
 pub mod error;
+pub mod problem;
 pub mod server;
+pub mod transfer;
 
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddr;
     use std::time::Duration;
     use crate::client::Client;
-    use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+    use crate::data::{SortBy, Status, Ticket, TicketDraft, TicketId, TicketPatch};
     use crate::server::Server;
     use super::*;
 
     #[tokio::test]
     async fn it_works() -> error::Result<()> {
-        let server = Server::serve("127.0.0.1:20202").await?;
-        let addr = server.local_addr()?;
+        let server = Server::builder().start().await?;
+        server.ready().await;
 
+        launch_client(server.local_addr()).await?;
+
+        server.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn check_if_tickets_survive_a_restart() -> error::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        for run in 0..2 {
+            let server = Server::serve_in("127.0.0.1:0", dir.path()).await?;
+            let c = Client::with_addr(server.local_addr()?.to_string())?;
+            let server = tokio::spawn(async { server.await });
+
+            let id = c.create(&TicketDraft::with("Kept", "Across restarts.")?).await?;
+            assert_eq!(id, TicketId(run), "Ids carry on where the last run stopped");
+            assert_eq!(c.list_all().await?.len(), run as usize + 1);
+
+            server.abort();
+            let _ = server.await;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_tickets_can_be_archived_restored_and_deleted() -> error::Result<()> {
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let kept = c.create(&TicketDraft::with("Kept", "Around for good.")?).await?;
+        let doomed = c.create(&TicketDraft::with("Doomed", "Not for long.")?).await?;
+
+        let ticket = c.archive(doomed).await?;
+        assert!(ticket.archived, "Ticket archive test, ticket received is: {ticket:#?}");
+
+        let ids = |tickets: Vec<Ticket>| tickets.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids(c.list_all().await?), vec![kept]);
+        assert_eq!(ids(c.list_all().archived(true).await?), vec![kept, doomed]);
+
+        let ticket = c.restore(doomed).await?;
+        assert!(!ticket.archived, "Ticket restore test, ticket received is: {ticket:#?}");
+        assert_eq!(ids(c.list_all().await?), vec![kept, doomed]);
+
+        let ticket = c.delete(doomed).await?;
+        assert_eq!(ticket.id, doomed);
+        assert_eq!(ids(c.list_all().archived(true).await?), vec![kept]);
+        assert!(c.delete(doomed).await.is_err(), "Deleting twice should fail");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_list_all_can_filter_sort_and_page() -> error::Result<()> {
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        for title in ["Delta", "Alpha", "Charlie", "Bravo"] {
+            c.create(&TicketDraft::with(title, "Phonetic.")?).await?;
+        }
+        c.create(&TicketDraft::with("Zulu", "Off by itself.")?).await?;
+
+        let titles = |tickets: &[Ticket]| tickets.iter().map(|t| t.title.to_string()).collect::<Vec<_>>();
+
+        let page = c.list_all().matching("phonetic").sort_by(SortBy::Title).limit(3).page().await?;
+        assert_eq!(titles(&page.tickets), vec!["Alpha", "Bravo", "Charlie"]);
+
+        let cursor = page.next_cursor.expect("There should be a next page");
+        let page = c.list_all().matching("phonetic").sort_by(SortBy::Title).limit(3).after(cursor).page().await?;
+        assert_eq!(titles(&page.tickets), vec!["Delta"]);
+        assert_eq!(page.next_cursor, None);
+
+        let all = c.list_all().sort_by(SortBy::Title).limit(2).await?;
+        assert_eq!(titles(&all), vec!["Alpha", "Bravo", "Charlie", "Delta", "Zulu"]);
+
+        assert!(c.list_all().status(Status::Done).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_concurrent_patches_are_caught_by_version() -> error::Result<()> {
+        let addr = spawn_server().await?;
+        let c = Client::with_addr(addr.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Contested", "Everyone wants it.")?).await?;
+        let ticket = c.retrieve(id).await?;
+
+        let mine = TicketPatch { id, status: Some(Status::InProgress), version: Some(ticket.version), ..Default::default() };
+        let theirs = TicketPatch { id, status: Some(Status::Done), version: Some(ticket.version), ..Default::default() };
+
+        let patched = c.patch(mine).await?;
+        assert_eq!(patched.version, ticket.version + 1);
+
+        match c.patch(theirs).await {
+            Err(error::Error::VersionMismatch { expected, actual, .. }) => {
+                assert_eq!((expected, actual), (ticket.version, patched.version));
+            }
+            other => panic!("Expected a version mismatch, got {other:?}"),
+        }
+
+        let response = reqwest::get(format!("http://{addr}/tickets/{id}")).await?;
+        assert_eq!(
+            response.headers()[reqwest::header::ETAG],
+            data::etag(patched.version).as_str(),
+            "ETag should carry the version"
+        );
+
+        let response = reqwest::Client::new()
+            .patch(format!("http://{addr}/tickets/{id}"))
+            .header(reqwest::header::IF_MATCH, "not an etag")
+            .json(&serde_json::json!({ "status": "Done" }))
+            .send().await?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_history_tracks_status_changes() -> error::Result<()> {
+        use crate::data::history::{Action, Change};
+
+        let addr = spawn_server().await?.to_string();
+        let c = Client::with_addr(&addr)?;
+        let ada = Client::builder().addr(&addr).actor("ada").build()?;
+
+        let id = c.create(&TicketDraft::with("Audited", "Who did what?")?).await?;
+        ada.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await?;
+
+        let history = c.history(id).await?;
+
+        assert_eq!(history.len(), 2, "History received is: {history:#?}");
+        assert_eq!(history[0].action, Action::Created);
+        assert_eq!(history[1].status, Some(Change { old: Some(Status::ToDo), new: Status::Done }));
+        assert_eq!([history[0].actor.as_deref(), history[1].actor.as_deref()], [None, Some("ada")]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_done_tickets_can_only_come_back_through_reopen() -> error::Result<()> {
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Shipped", "Or so we thought.")?).await?;
+        c.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await?;
+
+        match c.patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() }).await {
+            Err(error::Error::InvalidTransition { from, to, allowed, .. }) => {
+                assert_eq!((from, to), (Status::Done, Status::InProgress));
+                assert_eq!(allowed, vec![]);
+            }
+            other => panic!("Expected a conflict, got {other:?}"),
+        }
+
+        match c.reopen(id, Some(1)).await {
+            Err(error::Error::VersionMismatch { expected: 1, actual: 2, .. }) => {}
+            other => panic!("Expected a stale reopen to be refused, got {other:?}"),
+        }
+
+        let ticket = c.reopen(id, Some(2)).await?;
+        assert_eq!((ticket.status, ticket.version), (Status::ToDo, 3));
+
+        assert!(c.reopen(id, None).await.is_err(), "Only done tickets can be reopened");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_comments_can_be_managed_over_http() -> error::Result<()> {
+        use crate::data::{CommentDraft, CommentId};
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Chatty", "Lots to say.")?).await?;
+        let comment = c.add_comment(id, &CommentDraft::with("Looks good to me.")?).await?;
+        assert_eq!(comment.ticket_id, id);
+
+        let edited = c.edit_comment(id, comment.id, &CommentDraft::with("Looks great to me.")?).await?;
+        assert_eq!(c.comments(id).await?, vec![edited.clone()]);
+
+        assert_eq!(c.delete_comment(id, comment.id).await?, edited);
+        assert!(c.comments(id).await?.is_empty());
+
+        match c.delete_comment(id, CommentId(42)).await {
+            Err(error::Error::CommentNotFound { ticket, comment }) => assert_eq!((ticket, comment), (id, CommentId(42))),
+            other => panic!("Expected a missing comment, got {other:?}"),
+        }
+
+        let blank = serde_json::from_str(r#"{"body":""}"#)?;
+        match c.add_comment(id, &blank).await {
+            Err(error::Error::Validation(errors)) => assert_eq!(errors[0].field, "body"),
+            other => panic!("Expected a 400, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_tickets_can_be_labelled_and_filtered_by_label() -> error::Result<()> {
+        use crate::data::Label;
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+        let docs = Label::try_from("docs")?;
+
+        let readme = c.create(&TicketDraft::with("Readme", "Write one.")?).await?;
+        c.create(&TicketDraft::with("Server", "Make it faster.")?).await?;
+        let guide = c.create(&TicketDraft::with("Guide", "Getting started.")?).await?;
+
+        c.add_label(readme, &docs).await?;
+        let ticket = c.add_label(guide, &docs).await?;
+        assert!(ticket.labels.contains(&docs));
+
+        let labelled = c.list_all().label(docs.clone()).await?;
+        assert_eq!(labelled.iter().map(|t| t.id).collect::<Vec<_>>(), vec![readme, guide]);
+
+        let ticket = c.remove_label(readme, &docs).await?;
+        assert!(ticket.labels.is_empty());
+        assert_eq!(c.list_all().label(docs).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_overdue_tickets_are_listed_by_priority() -> error::Result<()> {
+        use chrono::{NaiveDate, TimeDelta, Utc};
+        use crate::data::Priority;
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+        let last_week = Utc::now().date_naive() - TimeDelta::days(7);
+
+        let minor = c.create(&TicketDraft::with("Minor", "Can wait.")?.due_on(last_week)).await?;
+        let major = c.create(&TicketDraft::with("Major", "Can't wait.")?.due_on(last_week)).await?;
+        c.create(&TicketDraft::with("Later", "Not due yet.")?.due_on(NaiveDate::MAX)).await?;
+
+        let patch = TicketPatch { id: major, priority: Some(Some(Priority::Critical)), ..Default::default() };
+        assert_eq!(c.patch(patch).await?.priority, Some(Priority::Critical));
+
+        let overdue: Vec<_> = c.overdue().await?.into_iter().map(|t| t.id).collect();
+        assert_eq!(overdue, vec![major, minor]);
+
+        c.patch(TicketPatch { id: minor, due_date: Some(None), ..Default::default() }).await?;
+        assert_eq!(c.overdue().await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_blocked_tickets_cannot_be_done_over_http() -> error::Result<()> {
+        use crate::data::{LinkDraft, LinkKind};
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let blocker = c.create(&TicketDraft::with("Foundations", "First things first.")?).await?;
+        let blocked = c.create(&TicketDraft::with("Roof", "Last.")?).await?;
+
+        let link = c.link(blocker, &LinkDraft { kind: LinkKind::Blocks, to: blocked }).await?;
+        assert_eq!(c.links(blocked).await?, vec![link]);
+
+        match c.link(blocked, &LinkDraft { kind: LinkKind::Blocks, to: blocker }).await {
+            Err(error::Error::LinkCycle { path, .. }) => assert_eq!(path, vec![blocked, blocker, blocked]),
+            other => panic!("Expected a conflict, got {other:?}"),
+        }
+
+        match c.patch(TicketPatch { id: blocked, status: Some(Status::Done), ..Default::default() }).await {
+            Err(error::Error::OpenDependencies { blockers, .. }) => assert_eq!(blockers, vec![blocker]),
+            other => panic!("Expected a conflict, got {other:?}"),
+        }
+
+        assert_eq!(c.unlink(link).await?, link);
+        c.patch(TicketPatch { id: blocked, status: Some(Status::Done), ..Default::default() }).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_changes_are_streamed_and_can_be_resumed() -> error::Result<()> {
+        use futures_util::{StreamExt, TryStreamExt};
+        use crate::data::{EventFilter, EventKind};
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Live", "Watch it go.")?).await?;
+        c.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await?;
+        c.delete(id).await?;
+
+        let take = |filter, n| {
+            let events = c.events(filter).take(n).try_collect::<Vec<_>>();
+            async move { tokio::time::timeout(Duration::from_secs(5), events).await.expect("Events should arrive") }
+        };
+        let kinds = |events: &[data::Event]| events.iter().map(|event| event.kind).collect::<Vec<_>>();
+
+        let events = take(EventFilter::default(), 3).await?;
+        assert_eq!(kinds(&events), vec![EventKind::Created, EventKind::Patched, EventKind::Deleted]);
+        assert_eq!(events[1].ticket.status, Status::Done);
+
+        let resumed = take(EventFilter { after: Some(events[0].seq), ..Default::default() }, 2).await?;
+        assert_eq!(resumed, events[1..]);
+
+        let done = take(EventFilter { status: Some(Status::Done), ..Default::default() }, 2).await?;
+        assert_eq!(done, events[1..]);
+
+        let mut live = Box::pin(c.events(EventFilter { id: Some(data::TicketId(1)), ..Default::default() }));
+        let other = c.create(&TicketDraft::with("Later", "Only this one.")?).await?;
+
+        let event = tokio::time::timeout(Duration::from_secs(5), live.next()).await.expect("Event should arrive");
+        assert_eq!(event.expect("The feed should still be open")?.ticket.id, other);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_events_come_in_the_order_the_store_took_the_changes() -> error::Result<()> {
+        use futures_util::{StreamExt, TryStreamExt};
+        use crate::data::{EventFilter, TicketTitle};
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+        let id = c.create(&TicketDraft::with("Raced", "By everyone at once.")?).await?;
+
+        let patches = (0..20).map(|n| {
+            let title = TicketTitle::try_from(format!("Take {n}")).unwrap();
+            c.patch(TicketPatch { id, title: Some(title), ..Default::default() })
+        });
+        futures_util::future::try_join_all(patches).await?;
+
+        let events = c.events(EventFilter::default()).take(21).try_collect::<Vec<_>>();
+        let events = tokio::time::timeout(Duration::from_secs(5), events).await.expect("Events should arrive")?;
+
+        let versions = events.iter().map(|event| event.ticket.version).collect::<Vec<_>>();
+        assert_eq!(versions, (1..=21).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_the_feed_reconnects_and_says_what_it_missed() -> error::Result<()> {
+        use futures_util::StreamExt;
+        use crate::client::Retry;
+        use crate::data::EventFilter;
+
+        let server = Server::builder().addr("127.0.0.1:0").start().await?;
+        server.ready().await;
+        let addr = server.local_addr().to_string();
+
+        let reconnect = |attempts| Retry {
+            attempts,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        };
+        async fn next<S: futures_util::Stream + Unpin>(events: &mut S) -> Option<S::Item> {
+            tokio::time::timeout(Duration::from_secs(5), events.next()).await.expect("The feed should not hang")
+        }
+
+        let c = Client::builder().addr(&addr).reconnect(reconnect(50)).build()?;
+
+        let mut events = Box::pin(c.events(EventFilter::default()));
+        c.create(&TicketDraft::with("Seen", "Before the restart.")?).await?;
+        assert_eq!(next(&mut events).await.expect("The feed should be open")?.seq, 1);
+
+        // Down long enough for a few attempts to fail, the new run starts counting over.
+        server.shutdown().await?;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let server = Server::builder().addr(&addr).start().await?;
+        server.ready().await;
+
+        let missed = next(&mut events).await.expect("The feed should say why it ended");
+        assert!(matches!(missed, Err(error::Error::MissedEvents { after: 1 })), "Got: {missed:?}");
+        assert!(events.next().await.is_none());
+
+        // Gives up once the attempts run out.
+        server.shutdown().await?;
+        let c = Client::builder().addr(&addr).reconnect(reconnect(3)).build()?;
+        let mut events = Box::pin(c.events(EventFilter::default()));
+        assert!(matches!(next(&mut events).await, Some(Err(error::Error::Request(_)))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_changes_are_sent_over_websockets() -> error::Result<()> {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+        use crate::data::{Event, EventKind};
+
+        let addr = spawn_server().await?;
+        let c = Client::with_addr(addr.to_string())?;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/tickets/events/ws?status=Done"))
+            .await
+            .expect("WebSocket should connect");
+
+        let id = c.create(&TicketDraft::with("Sockets", "Straight through.")?).await?;
+        c.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await?;
+
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.expect("Event should arrive");
+
+        let Some(Ok(Message::Text(text))) = message else { panic!("Expected a text message, got {message:?}") };
+        let event: Event = serde_json::from_str(&text)?;
+
+        assert_eq!((event.seq, event.kind, event.ticket.id), (2, EventKind::Patched, id));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_webhooks_are_signed_retried_and_dead_lettered() -> error::Result<()> {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::sync::Arc;
+        use axum::{extract::{Path, State}, http::{HeaderMap, StatusCode}, routing::post, Router};
+        use tokio::sync::mpsc;
+        use crate::data::{webhook::{signature, SIGNATURE_HEADER}, Event, EventKind, WebhookDraft};
+        use crate::server::Delivery;
+        use crate::store::TicketStore;
+
+        // A stand-in receiver: `/hook` always takes it, `/flaky` only on the third try, `/down` never.
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let tries = Arc::new(AtomicU32::new(0));
+        let receiver = Router::new()
+            .route("/{path}", post(
+                |State((sender, tries)): State<(mpsc::UnboundedSender<_>, Arc<AtomicU32>)>,
+                 Path(path): Path<String>,
+                 headers: HeaderMap,
+                 body: String| async move {
+                    match path.as_str() {
+                        "down" => return StatusCode::INTERNAL_SERVER_ERROR,
+                        "flaky" if tries.fetch_add(1, Ordering::SeqCst) < 2 => return StatusCode::SERVICE_UNAVAILABLE,
+                        _ => {}
+                    }
+                    let signed = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                    sender.send((path, signed, body)).unwrap();
+                    StatusCode::NO_CONTENT
+                }
+            ))
+            .with_state((sender, tries.clone()));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let receiver_addr = listener.local_addr()?;
+        tokio::spawn(async { axum::serve(listener, receiver).await });
+
+        let delivery = Delivery {
+            attempts: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+            timeout: Duration::from_secs(1),
+        };
+        let server = Server::serve_with_delivery("127.0.0.1:0", tokio::sync::RwLock::new(TicketStore::new()), delivery).await?;
+        let c = Client::with_addr(server.local_addr()?.to_string())?;
         tokio::spawn(async { server.await });
 
-        tokio::time::sleep(Duration::from_secs(2)).await;
+        for path in ["hook", "flaky", "down"] {
+            let webhook = c.add_webhook(&WebhookDraft::with(format!("http://{receiver_addr}/{path}"), "s3cret")?).await?;
+            assert!(webhook.secret.is_empty(), "The secret must not be sent back");
+        }
+        assert_eq!(c.webhooks().await?.len(), 3);
+
+        let id = c.create(&TicketDraft::with("Hooked", "Tell everyone.")?).await?;
+
+        let mut paths = Vec::new();
+        for _ in 0..2 {
+            let (path, signed, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+                .await
+                .expect("Webhook should be delivered")
+                .unwrap();
+
+            assert_eq!(signed, signature("s3cret", body.as_bytes()));
+            let event: Event = serde_json::from_str(&body)?;
+            assert_eq!((event.kind, event.ticket.id), (EventKind::Created, id));
+            paths.push(path);
+        }
+        paths.sort();
+        assert_eq!(paths, vec!["flaky", "hook"]);
+        assert_eq!(tries.load(Ordering::SeqCst), 3);
+
+        let dead_letters = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let dead_letters = c.dead_letters().await?;
+                if !dead_letters.is_empty() {
+                    return Ok::<_, error::Error>(dead_letters)
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }).await.expect("Delivery to /down should give up")?;
+
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].url, format!("http://{receiver_addr}/down"));
+        assert_eq!((dead_letters[0].attempts, dead_letters[0].event.as_ref().map(|event| event.ticket.id)), (3, Some(id)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_batches_go_through_in_one_request() -> error::Result<()> {
+        use crate::data::{Batch, BatchMode, BatchOp, BatchOutcome};
+
+        let addr = spawn_server().await?;
+        let c = Client::with_addr(addr.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Triage", "Sort it out.")?).await?;
+
+        let ops = vec![
+            BatchOp::Create(TicketDraft::with("Found", "During triage.")?),
+            BatchOp::Patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() }),
+        ];
+        let outcomes = c.batch(&Batch { mode: BatchMode::Atomic, ops }).await?;
+        assert!(matches!(&outcomes[..], [BatchOutcome::Created(_), BatchOutcome::Patched(_)]), "Got: {outcomes:?}");
+
+        let stale = BatchOp::Patch(TicketPatch { id, status: Some(Status::Done), version: Some(1), ..Default::default() });
+        let ops = vec![BatchOp::Create(TicketDraft::with("Lost", "Rolled back.")?), stale];
+
+        match c.batch(&Batch { mode: BatchMode::Atomic, ops: ops.clone() }).await {
+            Err(error::Error::BatchFailed { index: 1, error }) => {
+                assert!(matches!(*error, error::Error::VersionMismatch { expected: 1, actual: 2, .. }), "{error:?}");
+            }
+            other => panic!("Expected the batch to fail, got {other:?}"),
+        }
+        assert_eq!(c.list_all().await?.len(), 2);
+
+        let outcomes = c.batch(&Batch { mode: BatchMode::PerItem, ops }).await?;
+        assert!(matches!(&outcomes[..], [BatchOutcome::Created(_), BatchOutcome::Failed(_)]), "Got: {outcomes:?}");
+        let failed = outcomes[1].clone().into_result();
+        assert!(matches!(failed, Err(error::Error::VersionMismatch { expected: 1, actual: 2, .. })), "{failed:?}");
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/tickets/batch"))
+            .json(&serde_json::json!({ "mode": "per_item", "ops": [{ "op": "patch", "id": 42 }] }))
+            .send().await?;
+        let outcomes: serde_json::Value = response.json().await?;
+        assert_eq!(outcomes[0]["failed"]["code"], "not_found");
+        assert_eq!(outcomes[0]["failed"]["id"], 42);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_tickets_can_be_exported_and_imported_over_http() -> error::Result<()> {
+        use crate::transfer::{Format, CSV_COLUMNS};
+
+        let c = Client::with_addr(spawn_server().await?.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Moving", "To another server.")?).await?;
+        c.archive(id).await?;
+
+        let csv = c.export(Format::Csv).await?;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_COLUMNS.join(",").as_str()));
+        assert!(lines.next().is_some_and(|line| line.starts_with("0,Moving,To another server.,To-do,2,true,")), "Got: {csv}");
+
+        let report = c.import(Format::Csv, csv).await?;
+        assert_eq!(report.imported.len(), 1);
+
+        let ndjson = "{\"title\": \"Good\", \"description\": \"Fine.\", \"status\": \"Done\"}\n{\"title\": \"\"}\n";
+        let report = c.import(Format::Ndjson, ndjson).await?;
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(
+            report.errors.iter().map(|e| (e.line, e.column.as_deref())).collect::<Vec<_>>(),
+            vec![(2, Some("title")), (2, Some("description"))],
+        );
+
+        let exported: Vec<Ticket> = serde_json::from_str(&c.export(Format::Json).await?)?;
+        assert_eq!(exported.len(), 3);
+        assert_eq!(exported[2].status, Status::Done);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_shutdown_waits_for_requests_in_flight() -> error::Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = Server::builder().addr("127.0.0.1:0").start().await?;
+        server.ready().await;
+        let addr = server.local_addr();
+
+        // Never ends by itself, shutting down has to end it.
+        let events = reqwest::get(format!("http://{addr}/tickets/events")).await?;
+
+        // Headers now, body only once the shutdown has started.
+        let body = r#"{"title": "Late", "description": "Still counts."}"#;
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        let head = format!("POST /tickets HTTP/1.1\r\nhost: {addr}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n", body.len());
+        stream.write_all(head.as_bytes()).await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let shutdown = tokio::spawn(server.shutdown());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!shutdown.is_finished(), "Shutdown didn't wait for the request in flight");
+
+        stream.write_all(body.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200"), "Response received is: {response}");
+
+        shutdown.await??;
+        assert!(events.text().await.is_ok());
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err(), "Still taking connections");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_slow_and_large_requests_are_turned_away() -> error::Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = Server::builder()
+            .addr("127.0.0.1:0")
+            .body_limit(256)
+            .timeout(Duration::from_millis(100))
+            .start().await?;
+        server.ready().await;
+        let addr = server.local_addr();
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/tickets"))
+            .json(&TicketDraft::with("Big", "x".repeat(300).as_str())?)
+            .send().await?;
+        assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+
+        // A body that never comes.
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        let head = format!("POST /tickets HTTP/1.1\r\nhost: {addr}\r\ncontent-type: application/json\r\ncontent-length: 10\r\n\r\n");
+        stream.write_all(head.as_bytes()).await?;
+
+        let mut response = [0; 64];
+        let read = stream.read(&mut response).await?;
+        let response = String::from_utf8_lossy(&response[..read]);
+        assert!(response.starts_with("HTTP/1.1 408"), "Response received is: {response}");
+
+        server.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn check_if_slow_handlers_are_the_servers_fault() -> error::Result<()> {
+        use crate::problem::Problem;
+        use crate::store::sqlite::SqliteStore;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("tickets.db");
+
+        let server = Server::builder()
+            .addr("127.0.0.1:0")
+            .store(SqliteStore::open(&path)?)
+            .timeout(Duration::from_millis(100))
+            .start().await?;
+        server.ready().await;
+
+        // Keeps every query waiting on the lock for longer than the timeout.
+        let lock = rusqlite::Connection::open(&path)?;
+        lock.execute_batch("BEGIN EXCLUSIVE")?;
+
+        let response = reqwest::get(format!("http://{}/tickets", server.local_addr())).await?;
+        assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.json::<Problem>().await?.code, "timeout");
+
+        drop(lock);
+        server.shutdown().await
+    }
+
+    #[tokio::test]
+    async fn check_if_errors_come_back_as_problem_details() -> error::Result<()> {
+        use crate::problem::{Problem, CONTENT_TYPE};
+
+        let addr = spawn_server().await?;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(format!("http://{addr}/tickets"))
+            .json(&serde_json::json!({ "title": "x".repeat(51), "due_date": "someday" }))
+            .send().await?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[reqwest::header::CONTENT_TYPE], CONTENT_TYPE);
+
+        let problem: Problem = response.json().await?;
+        assert_eq!(problem.code, "validation_failed");
+        assert_eq!(
+            problem.errors.iter().map(|e| (e.field.as_str(), e.code.as_str(), e.limit)).collect::<Vec<_>>(),
+            vec![("description", "required", None), ("due_date", "invalid_date", None), ("title", "too_long", Some(50))],
+        );
+
+        let response = client
+            .post(format!("http://{addr}/tickets"))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(r#"{"title": "#)
+            .send().await?;
+        let problem: Problem = response.json().await?;
+        assert_eq!((problem.status, problem.code.as_str()), (400, "malformed_json"));
+
+        // Only a request body that can't be read is the client's fault.
+        let internal = error::Error::from(serde_json::from_str::<Ticket>("{}").unwrap_err());
+        assert_eq!(internal.status(), reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+
+        let problem: Problem = client.get(format!("http://{addr}/tickets/42")).send().await?.json().await?;
+        assert_eq!((problem.status, problem.code.as_str()), (404, "not_found"));
+
+        let problem: Problem = client.get(format!("http://{addr}/tickets/forty-two")).send().await?.json().await?;
+        assert_eq!((problem.status, problem.code.as_str()), (400, "bad_request"));
+
+        let id = Client::with_addr(addr.to_string())?.create(&TicketDraft::with("Done", "Already.")?).await?;
+        client.patch(format!("http://{addr}/tickets/{id}")).json(&serde_json::json!({ "status": "Done" })).send().await?;
+
+        let response = client
+            .patch(format!("http://{addr}/tickets/{id}"))
+            .json(&serde_json::json!({ "status": "In progress" }))
+            .send().await?;
+        let problem: Problem = response.json().await?;
+        assert_eq!((problem.status, problem.code.as_str()), (409, "invalid_transition"));
+        assert_eq!(problem.extensions["allowed"], serde_json::json!([]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_client_errors_say_what_went_wrong() -> error::Result<()> {
+        use crate::data::{Link, LinkKind, WebhookId};
+
+        let addr = spawn_server().await?;
+        let c = Client::with_addr(addr.to_string())?;
+
+        assert!(matches!(c.retrieve(TicketId(42)).await, Err(error::Error::NotFound(TicketId(42)))));
+        assert!(matches!(c.archive(TicketId(42)).await, Err(error::Error::NotFound(TicketId(42)))));
+
+        let link = Link { kind: LinkKind::Blocks, from: TicketId(1), to: TicketId(2) };
+        assert!(matches!(c.unlink(link).await, Err(error::Error::LinkNotFound(missing)) if missing == link));
+        assert!(matches!(c.remove_webhook(WebhookId(7)).await, Err(error::Error::WebhookNotFound(WebhookId(7)))));
+
+        let draft = serde_json::from_str(&format!(r#"{{"title":"{}","description":""}}"#, "x".repeat(51)))?;
+        match c.create(&draft).await {
+            Err(error::Error::Validation(errors)) => {
+                let fields = errors.iter().map(|e| (e.field.as_str(), e.code.as_str())).collect::<Vec<_>>();
+                assert_eq!(fields, vec![("description", "required"), ("title", "too_long")]);
+            }
+            other => panic!("Expected invalid fields, got {other:?}"),
+        }
+
+        // Nothing's listening there, which is a different thing from a missing ticket.
+        let offline = Client::with_addr("127.0.0.1:1")?;
+        match offline.retrieve(TicketId(42)).await {
+            Err(error::Error::Request(error)) => assert!(error.is_connect(), "{error}"),
+            other => panic!("Expected a connection error, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_only_safe_requests_are_retried() -> error::Result<()> {
+        use std::sync::{Arc, Mutex};
+        use axum::{extract::State, http::{Method, StatusCode, Uri}, response::IntoResponse, Json, Router};
+        use crate::client::Retry;
+
+        // A stand-in server: fetching ticket 0 works on the third try, `/overdue` is too
+        // slow and everything else is down for good. It keeps track of every request.
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let flaky = Router::new()
+            .fallback(|State(requests): State<Arc<Mutex<Vec<String>>>>, method: Method, uri: Uri| async move {
+                let request = format!("{method} {}", uri.path());
+                let tries = {
+                    let mut requests = requests.lock().unwrap();
+                    requests.push(request.clone());
+                    requests.iter().filter(|r| **r == request).count()
+                };
+
+                match request.as_str() {
+                    "GET /tickets/0" if tries == 3 => {
+                        Json(Ticket::with(TicketId(0), "Finally", "Third time lucky.", Status::ToDo).unwrap()).into_response()
+                    }
+                    "GET /tickets/overdue" => {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        StatusCode::OK.into_response()
+                    }
+                    _ => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                }
+            })
+            .with_state(requests.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async { axum::serve(listener, flaky).await });
+
+        let c = Client::builder()
+            .addr(addr.to_string())
+            .timeout(Duration::from_millis(200))
+            .retry(Retry { attempts: 3, initial_backoff: Duration::from_millis(10), max_backoff: Duration::from_millis(40) })
+            .build()?;
+        let count = |request: &str| requests.lock().unwrap().iter().filter(|r| *r == request).count();
+
+        assert_eq!(c.retrieve(TicketId(0)).await?.title.to_string(), "Finally");
+        assert_eq!(count("GET /tickets/0"), 3);
+
+        let unavailable = |result: error::Result<_>| {
+            matches!(result, Err(error::Error::HttpStatusCode(StatusCode::SERVICE_UNAVAILABLE, _)))
+        };
+
+        // These could end up applied twice, so one failure is the end of them.
+        assert!(unavailable(c.create(&TicketDraft::with("Once", "Only.")?).await.map(drop)));
+        assert_eq!(count("POST /tickets"), 1);
 
-        launch_client(addr).await
+        let patch = TicketPatch { id: TicketId(1), status: Some(Status::Done), ..Default::default() };
+        assert!(unavailable(c.patch(patch.clone()).await.map(drop)));
+        assert_eq!(count("PATCH /tickets/1"), 1);
+
+        // With a version the server won't apply it twice.
+        assert!(unavailable(c.patch(TicketPatch { version: Some(1), ..patch }).await.map(drop)));
+        assert_eq!(count("PATCH /tickets/1"), 4);
+
+        // Only patches, even when other changes carry a version.
+        assert!(unavailable(c.reopen(TicketId(1), None).await.map(drop)));
+        assert!(unavailable(c.reopen(TicketId(1), Some(2)).await.map(drop)));
+        assert_eq!(count("POST /tickets/1/reopen"), 2);
+
+        match c.overdue().await {
+            Err(error::Error::Request(error)) => assert!(error.is_timeout(), "{error}"),
+            other => panic!("Expected a timeout, got {other:?}"),
+        }
+        assert_eq!(count("GET /tickets/overdue"), 3);
+
+        let c = Client::builder().addr(addr.to_string()).retry(Retry::never()).build()?;
+        assert!(unavailable(c.retrieve(TicketId(2)).await.map(drop)));
+        assert_eq!(count("GET /tickets/2"), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_if_the_blocking_client_works_outside_of_a_runtime() -> error::Result<()> {
+        use std::sync::mpsc;
+        use tokio::sync::oneshot;
+        use crate::client::blocking;
+
+        // The server gets a thread and a runtime of its own, this one has neither.
+        let (started, addr) = mpsc::channel();
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = std::thread::spawn(move || -> error::Result<()> {
+            tokio::runtime::Runtime::new()?.block_on(async {
+                let server = Server::builder().addr("127.0.0.1:0").start().await?;
+                server.ready().await;
+                let _ = started.send(server.local_addr());
+
+                let _ = stopped.await;
+                server.shutdown().await
+            })
+        });
+
+        let addr = addr.recv().expect("The server should start");
+        assert!(tokio::runtime::Handle::try_current().is_err(), "The test thread runs no runtime");
+
+        let c = blocking::Client::with_addr(addr.to_string())?;
+
+        let id = c.create(&TicketDraft::with("Sync", "No async here.")?)?;
+        c.create(&TicketDraft::with("Other", "Also here.")?)?;
+
+        let ticket = c.patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() })?;
+        assert_eq!(c.retrieve(id)?, ticket);
+
+        let page = c.list_all().limit(1).page()?;
+        assert_eq!((page.tickets.len(), page.next_cursor), (1, Some(id)));
+        assert_eq!(c.list_all().status(Status::InProgress).all()?, vec![ticket]);
+
+        assert!(matches!(c.retrieve(TicketId(42)), Err(error::Error::NotFound(TicketId(42)))));
+
+        let _ = stop.send(());
+        server.join().expect("The server thread shouldn't panic")
+    }
+
+    #[tokio::test]
+    async fn check_if_every_documented_route_is_served() -> error::Result<()> {
+        use crate::problem::Problem;
+
+        let addr = spawn_server().await?;
+        let c = Client::with_addr(addr.to_string())?;
+        let client = reqwest::Client::new();
+
+        let served: serde_json::Value = client.get(format!("http://{addr}/openapi.json")).send().await?.json().await?;
+        let openapi = serde_json::to_value(Server::openapi())?;
+        assert_eq!(served, openapi);
+
+        c.create(&TicketDraft::with("First", "Ticket 0.")?).await?;
+        c.create(&TicketDraft::with("Second", "Ticket 1.")?).await?;
+
+        for (path, item) in openapi["paths"].as_object().unwrap() {
+            let url = path
+                .replace("{id}", "0")
+                .replace("{to}", "1")
+                .replace("{comment_id}", "0")
+                .replace("{label}", "urgent")
+                .replace("{kind}", "blocks");
+
+            for (method, operation) in item.as_object().unwrap() {
+                let method = reqwest::Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+                let mut request = client.request(method.clone(), format!("http://{addr}{url}"));
+                if method != reqwest::Method::GET {
+                    request = request.json(&serde_json::json!({}));
+                }
+
+                // Only the status is looked at, the event feed would never end.
+                let response = request.send().await?;
+                let status = response.status();
+
+                if status.is_success() {
+                    assert!(operation["responses"].get(status.as_str()).is_some(), "{method} {path} answered {status}, which isn't documented");
+                    continue
+                }
+
+                assert_ne!(status, reqwest::StatusCode::METHOD_NOT_ALLOWED, "{method} {path} isn't served");
+                if status == reqwest::StatusCode::NOT_FOUND {
+                    let problem: Problem = response.json().await?;
+                    assert_ne!(problem.detail, "There's nothing here.", "{method} {path} isn't served");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Test helper function.
+    async fn spawn_server() -> error::Result<SocketAddr> {
+        let server = Server::builder().addr("127.0.0.1:0").start().await?;
+        server.ready().await;
+
+        Ok(server.local_addr())
     }
 
     // Test helper function.
@@ -48,6 +961,16 @@ mod tests {
 
         let ticket = c.retrieve(id).await?;
 
+        // Timestamps come from the server's clock, they only have to agree with each other.
+        assert_eq!(ticket.created_at, ticket.updated_at);
+        assert_eq!(ticket.created_at, ticket.status_changed_at);
+
+        let test_ticket = Ticket {
+            created_at: ticket.created_at,
+            updated_at: ticket.updated_at,
+            status_changed_at: ticket.status_changed_at,
+            ..test_ticket
+        };
         assert_eq!(test_ticket, ticket, "Ticket retrieval test, ticket received is: {ticket:#?}");
 
         let ticket_patch = TicketPatch{
@@ -55,6 +978,9 @@ mod tests {
             title: None,
             description: None,
             status: Some(Status::InProgress),
+            priority: None,
+            due_date: None,
+            version: None,
         };
 
         let ticket = c.patch(ticket_patch).await?;
@@ -0,0 +1,156 @@
+// Error responses as RFC 7807 problem details, e.g.
+//
+//     {
+//         "type": "urn:ticket-store:validation_failed",
+//         "title": "Bad Request",
+//         "status": 400,
+//         "detail": "The request has 2 invalid fields: ...",
+//         "code": "validation_failed",
+//         "errors": [
+//             { "field": "title", "code": "too_long", "message": "...", "limit": 50 },
+//             { "field": "description", "code": "required", "message": "..." }
+//         ]
+//     }
+//
+// `code` is what programs should match on, it won't change. `detail` and the
+// messages are for people and might.
+
+use std::fmt::Display;
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize, Deserialize};
+use serde_json::{Map, Value};
+use utoipa::{ToResponse, ToSchema};
+
+pub const CONTENT_TYPE: &str = "application/problem+json";
+
+// One field of a request that didn't pass. `field` is its path in the body, e.g.
+// `title` or `ops[2].description`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+    // The most the field can take, for the ones that have a limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: &str, message: impl Display) -> Self {
+        Self { field: field.into(), code: code.into(), message: message.to_string(), limit: None }
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    // The same error, found further down in the body.
+    pub fn within(mut self, path: &str) -> Self {
+        if !path.is_empty() {
+            self.field = format!("{path}.{}", self.field);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema, ToResponse)]
+#[response(description = "What went wrong, as problem details", content_type = "application/problem+json")]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    // Whatever else helps with this particular problem, e.g. `allowed` for a
+    // refused status change.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &str, detail: impl Into<String>) -> Self {
+        Self {
+            kind: format!("urn:ticket-store:{code}"),
+            title: status.canonical_reason().unwrap_or("Unknown").into(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            code: code.into(),
+            errors: Vec::new(),
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    pub fn with(mut self, name: &str, value: impl Serialize) -> Self {
+        if let Ok(value) = serde_json::to_value(value) {
+            self.extensions.insert(name.into(), value);
+        }
+        self
+    }
+
+    pub fn with_extensions(mut self, extensions: Map<String, Value>) -> Self {
+        self.extensions.extend(extensions);
+        self
+    }
+
+    pub fn extension<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        serde_json::from_value(self.extensions.get(name)?.clone()).ok()
+    }
+
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let mut response = (self.status(), Json(self)).into_response();
+        response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+        response
+    }
+}
+
+// A code for statuses that don't come with one of their own, e.g. `payload_too_large`.
+pub fn status_code(status: StatusCode) -> String {
+    status.canonical_reason()
+        .map(|reason| reason.to_lowercase().replace([' ', '-'], "_"))
+        .unwrap_or_else(|| format!("http_{}", status.as_u16()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_problems_serialize_with_their_extensions() {
+        let problem = Problem::new(StatusCode::CONFLICT, "invalid_transition", "Nope.")
+            .with_errors(vec![FieldError::new("status", "not_allowed", "Can't.").within("ops[1]")])
+            .with("allowed", ["Done"]);
+
+        let json = serde_json::to_value(&problem).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "type": "urn:ticket-store:invalid_transition",
+            "title": "Conflict",
+            "status": 409,
+            "detail": "Nope.",
+            "code": "invalid_transition",
+            "errors": [{ "field": "ops[1].status", "code": "not_allowed", "message": "Can't." }],
+            "allowed": ["Done"],
+        }));
+        assert_eq!(serde_json::from_value::<Problem>(json).unwrap(), problem);
+
+        assert_eq!(status_code(StatusCode::PAYLOAD_TOO_LARGE), "payload_too_large");
+    }
+}
@@ -1,106 +1,963 @@
 use std::sync::Arc;
 use tokio::net::{ToSocketAddrs, TcpListener};
 use serde_json::Value;
+use futures_util::{Stream, StreamExt};
 use axum::{
     Router,
     Json,
-    http::StatusCode,
+    body::Body,
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
     routing::get,
     serve::Serve,
-    extract::{Path, State},
+    extract::{FromRef, Request, State},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
 };
-use axum::response::Html;
+use axum::response::{sse::{self, KeepAlive, Sse}, Html, IntoResponse, Response};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use utoipa::{
+    openapi::{OpenApi, RefOr, Schema},
+    IntoParams, OpenApi as _,
+};
+use utoipa_axum::{router::OpenApiRouter, routes};
 use crate::{
     error::{Result, Error},
-    store::TicketStore,
-    data::{TicketId, Ticket, TicketDraft},
+    problem::{FieldError, Problem},
+    store::{actor, TicketRepository, TicketStore},
+    data::{HistoryEntry, Page, SortBy, TicketId, Ticket, TicketDraft, TicketPatch, TicketQuery},
+    data::{Comment, CommentDraft, CommentId, Label, Link, LinkDraft, LinkKind},
+    data::{Event, EventFilter, EventKind},
+    data::{DeadLetter, Webhook, WebhookDraft, WebhookId},
+    data::{Batch, BatchOutcome},
+    data::{comment::MAX_COMMENT_LEN, description::MAX_DESCRIPTION_LEN, label::MAX_LABEL_LEN, title::MAX_TITLE_LEN},
+    transfer::{self, Format, ImportReport},
 };
-use crate::data::{Status, TicketDescription, TicketTitle};
+use crate::data::{etag, version_from_etag, Priority, Status, TicketDescription, TicketTitle};
+
+mod builder;
+mod extract;
+mod feed;
+mod webhooks;
+
+pub use builder::{ServerBuilder, ServerHandle};
+pub use feed::Feed;
+pub use webhooks::{Delivery, Webhooks};
 
-type Store = Arc<RwLock<TicketStore>>;
+use extract::{PatchBody, Path, Payload, Query};
+
+type Store = Arc<dyn TicketRepository>;
+
+// Handlers pick whichever part they need, e.g. `State<Store>`.
+#[derive(Clone)]
+struct AppState {
+    store: Store,
+    feed: Arc<Feed>,
+    webhooks: Arc<Webhooks>,
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Feed> {
+    fn from_ref(state: &AppState) -> Self {
+        state.feed.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Webhooks> {
+    fn from_ref(state: &AppState) -> Self {
+        state.webhooks.clone()
+    }
+}
+
+// A single ticket, sent back with its version as the ETag.
+struct Versioned(Ticket);
+
+#[derive(Debug, Default, serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FormatQuery {
+    #[serde(default)]
+    format: Format,
+}
+
+impl IntoResponse for Versioned {
+    fn into_response(self) -> Response {
+        ([(header::ETAG, etag(self.0.version))], Json(self.0)).into_response()
+    }
+}
 
 #[derive(Debug)]
 pub struct Server;
 
 impl Server {
 
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
 
     pub async fn serve(addr: impl ToSocketAddrs)
         -> Result<Serve<TcpListener, Router, Router>>
     {
-        let store = Arc::new(RwLock::new(TicketStore::new()));
-        
-        let router = Router::new()
-            .route("/", get(|| async { Html::from("Welcome to the ticket store!") }))
-            .route("/tickets", get(Self::list_all).post(Self::create))
-            .route("/tickets/{id}", get(Self::retrieve).patch(Self::patch))
-            .with_state(store);
-        
+        Self::serve_with(addr, RwLock::new(TicketStore::new())).await
+    }
+
+    // Keeps its tickets in `dir`, so a restart picks up where the last run left off.
+    pub async fn serve_in(addr: impl ToSocketAddrs, dir: impl AsRef<std::path::Path>)
+        -> Result<Serve<TcpListener, Router, Router>>
+    {
+        Self::serve_with(addr, RwLock::new(TicketStore::open(dir)?)).await
+    }
+
+    pub async fn serve_with(addr: impl ToSocketAddrs, repository: impl TicketRepository + 'static)
+        -> Result<Serve<TcpListener, Router, Router>>
+    {
+        Self::serve_with_delivery(addr, repository, Delivery::default()).await
+    }
+
+    pub async fn serve_with_delivery(
+        addr: impl ToSocketAddrs,
+        repository: impl TicketRepository + 'static,
+        delivery: Delivery
+    ) -> Result<Serve<TcpListener, Router, Router>>
+    {
+        let (state, _) = Self::state(Arc::new(repository), delivery);
+        let router = Self::router(state);
+
         let listener = TcpListener::bind(addr).await?;
         Ok(axum::serve(listener, router))
     }
 
-    async fn list_all(State(store): State<Store>) -> Json<Vec<Ticket>> {
-        let mut tickets = Vec::new();
+    // Along with the task delivering webhooks, which stops by itself once the feed
+    // is closed or gone with the server.
+    fn state(store: Store, delivery: Delivery) -> (AppState, JoinHandle<()>) {
+        let state = AppState {
+            store,
+            feed: Arc::new(Feed::new()),
+            webhooks: Arc::new(Webhooks::new(delivery)),
+        };
+
+        let webhooks = state.webhooks.clone().spawn(state.store.clone(), Arc::downgrade(&state.feed));
+
+        (state, webhooks)
+    }
+
+    // The API as OpenAPI 3, the same document the server hands out at `/openapi.json`.
+    pub fn openapi() -> OpenApi {
+        api().1
+    }
+
+    fn router(state: AppState) -> Router {
+        let (routes, openapi) = api();
 
-        for ticket in store.read().await.get_all() {
-            tickets.push(ticket.read().await.clone());
+        routes
+            .route("/", get(|| async { Html::from("Welcome to the ticket store!") }))
+            .route("/openapi.json", get(move || std::future::ready(Json(openapi.clone()))))
+            .fallback(|| async { Error::HttpStatusCode(StatusCode::NOT_FOUND, "There's nothing here.".into()) })
+            .layer(middleware::from_fn(|request: Request, next: Next| {
+                let actor = request.headers()
+                    .get(actor::HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::trim)
+                    .filter(|actor| !actor.is_empty())
+                    .map(str::to_string);
+
+                actor::scope(actor, next.run(request))
+            }))
+            .with_state(state)
+    }
+}
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    info(
+        title = "Ticket store",
+        description = "Tickets, their comments and links, and a feed of every change. Changes made with an \
+            `X-Actor` header are put down to that name in the ticket's history.",
+    ),
+    // Only paths pick up what they use on their own, parameters and responses don't.
+    components(schemas(Problem, FieldError, SortBy, Format), responses(Problem)),
+    tags(
+        (name = "tickets"),
+        (name = "comments"),
+        (name = "events", description = "Every change to a ticket, as it happens"),
+        (name = "webhooks", description = "Every change to a ticket, POSTed elsewhere"),
+    ),
+)]
+struct ApiDoc;
+
+// Every route along with its description. Each handler's `#[utoipa::path]` is both
+// where its route comes from and how `/openapi.json` describes it, so the two can't
+// go separate ways. Paths that take more than one method go in the same `routes!`.
+fn api() -> (Router<AppState>, OpenApi) {
+    let (router, mut openapi) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(list_all, create))
+        .routes(routes!(batch))
+        .routes(routes!(overdue))
+        .routes(routes!(export))
+        .routes(routes!(import))
+        .routes(routes!(events))
+        .routes(routes!(events_ws))
+        .routes(routes!(retrieve, patch, delete))
+        .routes(routes!(archive))
+        .routes(routes!(restore))
+        .routes(routes!(reopen))
+        .routes(routes!(add_label, remove_label))
+        .routes(routes!(links, link))
+        .routes(routes!(unlink))
+        .routes(routes!(history))
+        .routes(routes!(comments, add_comment))
+        .routes(routes!(edit_comment, delete_comment))
+        .routes(routes!(webhooks, add_webhook))
+        .routes(routes!(dead_letters))
+        .routes(routes!(remove_webhook))
+        .split_for_parts();
+
+    // The crate doesn't have one.
+    openapi.info.license = None;
+    set_limits(&mut openapi);
+
+    (router, openapi)
+}
+
+// Derived schemas of newtypes can't have a length, so they get the one the
+// checks use here.
+fn set_limits(openapi: &mut OpenApi) {
+    let Some(components) = openapi.components.as_mut() else {
+        return
+    };
+
+    let limits = [
+        ("TicketTitle", MAX_TITLE_LEN),
+        ("TicketDescription", MAX_DESCRIPTION_LEN),
+        ("CommentBody", MAX_COMMENT_LEN),
+        ("Label", MAX_LABEL_LEN),
+    ];
+
+    for (name, limit) in limits {
+        if let Some(RefOr::T(Schema::Object(schema))) = components.schemas.get_mut(name) {
+            schema.min_length = Some(1);
+            schema.max_length = Some(limit);
         }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets",
+    tag = "tickets",
+    params(TicketQuery),
+    responses(
+        (status = 200, description = "A page of tickets, `next_cursor` fetches the next", body = Page),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn list_all(Query(query): Query<TicketQuery>, State(store): State<Store>)
+    -> Result<Json<Page>>
+{
+    Ok(Json(store.list(query).await?))
+}
 
-        Json(tickets)
+#[utoipa::path(
+    get,
+    path = "/tickets/overdue",
+    tag = "tickets",
+    responses(
+        (status = 200, description = "Open tickets past their due date, most urgent first", body = Vec<Ticket>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn overdue(State(store): State<Store>) -> Result<Json<Vec<Ticket>>> {
+    Ok(Json(store.overdue().await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets",
+    tag = "tickets",
+    request_body = TicketDraft,
+    responses(
+        (status = 200, description = "Id of the new ticket", body = TicketId),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn create(State(store): State<Store>, State(feed): State<Arc<Feed>>, Payload(draft): Payload<TicketDraft>)
+    -> Result<Json<TicketId>> {
+    let _changing = feed.changing().await;
+    let id = store.create(draft).await?;
+
+    if let Some(ticket) = store.get(id).await? {
+        feed.publish(EventKind::Created, ticket);
     }
 
-    async fn create(State(store): State<Store>, Json(draft): Json<TicketDraft>)
-        -> Json<TicketId> {
-        Json(store.write().await.add_ticket(draft))
+    Ok(Json(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/batch",
+    tag = "tickets",
+    request_body = Batch,
+    responses(
+        (status = 200, description = "What became of each op, in order", body = Vec<BatchOutcome>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn batch(State(store): State<Store>, State(feed): State<Arc<Feed>>, Payload(batch): Payload<Batch>)
+    -> Result<Json<Vec<BatchOutcome>>>
+{
+    let _changing = feed.changing().await;
+    let outcomes = store.batch(batch).await?;
+
+    for outcome in &outcomes {
+        match outcome {
+            BatchOutcome::Created(ticket) => feed.publish(EventKind::Created, ticket.clone()),
+            BatchOutcome::Patched(ticket) => feed.publish(EventKind::Patched, ticket.clone()),
+            BatchOutcome::Failed(_) => {}
+        }
     }
 
-    async fn retrieve(Path(id): Path<TicketId>, State(store): State<Store>)
-        ->  Result<Json<Ticket>>
-    {
-        if let Some(ticket) = store.read().await.get(id) {
-            Ok(Json(ticket.read().await.clone()))
-        } else {
-            Err(
-                Error::HttpStatusCode(
-                    StatusCode::NOT_FOUND, format!("Cannot find ticket with id: {id}.")
-                )
-            )
+    Ok(Json(outcomes))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/export",
+    tag = "tickets",
+    params(FormatQuery),
+    responses(
+        (status = 200, description = "Every ticket, archived ones included", content((String = "text/csv"), (Vec<Ticket> = "application/json"), (String = "application/x-ndjson"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn export(Query(query): Query<FormatQuery>, State(store): State<Store>) -> Response {
+    let body = Body::from_stream(transfer::export(store, query.format));
+    ([(header::CONTENT_TYPE, query.format.content_type())], body).into_response()
+}
+
+// Good rows go in even when others fail, the report says which is which.
+#[utoipa::path(
+    post,
+    path = "/tickets/import",
+    tag = "tickets",
+    params(FormatQuery),
+    request_body(content((String = "text/csv"), (Vec<Value> = "application/json"), (String = "application/x-ndjson"))),
+    responses(
+        (status = 200, description = "Which tickets were created and which rows were turned away", body = ImportReport),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn import(Query(query): Query<FormatQuery>, State(store): State<Store>, State(feed): State<Arc<Feed>>, body: String)
+    -> Result<Json<ImportReport>>
+{
+    let _changing = feed.changing().await;
+    let report = transfer::import(store.as_ref(), query.format, &body).await?;
+
+    for &id in &report.imported {
+        if let Some(ticket) = store.get(id).await? {
+            feed.publish(EventKind::Created, ticket);
         }
     }
 
-    async fn patch(Path(id): Path<TicketId>, State(store): State<Store>, Json(patch): Json<Value>)
-                   -> Result<Json<Ticket>>
-    {
-        let Some(ticket) = store.read().await.get(id) else {
-            return Err(
-                Error::HttpStatusCode(
-                    StatusCode::NOT_FOUND, format!("Cannot find ticket with id: {id}.")
-                )
-            )
-        };
+    Ok(Json(report))
+}
 
-        let Value::Object(map) = serde_json::to_value(patch)? else {
-            return Err(
-                Error::JsonParse(serde::de::Error::custom("Invalid type. Expected JSON object."))
-            )
-        };
+// Server-sent events. Browsers resend the last id they saw when they reconnect,
+// which counts the same as `after` and `epoch`.
+#[utoipa::path(
+    get,
+    path = "/tickets/events",
+    tag = "events",
+    params(
+        EventFilter,
+        ("Last-Event-ID" = Option<String>, Header, description = "`epoch:seq` of the last event seen, browsers send it when they reconnect"),
+    ),
+    responses(
+        (status = 200, description = "Server-sent events, one per change", body = Event, content_type = "text/event-stream"),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn events(Query(mut filter): Query<EventFilter>, State(feed): State<Arc<Feed>>, headers: HeaderMap)
+    -> Result<Sse<impl Stream<Item = std::result::Result<sse::Event, axum::Error>>>>
+{
+    if filter.after.is_none() {
+        if let Some(id) = headers.get("last-event-id").and_then(|id| id.to_str().ok()) {
+            filter.resume(id);
+        }
+    }
+
+    let events = feed.subscribe(filter)?.map(|event| {
+        sse::Event::default().id(event.id()).event(event.kind.to_string()).json_data(&event)
+    });
 
-        if let Some(Value::String(title)) = map.get("title") {
-            ticket.write().await.title = TicketTitle::try_from(title.to_string())?;
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/events/ws",
+    tag = "events",
+    params(EventFilter),
+    responses(
+        (status = 101, description = "A WebSocket that sends each change as a JSON `Event`"),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn events_ws(ws: WebSocketUpgrade, Query(filter): Query<EventFilter>, State(feed): State<Arc<Feed>>)
+    -> Result<Response>
+{
+    let events = feed.subscribe(filter)?;
+    Ok(ws.on_upgrade(move |socket| forward(socket, events)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/{id}",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "The ticket", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn retrieve(Path(id): Path<TicketId>, State(store): State<Store>)
+    ->  Result<Versioned>
+{
+    store.get(id).await?.map(Versioned).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/tickets/{id}",
+    tag = "tickets",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("If-Match" = Option<String>, Header, description = "Only changes the ticket if it's still at this version, e.g. `\"3\"`"),
+    ),
+    request_body(content = TicketPatch, description = "The fields to change, `null` clears `priority` and `due_date`. `id` and `version` come from the path and `If-Match` instead"),
+    responses(
+        (status = 200, description = "The changed ticket", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn patch(
+    Path(id): Path<TicketId>,
+    State(store): State<Store>,
+    State(feed): State<Arc<Feed>>,
+    headers: HeaderMap,
+    Payload(PatchBody(map)): Payload<PatchBody>
+) -> Result<Versioned>
+{
+    let mut patch = TicketPatch { id, version: if_match(&headers)?, ..Default::default() };
+
+    if let Some(Value::String(title)) = map.get("title") {
+        patch.title = Some(TicketTitle::try_from(title.to_string())?);
+    }
+
+    if let Some(Value::String(desc)) = map.get("description") {
+        patch.description = Some(TicketDescription::try_from(desc.clone())?);
+    }
+
+    if let Some(Value::String(status)) = map.get("status") {
+        patch.status = Some(Status::try_from(status.clone())?);
+    }
+
+    // `null` clears these, leaving them out keeps them as they are.
+    match map.get("priority") {
+        Some(Value::Null) => patch.priority = Some(None),
+        Some(Value::String(priority)) => patch.priority = Some(Some(Priority::try_from(priority.clone())?)),
+        _ => {}
+    }
+
+    match map.get("due_date") {
+        Some(Value::Null) => patch.due_date = Some(None),
+        Some(Value::String(due_date)) => patch.due_date = Some(Some(due_date.parse()?)),
+        _ => {}
+    }
+
+    let _changing = feed.changing().await;
+    store.patch(patch).await?.map(published(&feed, EventKind::Patched)).map(Versioned).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tickets/{id}",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "The deleted ticket", body = Ticket),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn delete(Path(id): Path<TicketId>, State(store): State<Store>, State(feed): State<Arc<Feed>>)
+    -> Result<Json<Ticket>>
+{
+    let _changing = feed.changing().await;
+    store.delete(id).await?.map(published(&feed, EventKind::Deleted)).map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/{id}/archive",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "The archived ticket", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn archive(Path(id): Path<TicketId>, State(store): State<Store>, State(feed): State<Arc<Feed>>)
+    -> Result<Versioned>
+{
+    let _changing = feed.changing().await;
+    store.archive(id).await?.map(published(&feed, EventKind::Patched)).map(Versioned).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/{id}/restore",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "The restored ticket", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn restore(Path(id): Path<TicketId>, State(store): State<Store>, State(feed): State<Arc<Feed>>)
+    -> Result<Versioned>
+{
+    let _changing = feed.changing().await;
+    store.restore(id).await?.map(published(&feed, EventKind::Patched)).map(Versioned).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/{id}/reopen",
+    tag = "tickets",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("If-Match" = Option<String>, Header, description = "Only changes the ticket if it's still at this version, e.g. `\"3\"`"),
+    ),
+    responses(
+        (status = 200, description = "The ticket, in the status the workflow reopens it to", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn reopen(
+    Path(id): Path<TicketId>,
+    State(store): State<Store>,
+    State(feed): State<Arc<Feed>>,
+    headers: HeaderMap
+) -> Result<Versioned>
+{
+    let version = if_match(&headers)?;
+    let _changing = feed.changing().await;
+    store.reopen(id, version).await?
+        .map(published(&feed, EventKind::Patched))
+        .map(Versioned)
+        .ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    put,
+    path = "/tickets/{id}/labels/{label}",
+    tag = "tickets",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("label" = Label, Path),
+        ("If-Match" = Option<String>, Header, description = "Only changes the ticket if it's still at this version, e.g. `\"3\"`"),
+    ),
+    responses(
+        (status = 200, description = "The labelled ticket", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn add_label(
+    Path((id, label)): Path<(TicketId, Label)>,
+    State(store): State<Store>,
+    State(feed): State<Arc<Feed>>,
+    headers: HeaderMap
+) -> Result<Versioned>
+{
+    let version = if_match(&headers)?;
+    let _changing = feed.changing().await;
+    store.add_label(id, label, version).await?
+        .map(published(&feed, EventKind::Patched))
+        .map(Versioned)
+        .ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tickets/{id}/labels/{label}",
+    tag = "tickets",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("label" = Label, Path),
+        ("If-Match" = Option<String>, Header, description = "Only changes the ticket if it's still at this version, e.g. `\"3\"`"),
+    ),
+    responses(
+        (status = 200, description = "The ticket without the label", body = Ticket, headers(("ETag" = String, description = "Version of the ticket, e.g. `\"3\"`"))),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn remove_label(
+    Path((id, label)): Path<(TicketId, Label)>,
+    State(store): State<Store>,
+    State(feed): State<Arc<Feed>>,
+    headers: HeaderMap
+) -> Result<Versioned>
+{
+    let version = if_match(&headers)?;
+    let _changing = feed.changing().await;
+    store.remove_label(id, label, version).await?
+        .map(published(&feed, EventKind::Patched))
+        .map(Versioned)
+        .ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/{id}/links",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    request_body = LinkDraft,
+    responses(
+        (status = 200, description = "The new link", body = Link),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn link(Path(id): Path<TicketId>, State(store): State<Store>, Payload(draft): Payload<LinkDraft>)
+    -> Result<Json<Link>>
+{
+    let link = Link { kind: draft.kind, from: id, to: draft.to };
+    store.link(link).await?.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tickets/{id}/links/{kind}/{to}",
+    tag = "tickets",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("kind" = LinkKind, Path),
+        ("to" = TicketId, Path),
+    ),
+    responses(
+        (status = 200, description = "The removed link", body = Link),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn unlink(Path((id, kind, to)): Path<(TicketId, LinkKind, TicketId)>, State(store): State<Store>)
+    -> Result<Json<Link>>
+{
+    let link = Link { kind, from: id, to };
+    store.unlink(link).await?.map(Json).ok_or(Error::LinkNotFound(link))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/{id}/links",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "Links from and to the ticket", body = Vec<Link>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn links(Path(id): Path<TicketId>, State(store): State<Store>)
+    -> Result<Json<Vec<Link>>>
+{
+    store.links(id).await?.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/{id}/history",
+    tag = "tickets",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "Every change to the ticket, oldest first, its deletion included", body = Vec<HistoryEntry>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn history(Path(id): Path<TicketId>, State(store): State<Store>)
+    -> Result<Json<Vec<HistoryEntry>>>
+{
+    store.history(id).await?.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tickets/{id}/comments",
+    tag = "comments",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    request_body = CommentDraft,
+    responses(
+        (status = 200, description = "The new comment", body = Comment),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn add_comment(
+    Path(id): Path<TicketId>,
+    State(store): State<Store>,
+    Payload(draft): Payload<CommentDraft>
+) -> Result<Json<Comment>>
+{
+    store.add_comment(id, draft).await?.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tickets/{id}/comments",
+    tag = "comments",
+    params(("id" = TicketId, Path, description = "Id of the ticket")),
+    responses(
+        (status = 200, description = "Comments on the ticket, oldest first", body = Vec<Comment>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn comments(Path(id): Path<TicketId>, State(store): State<Store>)
+    -> Result<Json<Vec<Comment>>>
+{
+    store.comments(id).await?.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/tickets/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("comment_id" = CommentId, Path),
+    ),
+    request_body = CommentDraft,
+    responses(
+        (status = 200, description = "The edited comment", body = Comment),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn edit_comment(
+    Path((id, comment)): Path<(TicketId, CommentId)>,
+    State(store): State<Store>,
+    Payload(draft): Payload<CommentDraft>
+) -> Result<Json<Comment>>
+{
+    store.edit_comment(id, comment, draft).await?.map(Json).ok_or_else(|| comment_not_found(id, comment))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tickets/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(
+        ("id" = TicketId, Path, description = "Id of the ticket"),
+        ("comment_id" = CommentId, Path),
+    ),
+    responses(
+        (status = 200, description = "The deleted comment", body = Comment),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn delete_comment(Path((id, comment)): Path<(TicketId, CommentId)>, State(store): State<Store>)
+    -> Result<Json<Comment>>
+{
+    store.delete_comment(id, comment).await?.map(Json).ok_or_else(|| comment_not_found(id, comment))
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = WebhookDraft,
+    responses(
+        (status = 200, description = "The new webhook, without its secret", body = Webhook),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn add_webhook(State(store): State<Store>, Payload(draft): Payload<WebhookDraft>) -> Result<Json<Webhook>> {
+    Ok(Json(store.add_webhook(draft).await?.redacted()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Every webhook, without their secrets", body = Vec<Webhook>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn webhooks(State(store): State<Store>) -> Result<Json<Vec<Webhook>>> {
+    Ok(Json(store.webhooks().await?.into_iter().map(Webhook::redacted).collect()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = WebhookId, Path, description = "Id of the webhook")),
+    responses(
+        (status = 200, description = "The removed webhook", body = Webhook),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn remove_webhook(Path(id): Path<WebhookId>, State(store): State<Store>) -> Result<Json<Webhook>> {
+    store.remove_webhook(id).await?.map(|webhook| Json(webhook.redacted())).ok_or(Error::WebhookNotFound(id))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/dead_letters",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Deliveries that ran out of attempts, oldest first", body = Vec<DeadLetter>),
+        (status = "default", response = Problem),
+    ),
+)]
+async fn dead_letters(State(webhooks): State<Arc<Webhooks>>) -> Json<Vec<DeadLetter>> {
+    Json(webhooks.dead_letters())
+}
+
+// Passes the ticket along, telling the feed about it on the way. Only while
+// `Feed::changing` is held, or the event could overtake the one before it.
+fn published(feed: &Feed, kind: EventKind) -> impl FnOnce(Ticket) -> Ticket + '_ {
+    move |ticket| {
+        feed.publish(kind, ticket.clone());
+        ticket
+    }
+}
+
+// Sends each event as a JSON text message until either side hangs up. Whatever
+// the client sends is ignored.
+async fn forward(mut socket: WebSocket, events: impl Stream<Item = Event>) {
+    let mut events = std::pin::pin!(events);
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some(Ok(text)) = event.map(|event| serde_json::to_string(&event)) else { break };
+
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            }
         }
+    }
+}
 
-        if let Some(Value::String(desc)) = map.get("description") {
-            ticket.write().await.description = TicketDescription::try_from(desc.clone())?;
+// `*` matches any version, same as leaving the header out.
+fn if_match(headers: &HeaderMap) -> Result<Option<u64>> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None)
+    };
+
+    let value = value.to_str().unwrap_or_default().trim();
+
+    if value == "*" {
+        return Ok(None)
+    }
+
+    version_from_etag(value).map(Some).ok_or_else(|| {
+        Error::HttpStatusCode(StatusCode::BAD_REQUEST, format!("Invalid If-Match header: {value}"))
+    })
+}
+
+fn not_found(id: TicketId) -> Error {
+    Error::NotFound(id)
+}
+
+fn comment_not_found(id: TicketId, comment: CommentId) -> Error {
+    Error::CommentNotFound { ticket: id, comment }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use serde::Serialize;
+    use crate::server::extract::Checked;
+    use super::*;
+
+    fn schema(openapi: &serde_json::Value, name: &str) -> serde_json::Value {
+        openapi["components"]["schemas"][name].clone()
+    }
+
+    fn properties(schema: &serde_json::Value) -> BTreeSet<String> {
+        schema["properties"].as_object().unwrap().keys().cloned().collect()
+    }
+
+    fn required(schema: &serde_json::Value) -> BTreeSet<String> {
+        schema["required"].as_array().unwrap().iter().map(|name| name.as_str().unwrap().to_string()).collect()
+    }
+
+    // Every field, so nothing is skipped when it's serialized.
+    fn fields(value: impl Serialize) -> BTreeSet<String> {
+        serde_json::to_value(value).unwrap().as_object().unwrap().keys().cloned().collect()
+    }
+
+    fn refs(value: &serde_json::Value, found: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(object) => {
+                if let Some(serde_json::Value::String(path)) = object.get("$ref") {
+                    found.push(path.clone());
+                }
+                object.values().for_each(|value| refs(value, found));
+            }
+            serde_json::Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {}
         }
+    }
 
-        if let Some(Value::String(status)) = map.get("status") {
-            ticket.write().await.status = Status::try_from(status.clone())?;
+    #[test]
+    fn check_if_the_schemas_match_the_types() {
+        let openapi = serde_json::to_value(api().1).unwrap();
+
+        let mut found = Vec::new();
+        refs(&openapi, &mut found);
+        for path in found {
+            let pointer = path.strip_prefix('#').unwrap();
+            assert!(openapi.pointer(pointer).is_some(), "Nothing at {path}");
         }
 
-        let ticket = ticket.read().await.clone();
+        let statuses = [Status::ToDo, Status::InProgress, Status::Done].map(|status| serde_json::to_value(status).unwrap());
+        assert_eq!(schema(&openapi, "Status")["enum"], serde_json::json!(statuses));
+        assert_eq!(schema(&openapi, "TicketTitle")["maxLength"], MAX_TITLE_LEN);
+
+        let mut ticket = Ticket::with(TicketId(1), "Every", "Field.", Status::Done).unwrap();
+        ticket.archived = true;
+        ticket.labels.insert(Label::try_from("set").unwrap());
+        ticket.priority = Some(Priority::High);
+        ticket.due_date = Some(Default::default());
+        assert_eq!(properties(&schema(&openapi, "Ticket")), fields(&ticket));
 
-        Ok(Json(ticket))
+        let patch = TicketPatch {
+            title: Some(ticket.title.clone()),
+            description: Some(ticket.description.clone()),
+            status: Some(Status::Done),
+            priority: Some(None),
+            due_date: Some(None),
+            version: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(properties(&schema(&openapi, "TicketPatch")), fields(&patch));
+
+        // What the docs say can't be left out is what the server turns away.
+        let drafts = [
+            ("TicketDraft", TicketDraft::REQUIRED),
+            ("CommentDraft", CommentDraft::REQUIRED),
+            ("LinkDraft", LinkDraft::REQUIRED),
+            ("WebhookDraft", WebhookDraft::REQUIRED),
+        ];
+        for (name, fields) in drafts {
+            let fields = fields.iter().map(|field| field.to_string()).collect();
+            assert_eq!(required(&schema(&openapi, name)), fields, "{name}");
+        }
     }
 }
@@ -0,0 +1,235 @@
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{DefaultBodyLimit, Request},
+    middleware::{self, Next},
+    response::IntoResponse,
+};
+use futures_util::{stream, StreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::{watch, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::{
+    client::Client,
+    error::{Error, Result},
+    server::{Delivery, Server, Store},
+    store::{TicketRepository, TicketStore},
+};
+
+// Same as axum's own default.
+pub const DEFAULT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Signal = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// Everything about a server that can be set before it starts. Unless told otherwise
+// it listens on `Client::DEFAULT_URL` with a fresh in-memory store.
+pub struct ServerBuilder {
+    addr: String,
+    store: Store,
+    body_limit: usize,
+    timeout: Duration,
+    delivery: Delivery,
+    shutdown: Option<Signal>,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self {
+            addr: Client::DEFAULT_URL.into(),
+            store: Arc::new(RwLock::new(TicketStore::new())),
+            body_limit: DEFAULT_BODY_LIMIT,
+            timeout: DEFAULT_TIMEOUT,
+            delivery: Delivery::default(),
+            shutdown: None,
+        }
+    }
+}
+
+impl ServerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Port 0 picks any free port, `ServerHandle::local_addr` says which.
+    pub fn addr(mut self, addr: impl Into<String>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    pub fn store(mut self, repository: impl TicketRepository + 'static) -> Self {
+        self.store = Arc::new(repository);
+        self
+    }
+
+    // In bytes. Larger bodies are turned away with 413 Payload Too Large.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    // How long a handler gets to come up with a response. Running out while the
+    // request body is still coming in answers 408 Request Timeout, that's down to
+    // the client, otherwise it's 503 Service Unavailable. Streamed response bodies,
+    // like the event feed, aren't cut short.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn delivery(mut self, delivery: Delivery) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    // Shuts the server down gracefully once it resolves, e.g. `tokio::signal::ctrl_c()`.
+    pub fn shutdown_signal(mut self, signal: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+
+    // Binds straight away, so a bad address fails here, then serves in the background.
+    pub async fn start(self) -> Result<ServerHandle> {
+        let listener = TcpListener::bind(&self.addr).await?;
+        let addr = listener.local_addr()?;
+
+        let (state, webhooks) = Server::state(self.store, self.delivery);
+        let feed = state.feed.clone();
+
+        let timeout = self.timeout;
+        let router = Server::router(state)
+            .layer(middleware::from_fn(move |request: Request, next: Next| async move {
+                let (request, received) = track_body(request);
+
+                match tokio::time::timeout(timeout, next.run(request)).await {
+                    Ok(response) => response,
+                    Err(_) if !received.load(Ordering::Relaxed) => Error::RequestTimeout(timeout).into_response(),
+                    Err(_) => Error::Timeout(timeout).into_response(),
+                }
+            }))
+            .layer(DefaultBodyLimit::max(self.body_limit));
+
+        let (trigger, mut triggered) = watch::channel(false);
+        let signal = self.shutdown.unwrap_or_else(|| Box::pin(std::future::pending()));
+
+        let shutdown = async move {
+            // A dropped handle leaves the server running.
+            let triggered = async move {
+                if triggered.wait_for(|triggered| *triggered).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            };
+            tokio::select! {
+                _ = signal => {}
+                _ = triggered => {}
+            }
+            // Event streams never finish on their own, they'd hold the shutdown up for good.
+            feed.close();
+        };
+
+        let (ready, is_ready) = watch::channel(false);
+        let serve = axum::serve(listener, router).with_graceful_shutdown(shutdown).into_future();
+
+        let task = tokio::spawn(async move {
+            ready.send_replace(true);
+            serve.await
+        });
+
+        Ok(ServerHandle { addr, trigger, ready: is_ready, task, webhooks })
+    }
+}
+
+// A running server. Dropping the handle doesn't stop it.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addr: SocketAddr,
+    trigger: watch::Sender<bool>,
+    ready: watch::Receiver<bool>,
+    task: JoinHandle<std::io::Result<()>>,
+    webhooks: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // Resolves once the server is taking requests.
+    pub async fn ready(&self) {
+        let _ = self.ready.clone().wait_for(|ready| *ready).await;
+    }
+
+    // Stops taking new connections and waits for the requests already in flight.
+    pub async fn shutdown(self) -> Result<()> {
+        self.trigger.send_replace(true);
+        self.stopped().await
+    }
+
+    // Waits until the server stops, which only the shutdown signal makes it do.
+    // Webhook deliveries already under way carry on in the background.
+    pub async fn stopped(self) -> Result<()> {
+        self.task.await??;
+        Ok(self.webhooks.await?)
+    }
+}
+
+// Flags the request body once it's been read to the end, an empty one straight away.
+fn track_body(request: Request) -> (Request, Arc<AtomicBool>) {
+    let received = Arc::new(AtomicBool::new(request.body().is_end_stream()));
+    if received.load(Ordering::Relaxed) {
+        return (request, received)
+    }
+
+    let (parts, body) = request.into_parts();
+    let done = received.clone();
+    let end = stream::once(async move {
+        done.store(true, Ordering::Relaxed);
+        None::<std::result::Result<Bytes, axum::Error>>
+    });
+    let body = Body::from_stream(body.into_data_stream().chain(end.filter_map(std::future::ready)));
+
+    (Request::from_parts(parts, body), received)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use super::*;
+
+    #[tokio::test]
+    async fn check_if_webhooks_stop_while_requests_drain() -> Result<()> {
+        let server = Server::builder().addr("127.0.0.1:0").start().await?;
+        server.ready().await;
+        let addr = server.local_addr();
+
+        // Holds the shutdown up until its body comes.
+        let body = r#"{"title": "Slow", "description": "Still draining."}"#;
+        let mut stream = tokio::net::TcpStream::connect(addr).await?;
+        let head = format!("POST /tickets HTTP/1.1\r\nhost: {addr}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n", body.len());
+        stream.write_all(head.as_bytes()).await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        server.trigger.send_replace(true);
+
+        let finished = async {
+            while !server.webhooks.is_finished() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        assert!(tokio::time::timeout(Duration::from_secs(1), finished).await.is_ok(), "Webhooks outlived the feed");
+        assert!(!server.task.is_finished(), "Shutdown didn't wait for the request in flight");
+
+        stream.write_all(body.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200"), "Response received is: {response}");
+
+        server.stopped().await
+    }
+}
@@ -0,0 +1,287 @@
+// Stand-ins for axum's extractors that fail with `Error`, so a bad request gets a
+// problem+json body like every other error instead of axum's plain text.
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
+};
+use chrono::NaiveDate;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    data::{Batch, CommentBody, CommentDraft, LinkDraft, Priority, Status, TicketDescription, TicketDraft},
+    data::{TicketTitle, WebhookDraft},
+    error::{Error, Result},
+    problem::FieldError,
+};
+
+pub struct Path<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for Path<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Send,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        axum::extract::Path::from_request_parts(parts, state).await
+            .map(|axum::extract::Path(value)| Self(value))
+            .map_err(|rejection| Error::HttpStatusCode(rejection.status(), rejection.body_text()))
+    }
+}
+
+pub struct Query<T>(pub T);
+
+impl<S, T> FromRequestParts<S> for Query<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        axum::extract::Query::from_request_parts(parts, state).await
+            .map(|axum::extract::Query(value)| Self(value))
+            .map_err(|rejection| Error::HttpStatusCode(rejection.status(), rejection.body_text()))
+    }
+}
+
+// A JSON body. Every field is checked before it's deserialized, so all that's wrong
+// with it is reported together, each with its path.
+pub struct Payload<T>(pub T);
+
+impl<S, T> FromRequest<S> for Payload<T>
+where
+    S: Send + Sync,
+    T: Checked,
+{
+    type Rejection = Error;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self> {
+        let is_json = request.headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim)
+            .is_some_and(|essence| essence == "application/json" || essence.ends_with("+json"));
+
+        if !is_json {
+            let message = "Expected request with `Content-Type: application/json`".to_string();
+            return Err(Error::HttpStatusCode(StatusCode::UNSUPPORTED_MEDIA_TYPE, message))
+        }
+
+        let bytes = Bytes::from_request(request, state).await
+            .map_err(|rejection| Error::HttpStatusCode(rejection.status(), rejection.body_text()))?;
+        let body: Value = serde_json::from_slice(&bytes).map_err(Error::MalformedJson)?;
+
+        let mut errors = Vec::new();
+        T::check(&body, &mut errors);
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors))
+        }
+
+        serde_path_to_error::deserialize(body).map(Self).map_err(|error| {
+            let field = error.path().to_string();
+            Error::Validation(vec![FieldError::new(field, "invalid_value", error.into_inner())])
+        })
+    }
+}
+
+// Turns down a field's text, the field's own type says what's wrong with it.
+type Check = fn(&str) -> Result<()>;
+
+// A request body that knows which of its fields can't be left out, and which have
+// to be strings its `Check` lets through. Anything else is left to serde.
+pub trait Checked: DeserializeOwned {
+    const REQUIRED: &'static [&'static str] = &[];
+    const FIELDS: &'static [(&'static str, Check)] = &[];
+
+    fn check(body: &Value, errors: &mut Vec<FieldError>) {
+        check_object(body, Self::REQUIRED, Self::FIELDS, "", errors);
+    }
+}
+
+impl Checked for TicketDraft {
+    const REQUIRED: &'static [&'static str] = &["title", "description"];
+    const FIELDS: &'static [(&'static str, Check)] = &[
+        ("title", title),
+        ("description", description),
+        ("priority", priority),
+        ("due_date", due_date),
+    ];
+}
+
+// The body of `PATCH /tickets/{id}`, where anything can be left out. The id and
+// version come from the path and `If-Match` instead.
+#[derive(Debug, Deserialize)]
+pub struct PatchBody(pub Map<String, Value>);
+
+impl Checked for PatchBody {
+    const FIELDS: &'static [(&'static str, Check)] = &[
+        ("title", title),
+        ("description", description),
+        ("status", status),
+        ("priority", priority),
+        ("due_date", due_date),
+    ];
+}
+
+impl Checked for CommentDraft {
+    const REQUIRED: &'static [&'static str] = &["body"];
+    const FIELDS: &'static [(&'static str, Check)] = &[("body", comment_body)];
+}
+
+impl Checked for LinkDraft {
+    const REQUIRED: &'static [&'static str] = &["kind", "to"];
+}
+
+impl Checked for WebhookDraft {
+    const REQUIRED: &'static [&'static str] = &["url", "secret"];
+}
+
+impl Checked for Batch {
+    // Only atomic batches are turned away as a whole, in the other mode every
+    // item gets its own outcome.
+    fn check(body: &Value, errors: &mut Vec<FieldError>) {
+        check_object(body, &["ops"], &[], "", errors);
+
+        if body.get("mode").and_then(Value::as_str) == Some("per_item") {
+            return
+        }
+
+        let Some(ops) = body.get("ops").and_then(Value::as_array) else {
+            return
+        };
+
+        for (index, op) in ops.iter().enumerate() {
+            let (required, fields) = match op.get("op").and_then(Value::as_str) {
+                Some("create") => (TicketDraft::REQUIRED, TicketDraft::FIELDS),
+                Some("patch") => (&["op"][..], PatchBody::FIELDS),
+                _ => (&["op"][..], &[][..]),
+            };
+            check_object(op, required, fields, &format!("ops[{index}]"), errors);
+        }
+    }
+}
+
+fn check_object(
+    body: &Value,
+    required: &[&str],
+    fields: &[(&str, Check)],
+    path: &str,
+    errors: &mut Vec<FieldError>,
+) {
+    let Value::Object(object) = body else {
+        let field = if path.is_empty() { "$" } else { path };
+        errors.push(FieldError::new(field, "invalid_type", "Expected a JSON object."));
+        return
+    };
+
+    for name in required {
+        if object.get(*name).is_none_or(Value::is_null) {
+            errors.push(FieldError::new(*name, "required", format!("Missing {name}.")).within(path));
+        }
+    }
+
+    for (name, value) in object {
+        let Some((_, check)) = fields.iter().find(|(field, _)| field == name) else {
+            continue
+        };
+        let found = match check_field(name, value, *check) {
+            Ok(()) => continue,
+            Err(error) => error.field_errors(),
+        };
+        errors.extend(found.into_iter().map(|error| error.within(path)));
+    }
+}
+
+fn check_field(name: &str, value: &Value, check: Check) -> Result<()> {
+    match value {
+        Value::Null => Ok(()),
+        Value::String(text) => check(text),
+        _ => {
+            let error = FieldError::new(name, "invalid_type", format!("Expected a string, got {value}."));
+            Err(Error::Validation(vec![error]))
+        }
+    }
+}
+
+fn title(text: &str) -> Result<()> {
+    TicketTitle::try_from(text)?;
+    Ok(())
+}
+
+fn description(text: &str) -> Result<()> {
+    TicketDescription::try_from(text)?;
+    Ok(())
+}
+
+fn status(text: &str) -> Result<()> {
+    Status::try_from(text)?;
+    Ok(())
+}
+
+fn priority(text: &str) -> Result<()> {
+    Priority::try_from(text)?;
+    Ok(())
+}
+
+fn due_date(text: &str) -> Result<()> {
+    text.parse::<NaiveDate>()?;
+    Ok(())
+}
+
+fn comment_body(text: &str) -> Result<()> {
+    CommentBody::try_from(text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn fields<T: Checked>(body: Value) -> Vec<(String, String, Option<usize>)> {
+        let mut errors = Vec::new();
+        T::check(&body, &mut errors);
+        errors.into_iter().map(|error| (error.field, error.code, error.limit)).collect()
+    }
+
+    #[test]
+    fn check_if_every_bad_field_is_reported_with_its_path() {
+        let found = fields::<TicketDraft>(json!({ "title": "x".repeat(51), "priority": "urgent" }));
+        assert_eq!(found, vec![
+            ("description".into(), "required".into(), None),
+            ("priority".into(), "invalid_value".into(), None),
+            ("title".into(), "too_long".into(), Some(50)),
+        ]);
+
+        let found = fields::<Batch>(json!({ "ops": [
+            { "op": "create", "title": "Fine", "description": "Fine." },
+            { "op": "patch", "id": 0, "description": "", "status": 3 },
+        ]}));
+        assert_eq!(found, vec![
+            ("ops[1].description".into(), "required".into(), None),
+            ("ops[1].status".into(), "invalid_type".into(), None),
+        ]);
+
+        let found = fields::<Batch>(json!({ "mode": "per_item", "ops": [{ "op": "create" }] }));
+        assert_eq!(found, vec![]);
+    }
+
+    #[test]
+    fn check_if_each_body_only_checks_its_own_fields() {
+        // A draft has no status to check, a comment no title.
+        assert_eq!(fields::<TicketDraft>(json!({ "title": "Fine", "description": "Fine.", "status": 3 })), vec![]);
+        assert_eq!(fields::<CommentDraft>(json!({ "body": "Fine.", "title": "" })), vec![]);
+
+        let found = fields::<PatchBody>(json!({ "due_date": "soon", "status": "Sideways" }));
+        assert_eq!(found, vec![
+            ("due_date".into(), "invalid_date".into(), None),
+            ("status".into(), "invalid_value".into(), None),
+        ]);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use futures_util::{stream, Stream, StreamExt};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{watch, Mutex as AsyncMutex, MutexGuard};
+
+use crate::data::{Event, EventFilter, EventKind, Ticket};
+use crate::error::{Error, Result};
+
+// How many past events are kept around for clients resuming after a disconnect.
+pub const BACKLOG: usize = 1024;
+
+// Fans ticket changes out to every subscriber, numbering them as they go.
+// Nothing of it is kept across restarts, so every feed gets an epoch of its own,
+// the time it was made, to tell its sequence numbers from the last one's.
+#[derive(Debug)]
+pub struct Feed {
+    epoch: u64,
+    sender: broadcast::Sender<Event>,
+    recent: Mutex<Recent>,
+    closed: watch::Sender<bool>,
+    changing: AsyncMutex<()>,
+}
+
+#[derive(Debug, Default)]
+struct Recent {
+    seq: u64,
+    events: VecDeque<Event>,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        let epoch = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64);
+
+        Self {
+            epoch,
+            sender: broadcast::channel(BACKLOG).0,
+            recent: Default::default(),
+            closed: watch::channel(false).0,
+            changing: AsyncMutex::new(()),
+        }
+    }
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    // Held from a change to the store until it's published, so events are numbered
+    // in the order the store took the changes in, whichever request finishes first.
+    pub async fn changing(&self) -> MutexGuard<'_, ()> {
+        self.changing.lock().await
+    }
+
+    pub fn publish(&self, kind: EventKind, ticket: Ticket) {
+        let mut recent = self.recent.lock().unwrap();
+
+        recent.seq += 1;
+        let event = Event { epoch: self.epoch, seq: recent.seq, kind, ticket };
+
+        if recent.events.len() == BACKLOG {
+            recent.events.pop_front();
+        }
+        recent.events.push_back(event.clone());
+
+        // Nobody listening is fine, the backlog still has it.
+        let _ = self.sender.send(event);
+    }
+
+    // Ends every subscription, now and later, so long-lived connections let go of
+    // a server that's shutting down.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    // Replays what's still in the backlog after `filter.after`, then follows along live.
+    // Subscribing under the same lock `publish` takes means nothing falls in between.
+    // A subscriber that falls too far behind is cut off, it can resume from the last
+    // event it got. Resuming from before the backlog, or from another epoch, fails
+    // with `Error::MissedEvents` instead of quietly leaving out what's gone.
+    pub fn subscribe(&self, filter: EventFilter) -> Result<impl Stream<Item = Event> + Send + 'static> {
+        let recent = self.recent.lock().unwrap();
+
+        if let Some(after) = filter.after {
+            let oldest = recent.events.front().map_or(recent.seq + 1, |event| event.seq);
+            let elsewhere = filter.epoch.is_some_and(|epoch| epoch != self.epoch);
+
+            if elsewhere || after > recent.seq || after + 1 < oldest {
+                return Err(Error::MissedEvents { after })
+            }
+        }
+
+        let receiver = self.sender.subscribe();
+        let replay = if filter.live && filter.after.is_none() {
+            Vec::new()
+        } else {
+            recent.events.iter().filter(|event| filter.matches(event)).cloned().collect()
+        };
+
+        let live = stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((event, receiver)),
+                Err(RecvError::Lagged(_) | RecvError::Closed) => None,
+            }
+        });
+
+        // Dropping the feed closes the channel anyway, only `close` needs watching for.
+        let mut closed = self.closed.subscribe();
+        let closed = async move {
+            if closed.wait_for(|closed| *closed).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        };
+
+        let events = stream::iter(replay)
+            .chain(live.filter(move |event| std::future::ready(filter.matches(event))))
+            .take_until(closed);
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{Status, TicketId};
+    use super::*;
+
+    #[tokio::test]
+    async fn check_if_subscribers_get_the_backlog_then_live_events() {
+        let feed = Feed::new();
+        let ticket = |id| Ticket::with(TicketId(id), "Fed", "Through.", Status::ToDo).unwrap();
+
+        feed.publish(EventKind::Created, ticket(0));
+        feed.publish(EventKind::Created, ticket(1));
+
+        let events = feed.subscribe(EventFilter { after: Some(1), ..Default::default() }).unwrap();
+        let only_zero = feed.subscribe(EventFilter { id: Some(TicketId(0)), ..Default::default() }).unwrap();
+        let live = feed.subscribe(EventFilter { live: true, ..Default::default() }).unwrap();
+
+        feed.publish(EventKind::Deleted, ticket(0));
+        drop(feed);
+
+        let seqs = |events: Vec<Event>| events.iter().map(|event| (event.seq, event.kind)).collect::<Vec<_>>();
+
+        assert_eq!(seqs(events.collect().await), vec![(2, EventKind::Created), (3, EventKind::Deleted)]);
+        assert_eq!(seqs(only_zero.collect().await), vec![(1, EventKind::Created), (3, EventKind::Deleted)]);
+        assert_eq!(seqs(live.collect().await), vec![(3, EventKind::Deleted)]);
+    }
+
+    #[tokio::test]
+    async fn check_if_closing_ends_every_subscription() {
+        let feed = Feed::new();
+        let ticket = Ticket::with(TicketId(0), "Fed", "Through.", Status::ToDo).unwrap();
+
+        feed.publish(EventKind::Created, ticket);
+        let before = feed.subscribe(EventFilter::default()).unwrap();
+
+        feed.close();
+        let after = feed.subscribe(EventFilter::default()).unwrap();
+        assert!(feed.is_closed());
+
+        assert_eq!(before.count().await, 0);
+        assert_eq!(after.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn check_if_cursors_that_cannot_be_resumed_are_turned_down() {
+        let feed = Feed::new();
+        let ticket = Ticket::with(TicketId(0), "Fed", "Through.", Status::ToDo).unwrap();
+
+        for _ in 0..BACKLOG + 2 {
+            feed.publish(EventKind::Patched, ticket.clone());
+        }
+
+        let subscribe = |after, epoch| feed.subscribe(EventFilter { after: Some(after), epoch, ..Default::default() });
+        let epoch = feed.epoch();
+
+        // The backlog starts at 3, so 2 is the last cursor that misses nothing.
+        assert!(subscribe(2, Some(epoch)).is_ok());
+        assert!(matches!(subscribe(1, Some(epoch)), Err(Error::MissedEvents { after: 1 })));
+
+        // From another run of the server.
+        assert!(matches!(subscribe(2, Some(epoch + 1)), Err(Error::MissedEvents { after: 2 })));
+        assert!(matches!(subscribe(BACKLOG as u64 + 3, None), Err(Error::MissedEvents { .. })));
+    }
+}
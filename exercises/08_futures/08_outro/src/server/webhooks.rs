@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use chrono::Utc;
+use futures_util::StreamExt;
+use reqwest::header;
+use tokio::task::JoinHandle;
+
+use crate::{
+    data::{DeadLetter, Event, EventFilter, Missed, Webhook},
+    data::webhook::{signature, EVENT_HEADER, SIGNATURE_HEADER},
+    error::{Error, Result},
+    server::{Feed, Store},
+};
+
+// How many dead letters are kept, the oldest go first.
+pub const DEAD_LETTERS: usize = 1000;
+
+// How hard to try before giving up on a webhook. The wait after the n-th failed
+// attempt is `initial_backoff * 2^(n - 1)`, capped at `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delivery {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // Per attempt.
+    pub timeout: Duration,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Delivery {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+// POSTs every ticket event to every registered webhook. Each delivery retries on
+// its own, so a slow receiver doesn't hold up the others, but it also means a
+// receiver can see events out of order. `Event::seq` tells it the real order.
+#[derive(Debug)]
+pub struct Webhooks {
+    client: reqwest::Client,
+    delivery: Delivery,
+    dead_letters: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl Webhooks {
+    pub fn new(delivery: Delivery) -> Self {
+        Self { client: reqwest::Client::new(), delivery, dead_letters: Default::default() }
+    }
+
+    // Oldest first.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Follows the feed until it's closed or goes away with the server. Falling
+    // behind only means picking up again after the last event seen, or with
+    // whatever the backlog still has once that's gone too. What went missing in
+    // between is dead-lettered for every webhook.
+    pub fn spawn(self: Arc<Self>, store: Store, feed: Weak<Feed>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut after = None;
+            // The last event seen before the backlog moved on without us.
+            let mut lost_after = None;
+
+            while let Some(feed) = feed.upgrade() {
+                // Connections still draining keep the feed alive, but it has nothing more to give.
+                if feed.is_closed() {
+                    break
+                }
+
+                let events = feed.subscribe(EventFilter { after, ..Default::default() });
+                drop(feed);
+
+                let Ok(events) = events else {
+                    lost_after = after.take();
+                    continue
+                };
+
+                let mut events = std::pin::pin!(events);
+
+                while let Some(event) = events.next().await {
+                    if let Some(last) = lost_after.take().filter(|last| last + 1 < event.seq) {
+                        self.missed(&store, Missed { first: last + 1, last: event.seq - 1 }).await;
+                    }
+
+                    after = Some(event.seq);
+                    self.dispatch(&store, event).await;
+                }
+            }
+        })
+    }
+
+    async fn dispatch(self: &Arc<Self>, store: &Store, event: Event) {
+        // Nobody to tell if the store can't say who's listening.
+        let Ok(webhooks) = store.webhooks().await else {
+            return
+        };
+
+        for webhook in webhooks {
+            tokio::spawn(self.clone().deliver(webhook, event.clone()));
+        }
+    }
+
+    async fn missed(&self, store: &Store, missed: Missed) {
+        let Ok(webhooks) = store.webhooks().await else {
+            return
+        };
+
+        for webhook in webhooks {
+            self.bury(DeadLetter {
+                webhook: webhook.id,
+                url: webhook.url,
+                event: None,
+                missed: Some(missed),
+                attempts: 0,
+                error: format!("Events {} to {} were dropped before they could be sent.", missed.first, missed.last),
+                failed_at: Utc::now(),
+            });
+        }
+    }
+
+    fn bury(&self, dead_letter: DeadLetter) {
+        let mut dead_letters = self.dead_letters.lock().unwrap();
+
+        if dead_letters.len() == DEAD_LETTERS {
+            dead_letters.pop_front();
+        }
+        dead_letters.push_back(dead_letter);
+    }
+
+    async fn deliver(self: Arc<Self>, webhook: Webhook, event: Event) {
+        let Ok(body) = serde_json::to_vec(&event) else {
+            return
+        };
+
+        let attempts = self.delivery.attempts.max(1);
+
+        for attempt in 1..=attempts {
+            let error = match self.post(&webhook, &event, &body).await {
+                Ok(()) => return,
+                Err(error) => error,
+            };
+
+            if attempt == attempts {
+                self.bury(DeadLetter {
+                    webhook: webhook.id,
+                    url: webhook.url,
+                    event: Some(event),
+                    missed: None,
+                    attempts,
+                    error: error.to_string(),
+                    failed_at: Utc::now(),
+                });
+                return
+            }
+
+            tokio::time::sleep(self.delivery.backoff(attempt)).await;
+        }
+    }
+
+    async fn post(&self, webhook: &Webhook, event: &Event, body: &[u8]) -> Result<()> {
+        let response = self.client
+            .post(&webhook.url)
+            .timeout(self.delivery.timeout)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature(&webhook.secret, body))
+            .header(EVENT_HEADER, event.kind.to_string())
+            .body(body.to_vec())
+            .send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            return Err(Error::HttpStatusCode(status, response.text().await?))
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::RwLock;
+    use crate::data::{EventKind, Status, Ticket, TicketId, WebhookDraft};
+    use crate::server::feed::BACKLOG;
+    use crate::store::{TicketRepository, TicketStore};
+    use super::*;
+
+    #[test]
+    fn check_if_backoff_doubles_up_to_the_cap() {
+        let delivery = Delivery {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        let waits = (1..=5).map(|attempt| delivery.backoff(attempt).as_millis()).collect::<Vec<_>>();
+
+        assert_eq!(waits, vec![100, 200, 400, 500, 500]);
+        assert_eq!(delivery.backoff(u32::MAX), delivery.max_backoff);
+    }
+    #[tokio::test]
+    async fn check_if_events_lost_to_lagging_are_dead_lettered() {
+        let tickets = Arc::new(RwLock::new(TicketStore::new()));
+        let store: Store = tickets.clone();
+        let webhook = store.add_webhook(WebhookDraft::with("http://127.0.0.1:1/", "s3cret").unwrap()).await.unwrap();
+
+        // Nothing listens there, but no delivery gives up before the test is over.
+        let delivery = Delivery { attempts: 2, initial_backoff: Duration::from_secs(3600), ..Default::default() };
+        let feed = Arc::new(Feed::new());
+        let webhooks = Arc::new(Webhooks::new(delivery));
+        let task = webhooks.clone().spawn(store, Arc::downgrade(&feed));
+        let ticket = Ticket::with(TicketId(0), "Fed", "Through.", Status::ToDo).unwrap();
+
+        // Stuck on the first event until the store lets go, while the rest run past the backlog.
+        let held = tickets.write().await;
+        feed.publish(EventKind::Created, ticket.clone());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for _ in 0..BACKLOG + 10 {
+            feed.publish(EventKind::Patched, ticket.clone());
+        }
+        drop(held);
+
+        let missed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(missed) = webhooks.dead_letters().iter().find_map(|dead_letter| dead_letter.missed) {
+                    return missed
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.expect("The gap should be dead-lettered");
+
+        // The backlog starts at 12, after the first event only 2 to 11 are gone.
+        assert_eq!(missed, Missed { first: 2, last: 11 });
+        assert_eq!(webhooks.dead_letters().len(), 1);
+        assert_eq!(webhooks.dead_letters()[0].webhook, webhook.id);
+
+        feed.close();
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,42 @@
+// Who a change is made on behalf of, so the history can say. The server sets it for
+// each request from the `X-Actor` header, stores pick it up whenever they record an
+// entry. Outside of a scope nobody is acting, and entries go without.
+
+use std::future::Future;
+
+pub const HEADER: &str = "x-actor";
+
+tokio::task_local! {
+    static ACTOR: Option<String>;
+}
+
+pub async fn scope<F: Future>(actor: Option<String>, f: F) -> F::Output {
+    ACTOR.scope(actor, f).await
+}
+
+// For blocking code, which runs outside of the task that set the actor.
+pub fn sync_scope<R>(actor: Option<String>, f: impl FnOnce() -> R) -> R {
+    ACTOR.sync_scope(actor, f)
+}
+
+pub fn current() -> Option<String> {
+    ACTOR.try_with(Clone::clone).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_if_the_actor_is_only_set_within_its_scope() {
+        assert_eq!(current(), None);
+
+        let inside = scope(Some("ada".into()), async { current() }).await;
+        assert_eq!(inside, Some("ada".into()));
+
+        let blocking = tokio::task::spawn_blocking(|| sync_scope(Some("grace".into()), current)).await.unwrap();
+        assert_eq!(blocking, Some("grace".into()));
+
+        assert_eq!(current(), None);
+    }
+}
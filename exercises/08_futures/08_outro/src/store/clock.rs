@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+use std::sync::Mutex;
+use chrono::{DateTime, TimeDelta, Utc};
+
+// Where stores get the time from, so timestamps can be pinned down in tests.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// Only moves when told to.
+#[derive(Debug)]
+pub struct ManualClock(Mutex<DateTime<Utc>>);
+
+impl ManualClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self(Mutex::new(start))
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: TimeDelta) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_if_manual_clock_only_moves_when_told_to() {
+        let start = DateTime::parse_from_rfc3339("2024-03-01T09:00:00Z").unwrap().to_utc();
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+
+        clock.advance(TimeDelta::hours(2));
+        assert_eq!(clock.now().to_rfc3339(), "2024-03-01T11:00:00+00:00");
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
@@ -0,0 +1,832 @@
+// Behaviour every `TicketRepository` has to agree on. Each implementation gets
+// its own copy of the suite through `conformance_suite!` at the bottom.
+
+use std::sync::Arc;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use tempfile::TempDir;
+use tokio::{sync::RwLock, task};
+
+use crate::data::{
+    CommentDraft, CommentId, Label, Link, LinkKind, Priority, SortBy, Status, TicketDescription, TicketDraft, TicketId, TicketPatch, TicketQuery,
+    TicketTitle, WebhookDraft, WebhookId,
+};
+use crate::data::{Batch, BatchMode, BatchOp, BatchOutcome};
+use crate::error::Error;
+use super::{Clock, ManualClock, SqliteStore, TicketRepository, TicketStore};
+
+pub struct Fixture<R> {
+    pub repo: Arc<R>,
+    pub clock: Arc<ManualClock>,
+    // Keeps on-disk implementations alive for as long as the test runs.
+    _dir: Option<TempDir>,
+}
+
+fn start() -> DateTime<Utc> {
+    "2024-03-01T09:00:00Z".parse().unwrap()
+}
+
+fn in_memory() -> Fixture<RwLock<TicketStore>> {
+    let clock = Arc::new(ManualClock::new(start()));
+    let store = TicketStore::new().with_clock(clock.clone());
+    Fixture { repo: Arc::new(RwLock::new(store)), clock, _dir: None }
+}
+
+fn journaled() -> Fixture<RwLock<TicketStore>> {
+    let dir = tempfile::tempdir().unwrap();
+    let clock = Arc::new(ManualClock::new(start()));
+    let store = TicketStore::open(dir.path()).unwrap().with_clock(clock.clone());
+    Fixture { repo: Arc::new(RwLock::new(store)), clock, _dir: Some(dir) }
+}
+
+fn sqlite() -> Fixture<SqliteStore> {
+    let dir = tempfile::tempdir().unwrap();
+    let clock = Arc::new(ManualClock::new(start()));
+    let store = SqliteStore::open(dir.path().join("tickets.db")).unwrap().with_clock(clock.clone());
+    Fixture { repo: Arc::new(store), clock, _dir: Some(dir) }
+}
+
+fn create_draft(title: &str, description: &str) -> TicketDraft {
+    TicketDraft {
+        title: TicketTitle::try_from(title).unwrap(),
+        description: TicketDescription::try_from(description).unwrap(),
+        priority: None,
+        due_date: None,
+    }
+}
+
+async fn ids(repo: &impl TicketRepository, query: TicketQuery) -> Vec<TicketId> {
+    repo.list(query).await.unwrap().tickets.into_iter().map(|t| t.id).collect()
+}
+
+async fn check_if_create_returns_incrementing_ids_starting_from_zero(repo: Arc<impl TicketRepository>) {
+    let id0 = repo.create(create_draft("First", "First ticket")).await.unwrap();
+    let id1 = repo.create(create_draft("Second", "Second ticket")).await.unwrap();
+    let id2 = repo.create(create_draft("Third", "Third ticket")).await.unwrap();
+
+    assert_eq!(id0, TicketId(0));
+    assert_eq!(id1, TicketId(1));
+    assert_eq!(id2, TicketId(2));
+}
+
+async fn check_if_get_returns_inserted_ticket_with_default_status(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("The thing", "A very scary movie...")).await.unwrap();
+    let got = repo.get(id).await.unwrap().unwrap();
+
+    assert_eq!(got.id, id);
+    assert_eq!(got.title.to_string(), "The thing");
+    assert_eq!(got.status, Status::ToDo);
+    assert_eq!(got.description.to_string(), "A very scary movie...");
+}
+
+async fn check_if_get_unknown_id_returns_none(repo: Arc<impl TicketRepository>) {
+    assert!(repo.get(TicketId(42)).await.unwrap().is_none());
+}
+
+async fn check_if_list_returns_every_ticket_ordered_by_id(repo: Arc<impl TicketRepository>) {
+    assert!(repo.list(TicketQuery::default()).await.unwrap().tickets.is_empty());
+
+    for title in ["First", "Second", "Third"] {
+        repo.create(create_draft(title, "Listed.")).await.unwrap();
+    }
+
+    let titles: Vec<_> = repo.list(TicketQuery::default()).await.unwrap()
+        .tickets
+        .into_iter()
+        .map(|t| t.title.to_string())
+        .collect();
+
+    assert_eq!(titles, vec!["First", "Second", "Third"]);
+}
+
+async fn check_if_patch_allows_status_update(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("The Science", "A documentary about science.")).await.unwrap();
+
+    let patch = TicketPatch { id, status: Some(Status::InProgress), ..Default::default() };
+    let patched = repo.patch(patch).await.unwrap().unwrap();
+    assert_eq!(patched.status, Status::InProgress);
+
+    let got = repo.get(id).await.unwrap().unwrap();
+    assert_eq!(got.status, Status::InProgress);
+    assert_eq!(got.title.to_string(), "The Science", "Fields left out of the patch must not change");
+}
+
+async fn check_if_patch_unknown_id_returns_none(repo: Arc<impl TicketRepository>) {
+    let patch = TicketPatch { id: TicketId(42), status: Some(Status::Done), ..Default::default() };
+    assert!(repo.patch(patch).await.unwrap().is_none());
+}
+
+async fn check_if_delete_removes_ticket_without_reusing_its_id(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Doomed", "Won't be around for long.")).await.unwrap();
+
+    let deleted = repo.delete(id).await.unwrap().unwrap();
+    assert_eq!(deleted.id, id);
+
+    assert!(repo.get(id).await.unwrap().is_none());
+    assert!(repo.delete(id).await.unwrap().is_none());
+
+    let next = repo.create(create_draft("Survivor", "Gets a fresh id.")).await.unwrap();
+    assert_ne!(next, id);
+}
+
+async fn check_if_archived_tickets_are_left_out_of_list_until_restored(repo: Arc<impl TicketRepository>) {
+    let kept = repo.create(create_draft("Kept", "Stays in the list.")).await.unwrap();
+    let archived = repo.create(create_draft("Archived", "Hidden for now.")).await.unwrap();
+
+    let ticket = repo.archive(archived).await.unwrap().unwrap();
+    assert!(ticket.archived);
+    assert!(repo.get(archived).await.unwrap().unwrap().archived, "Archived tickets can still be retrieved");
+
+    let with_archived = TicketQuery { archived: true, ..Default::default() };
+    assert_eq!(ids(&*repo, TicketQuery::default()).await, vec![kept]);
+    assert_eq!(ids(&*repo, with_archived).await, vec![kept, archived]);
+
+    let ticket = repo.restore(archived).await.unwrap().unwrap();
+    assert!(!ticket.archived);
+    assert_eq!(ids(&*repo, TicketQuery::default()).await, vec![kept, archived]);
+}
+
+async fn check_if_archive_and_restore_unknown_id_return_none(repo: Arc<impl TicketRepository>) {
+    assert!(repo.archive(TicketId(42)).await.unwrap().is_none());
+    assert!(repo.restore(TicketId(42)).await.unwrap().is_none());
+}
+
+async fn check_if_every_change_bumps_the_version(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Versioned", "Counting changes.")).await.unwrap();
+    assert_eq!(repo.get(id).await.unwrap().unwrap().version, 1);
+
+    let patch = TicketPatch { id, status: Some(Status::InProgress), ..Default::default() };
+    assert_eq!(repo.patch(patch).await.unwrap().unwrap().version, 2);
+
+    assert_eq!(repo.archive(id).await.unwrap().unwrap().version, 3);
+    assert_eq!(repo.archive(id).await.unwrap().unwrap().version, 3, "Archiving twice changes nothing");
+    assert_eq!(repo.restore(id).await.unwrap().unwrap().version, 4);
+    assert_eq!(repo.get(id).await.unwrap().unwrap().version, 4);
+}
+
+async fn check_if_patch_with_stale_version_is_rejected(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Contested", "Two people at once.")).await.unwrap();
+
+    let first = TicketPatch { id, status: Some(Status::InProgress), version: Some(1), ..Default::default() };
+    assert_eq!(repo.patch(first).await.unwrap().unwrap().version, 2);
+
+    let second = TicketPatch { id, status: Some(Status::Done), version: Some(1), ..Default::default() };
+    match repo.patch(second).await {
+        Err(Error::VersionMismatch { id: got, expected: 1, actual: 2 }) => assert_eq!(got, id),
+        other => panic!("Expected a version mismatch, got {other:?}"),
+    }
+
+    let ticket = repo.get(id).await.unwrap().unwrap();
+    assert_eq!((ticket.status, ticket.version), (Status::InProgress, 2), "A rejected patch must change nothing");
+}
+
+async fn check_if_every_change_is_kept_in_history(repo: Arc<impl TicketRepository>) {
+    use crate::data::history::{Action, Change};
+
+    let id = repo.create(create_draft("Tracked", "Every step of the way.")).await.unwrap();
+    let patch = TicketPatch { id, status: Some(Status::InProgress), ..Default::default() };
+    repo.patch(patch).await.unwrap();
+    repo.archive(id).await.unwrap();
+    repo.restore(id).await.unwrap();
+
+    let history = repo.history(id).await.unwrap().unwrap();
+
+    let actions: Vec<_> = history.iter().map(|e| (e.version, e.action)).collect();
+    assert_eq!(
+        actions,
+        vec![(1, Action::Created), (2, Action::Patched), (3, Action::Archived), (4, Action::Restored)]
+    );
+
+    assert_eq!(history[0].status, Some(Change { old: None, new: Status::ToDo }));
+    assert_eq!(history[1].status, Some(Change { old: Some(Status::ToDo), new: Status::InProgress }));
+    assert_eq!(history[1].title, None, "Untouched fields aren't recorded");
+    assert!(history.windows(2).all(|w| w[0].at <= w[1].at));
+
+    let stale = TicketPatch { id, status: Some(Status::Done), version: Some(1), ..Default::default() };
+    assert!(repo.patch(stale).await.is_err());
+    assert_eq!(repo.history(id).await.unwrap().unwrap().len(), 4, "Rejected patches leave no trace");
+}
+
+async fn check_if_history_records_who_made_each_change(repo: Arc<impl TicketRepository>) {
+    use crate::store::actor;
+
+    let id = repo.create(create_draft("Audited", "By whom?")).await.unwrap();
+
+    let patch = TicketPatch { id, status: Some(Status::InProgress), ..Default::default() };
+    actor::scope(Some("ada".into()), repo.patch(patch)).await.unwrap();
+    actor::scope(Some("grace".into()), repo.archive(id)).await.unwrap();
+
+    let actors = repo.history(id).await.unwrap().unwrap().into_iter().map(|entry| entry.actor).collect::<Vec<_>>();
+    assert_eq!(actors, vec![None, Some("ada".into()), Some("grace".into())]);
+}
+
+async fn check_if_history_outlives_a_deleted_ticket(repo: Arc<impl TicketRepository>) {
+    use crate::data::history::Action;
+    use crate::store::actor;
+
+    assert!(repo.history(TicketId(42)).await.unwrap().is_none());
+
+    let id = repo.create(create_draft("Gone", "Soon.")).await.unwrap();
+    actor::scope(Some("ada".into()), repo.delete(id)).await.unwrap();
+
+    let history = repo.history(id).await.unwrap().unwrap();
+    assert_eq!(
+        history.iter().map(|entry| (entry.version, entry.action, entry.actor.as_deref())).collect::<Vec<_>>(),
+        vec![(1, Action::Created, None), (2, Action::Deleted, Some("ada"))]
+    );
+}
+
+async fn check_if_patch_cannot_leave_done(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Finished", "No going back.")).await.unwrap();
+    repo.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await.unwrap();
+
+    let patch = TicketPatch { id, status: Some(Status::ToDo), ..Default::default() };
+    match repo.patch(patch).await {
+        Err(Error::InvalidTransition { from: Status::Done, to: Status::ToDo, allowed, .. }) => {
+            assert!(allowed.is_empty())
+        }
+        other => panic!("Expected an invalid transition, got {other:?}"),
+    }
+
+    let ticket = repo.get(id).await.unwrap().unwrap();
+    assert_eq!((ticket.status, ticket.version), (Status::Done, 2), "A rejected patch must change nothing");
+    assert_eq!(repo.history(id).await.unwrap().unwrap().len(), 2);
+}
+
+async fn check_if_reopen_moves_done_tickets_back(repo: Arc<impl TicketRepository>) {
+    use crate::data::history::{Action, Change};
+
+    let id = repo.create(create_draft("Regressed", "It broke again.")).await.unwrap();
+    repo.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await.unwrap();
+
+    assert!(matches!(repo.reopen(id, Some(1)).await, Err(Error::VersionMismatch { .. })));
+
+    let ticket = repo.reopen(id, Some(2)).await.unwrap().unwrap();
+    assert_eq!((ticket.status, ticket.version), (Status::ToDo, 3));
+    assert_eq!(repo.get(id).await.unwrap().unwrap(), ticket);
+
+    let history = repo.history(id).await.unwrap().unwrap();
+    assert_eq!(history[2].action, Action::Reopened);
+    assert_eq!(history[2].status, Some(Change { old: Some(Status::Done), new: Status::ToDo }));
+}
+
+async fn check_if_reopen_is_refused_unless_done(repo: Arc<impl TicketRepository>) {
+    assert!(repo.reopen(TicketId(42), None).await.unwrap().is_none());
+
+    let id = repo.create(create_draft("Open", "Still being worked on.")).await.unwrap();
+    match repo.reopen(id, None).await {
+        Err(Error::CannotReopen { from: Status::ToDo, allowed, .. }) => {
+            assert_eq!(allowed, vec![Status::InProgress, Status::Done])
+        }
+        other => panic!("Expected reopening to fail, got {other:?}"),
+    }
+    assert_eq!(repo.get(id).await.unwrap().unwrap().version, 1);
+}
+
+async fn check_if_timestamps_follow_the_clock(repo: Arc<impl TicketRepository>, clock: Arc<ManualClock>) {
+    let id = repo.create(create_draft("Timed", "Watching the clock.")).await.unwrap();
+
+    let ticket = repo.get(id).await.unwrap().unwrap();
+    assert_eq!((ticket.created_at, ticket.updated_at, ticket.status_changed_at), (start(), start(), start()));
+
+    clock.advance(TimeDelta::hours(1));
+    let title = TicketTitle::try_from("Renamed").unwrap();
+    let ticket = repo.patch(TicketPatch { id, title: Some(title), ..Default::default() }).await.unwrap().unwrap();
+    assert_eq!(ticket.updated_at, clock.now());
+    assert_eq!(ticket.status_changed_at, start(), "Only status changes move status_changed_at");
+
+    clock.advance(TimeDelta::milliseconds(1500));
+    let ticket = repo.patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }).await.unwrap().unwrap();
+    assert_eq!((ticket.updated_at, ticket.status_changed_at), (clock.now(), clock.now()));
+
+    clock.advance(TimeDelta::days(1));
+    let ticket = repo.archive(id).await.unwrap().unwrap();
+    assert_eq!((ticket.created_at, ticket.updated_at), (start(), clock.now()));
+
+    clock.advance(TimeDelta::days(1));
+    let ticket = repo.reopen(id, None).await.unwrap().unwrap();
+    assert_eq!((ticket.updated_at, ticket.status_changed_at), (clock.now(), clock.now()));
+
+    assert_eq!(repo.get(id).await.unwrap().unwrap(), ticket, "Timestamps survive a round trip");
+
+    let history = repo.history(id).await.unwrap().unwrap();
+    assert_eq!(history.last().unwrap().at, ticket.updated_at);
+}
+
+async fn check_if_comments_can_be_added_edited_and_deleted(
+    repo: Arc<impl TicketRepository>,
+    clock: Arc<ManualClock>,
+) {
+    let id = repo.create(create_draft("Discussed", "Talk about it below.")).await.unwrap();
+    let other = repo.create(create_draft("Elsewhere", "Unrelated.")).await.unwrap();
+
+    let first = repo.add_comment(id, CommentDraft::with("First!").unwrap()).await.unwrap().unwrap();
+    clock.advance(TimeDelta::minutes(5));
+    let second = repo.add_comment(id, CommentDraft::with("Second.").unwrap()).await.unwrap().unwrap();
+    repo.add_comment(other, CommentDraft::with("Not here.").unwrap()).await.unwrap().unwrap();
+
+    assert_eq!((first.id, first.ticket_id, first.created_at), (CommentId(0), id, start()));
+    assert_eq!(second.id, CommentId(1));
+
+    clock.advance(TimeDelta::minutes(5));
+    let edited = repo.edit_comment(id, first.id, CommentDraft::with("First, edited.").unwrap()).await.unwrap().unwrap();
+    assert_eq!((edited.created_at, edited.updated_at), (start(), clock.now()));
+    assert_eq!(edited.body.to_string(), "First, edited.");
+
+    assert_eq!(repo.comments(id).await.unwrap().unwrap(), vec![edited.clone(), second.clone()]);
+
+    assert_eq!(repo.delete_comment(id, second.id).await.unwrap(), Some(second.clone()));
+    assert!(repo.delete_comment(id, second.id).await.unwrap().is_none());
+    assert_eq!(repo.comments(id).await.unwrap().unwrap(), vec![edited]);
+
+    let third = repo.add_comment(id, CommentDraft::with("Third.").unwrap()).await.unwrap().unwrap();
+    assert_eq!(third.id, CommentId(3), "Comment ids are never reused");
+
+    assert_eq!(repo.get(id).await.unwrap().unwrap().version, 1, "Comments don't change the ticket");
+}
+
+async fn check_if_comments_are_scoped_to_their_ticket(repo: Arc<impl TicketRepository>) {
+    assert!(repo.comments(TicketId(42)).await.unwrap().is_none());
+    assert!(repo.add_comment(TicketId(42), CommentDraft::with("Anyone?").unwrap()).await.unwrap().is_none());
+
+    let id = repo.create(create_draft("Owner", "Has a comment.")).await.unwrap();
+    let other = repo.create(create_draft("Other", "Has none.")).await.unwrap();
+    let comment = repo.add_comment(id, CommentDraft::with("Mine.").unwrap()).await.unwrap().unwrap();
+
+    let draft = CommentDraft::with("Hijacked.").unwrap();
+    assert!(repo.edit_comment(other, comment.id, draft).await.unwrap().is_none());
+    assert!(repo.delete_comment(other, comment.id).await.unwrap().is_none());
+    assert_eq!(repo.comments(other).await.unwrap().unwrap(), vec![]);
+
+    let blank: CommentDraft = serde_json::from_str(r#"{"body":" "}"#).unwrap();
+    assert!(matches!(repo.add_comment(id, blank.clone()).await, Err(Error::CommentBody(_))));
+    assert!(matches!(repo.edit_comment(id, comment.id, blank).await, Err(Error::CommentBody(_))));
+
+    repo.delete(id).await.unwrap();
+    assert!(repo.comments(id).await.unwrap().is_none());
+    assert!(repo.delete_comment(id, comment.id).await.unwrap().is_none());
+}
+
+fn label(name: &str) -> Label {
+    Label::try_from(name).unwrap()
+}
+
+async fn check_if_labels_can_be_added_and_removed(repo: Arc<impl TicketRepository>) {
+    use crate::data::history::Change;
+    use std::collections::BTreeSet;
+
+    assert!(repo.add_label(TicketId(42), label("docs"), None).await.unwrap().is_none());
+
+    let id = repo.create(create_draft("Labelled", "Sorted by area.")).await.unwrap();
+
+    let ticket = repo.add_label(id, label("docs"), Some(1)).await.unwrap().unwrap();
+    assert_eq!(ticket.version, 2);
+    let ticket = repo.add_label(id, label("backend"), None).await.unwrap().unwrap();
+    assert_eq!(ticket.labels, BTreeSet::from([label("backend"), label("docs")]));
+
+    let again = repo.add_label(id, label("docs"), None).await.unwrap().unwrap();
+    assert_eq!(again.version, 3, "Adding a label twice changes nothing");
+
+    assert!(matches!(repo.remove_label(id, label("docs"), Some(1)).await, Err(Error::VersionMismatch { .. })));
+
+    let ticket = repo.remove_label(id, label("docs"), Some(3)).await.unwrap().unwrap();
+    assert_eq!((ticket.version, ticket.labels.clone()), (4, BTreeSet::from([label("backend")])));
+    assert_eq!(repo.remove_label(id, label("docs"), None).await.unwrap().unwrap().version, 4);
+    assert_eq!(repo.get(id).await.unwrap().unwrap(), ticket);
+
+    let history = repo.history(id).await.unwrap().unwrap();
+    assert_eq!(
+        history.last().unwrap().labels,
+        Some(Change {
+            old: Some(BTreeSet::from([label("backend"), label("docs")])),
+            new: BTreeSet::from([label("backend")]),
+        })
+    );
+}
+
+async fn check_if_list_filters_by_label(repo: Arc<impl TicketRepository>) {
+    seed(repo.as_ref()).await;
+
+    for (id, name) in [(0, "musical"), (1, "pets"), (3, "pets"), (4, "musical"), (3, "tiny")] {
+        repo.add_label(TicketId(id), label(name), None).await.unwrap().unwrap();
+    }
+
+    let by_label = |name: &str| TicketQuery { label: Some(label(name)), ..Default::default() };
+
+    assert_eq!(ids(repo.as_ref(), by_label("pets")).await, vec![TicketId(1), TicketId(3)]);
+    assert_eq!(ids(repo.as_ref(), by_label("nothing")).await, vec![]);
+
+    let query = TicketQuery { status: Some(Status::ToDo), ..by_label("musical") };
+    assert_eq!(ids(repo.as_ref(), query).await, vec![TicketId(4)]);
+
+    // The cursor doesn't have to carry the label.
+    let query = TicketQuery { sort: SortBy::Title, after: Some(TicketId(2)), ..by_label("musical") };
+    assert_eq!(ids(repo.as_ref(), query).await, vec![TicketId(0), TicketId(4)]);
+
+    let query = TicketQuery { after: Some(TicketId(2)), ..by_label("musical") };
+    assert_eq!(ids(repo.as_ref(), query).await, vec![TicketId(4)]);
+
+    let query = TicketQuery { limit: Some(1), ..by_label("musical") };
+    let page = repo.list(query).await.unwrap();
+    assert_eq!((page.tickets[0].id, page.next_cursor), (TicketId(0), Some(TicketId(0))));
+
+    let mut query = TicketQuery { sort: SortBy::Title, limit: Some(1), ..by_label("pets") };
+    let page = repo.list(query.clone()).await.unwrap();
+    assert_eq!(page.tickets[0].id, TicketId(3));
+    query.after = page.next_cursor;
+    assert_eq!(repo.list(query).await.unwrap().tickets[0].id, TicketId(1));
+
+    repo.remove_label(TicketId(1), label("pets"), None).await.unwrap();
+    repo.delete(TicketId(3)).await.unwrap();
+    assert_eq!(ids(repo.as_ref(), by_label("pets")).await, vec![]);
+    assert_eq!(ids(repo.as_ref(), by_label("tiny")).await, vec![]);
+}
+
+fn march(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+}
+
+async fn check_if_priority_and_due_date_can_be_set_and_cleared(repo: Arc<impl TicketRepository>) {
+    use crate::data::history::Change;
+
+    let draft = create_draft("Urgent", "Do it now.").with_priority(Priority::High).due_on(march(5));
+    let id = repo.create(draft).await.unwrap();
+
+    let ticket = repo.get(id).await.unwrap().unwrap();
+    assert_eq!((ticket.priority, ticket.due_date), (Some(Priority::High), Some(march(5))));
+
+    let patch = TicketPatch { id, priority: Some(None), due_date: Some(Some(march(9))), ..Default::default() };
+    let ticket = repo.patch(patch).await.unwrap().unwrap();
+    assert_eq!((ticket.priority, ticket.due_date), (None, Some(march(9))));
+
+    let patch = TicketPatch { id, status: Some(Status::InProgress), ..Default::default() };
+    let ticket = repo.patch(patch).await.unwrap().unwrap();
+    assert_eq!((ticket.priority, ticket.due_date), (None, Some(march(9))), "Left out means left alone");
+    assert_eq!(repo.get(id).await.unwrap().unwrap(), ticket);
+
+    let history = repo.history(id).await.unwrap().unwrap();
+    assert_eq!(history[0].priority, Some(Change { old: None, new: Some(Priority::High) }));
+    assert_eq!(history[1].priority, Some(Change { old: Some(Some(Priority::High)), new: None }));
+    assert_eq!(history[2].due_date, None);
+}
+
+async fn check_if_overdue_lists_open_late_tickets_by_priority(
+    repo: Arc<impl TicketRepository>,
+    clock: Arc<ManualClock>,
+) {
+    for (priority, due_date) in [
+        (Some(Priority::Low), Some(NaiveDate::from_ymd_opt(2024, 2, 20).unwrap())),
+        (Some(Priority::Critical), Some(NaiveDate::from_ymd_opt(2024, 2, 25).unwrap())),
+        (None, Some(NaiveDate::from_ymd_opt(2024, 2, 10).unwrap())),
+        (Some(Priority::Critical), Some(NaiveDate::from_ymd_opt(2024, 2, 28).unwrap())),
+        (Some(Priority::High), Some(march(1))),
+        (Some(Priority::High), Some(NaiveDate::from_ymd_opt(2024, 2, 1).unwrap())),
+        (Some(Priority::Critical), None),
+    ] {
+        let draft = TicketDraft { priority, due_date, ..create_draft("Late", "Or not.") };
+        repo.create(draft).await.unwrap();
+    }
+
+    repo.patch(TicketPatch { id: TicketId(3), status: Some(Status::Done), ..Default::default() }).await.unwrap();
+    repo.archive(TicketId(5)).await.unwrap();
+
+    let overdue: Vec<_> = repo.overdue().await.unwrap().iter().map(|t| t.id.0).collect();
+    assert_eq!(overdue, vec![1, 0, 2], "Due today isn't late yet");
+
+    clock.advance(TimeDelta::days(1));
+    let overdue: Vec<_> = repo.overdue().await.unwrap().iter().map(|t| t.id.0).collect();
+    assert_eq!(overdue, vec![1, 4, 0, 2]);
+}
+
+fn link(kind: LinkKind, from: u64, to: u64) -> Link {
+    Link { kind, from: TicketId(from), to: TicketId(to) }
+}
+
+async fn check_if_links_can_be_added_listed_and_removed(repo: Arc<impl TicketRepository>) {
+    for title in ["Zero", "One", "Two"] {
+        repo.create(create_draft(title, "Linked up.")).await.unwrap();
+    }
+
+    for new in [link(LinkKind::Blocks, 0, 1), link(LinkKind::ParentOf, 2, 1), link(LinkKind::Duplicates, 0, 2)] {
+        assert_eq!(repo.link(new).await.unwrap(), Some(new));
+    }
+    assert_eq!(repo.link(link(LinkKind::Blocks, 0, 1)).await.unwrap(), Some(link(LinkKind::Blocks, 0, 1)));
+
+    assert_eq!(
+        repo.links(TicketId(1)).await.unwrap().unwrap(),
+        vec![link(LinkKind::Blocks, 0, 1), link(LinkKind::ParentOf, 2, 1)],
+        "Linking twice changes nothing"
+    );
+
+    assert!(repo.link(link(LinkKind::Blocks, 42, 1)).await.unwrap().is_none());
+    assert!(repo.links(TicketId(42)).await.unwrap().is_none());
+    assert!(matches!(repo.link(link(LinkKind::Blocks, 1, 42)).await, Err(Error::InvalidLink(_))));
+    assert!(matches!(repo.link(link(LinkKind::Blocks, 1, 1)).await, Err(Error::InvalidLink(_))));
+    assert!(matches!(repo.link(link(LinkKind::ParentOf, 0, 1)).await, Err(Error::InvalidLink(_))));
+
+    assert_eq!(repo.unlink(link(LinkKind::Blocks, 0, 1)).await.unwrap(), Some(link(LinkKind::Blocks, 0, 1)));
+    assert!(repo.unlink(link(LinkKind::Blocks, 0, 1)).await.unwrap().is_none());
+
+    repo.delete(TicketId(0)).await.unwrap();
+    assert_eq!(repo.links(TicketId(2)).await.unwrap().unwrap(), vec![link(LinkKind::ParentOf, 2, 1)]);
+}
+
+async fn check_if_blocking_and_parent_cycles_are_refused(repo: Arc<impl TicketRepository>) {
+    for title in ["Zero", "One", "Two"] {
+        repo.create(create_draft(title, "Going round.")).await.unwrap();
+    }
+
+    repo.link(link(LinkKind::Blocks, 0, 1)).await.unwrap();
+    repo.link(link(LinkKind::Blocks, 1, 2)).await.unwrap();
+
+    match repo.link(link(LinkKind::Blocks, 2, 0)).await {
+        Err(Error::LinkCycle { kind: LinkKind::Blocks, path }) => {
+            assert_eq!(path, vec![TicketId(2), TicketId(0), TicketId(1), TicketId(2)])
+        }
+        other => panic!("Expected a cycle, got {other:?}"),
+    }
+
+    repo.link(link(LinkKind::ParentOf, 2, 0)).await.unwrap();
+    assert!(matches!(repo.link(link(LinkKind::ParentOf, 0, 2)).await, Err(Error::LinkCycle { .. })));
+    assert_eq!(repo.links(TicketId(2)).await.unwrap().unwrap().len(), 2, "Refused links aren't stored");
+}
+
+async fn check_if_done_waits_for_blockers_and_sub_tasks(repo: Arc<impl TicketRepository>) {
+    for title in ["Blocker", "Parent", "Child"] {
+        repo.create(create_draft(title, "Waiting on each other.")).await.unwrap();
+    }
+
+    repo.link(link(LinkKind::Blocks, 0, 1)).await.unwrap();
+    repo.link(link(LinkKind::ParentOf, 1, 2)).await.unwrap();
+
+    let done = |id: u64| TicketPatch { id: TicketId(id), status: Some(Status::Done), ..Default::default() };
+
+    match repo.patch(done(1)).await {
+        Err(Error::OpenDependencies { id: TicketId(1), blockers, children }) => {
+            assert_eq!((blockers, children), (vec![TicketId(0)], vec![TicketId(2)]))
+        }
+        other => panic!("Expected open dependencies, got {other:?}"),
+    }
+    assert_eq!(repo.get(TicketId(1)).await.unwrap().unwrap().version, 1, "A rejected patch must change nothing");
+
+    repo.patch(done(0)).await.unwrap();
+    assert!(matches!(repo.patch(done(1)).await, Err(Error::OpenDependencies { .. })));
+
+    repo.patch(done(2)).await.unwrap();
+    assert_eq!(repo.patch(done(1)).await.unwrap().unwrap().status, Status::Done);
+}
+
+async fn check_if_webhooks_can_be_registered_and_removed(repo: Arc<impl TicketRepository>, clock: Arc<ManualClock>) {
+    let first = repo.add_webhook(WebhookDraft::with("http://localhost:9000/hook", "first").unwrap()).await.unwrap();
+    let second = repo.add_webhook(WebhookDraft::with("https://example.com/hook", "second").unwrap()).await.unwrap();
+
+    assert_eq!((first.id, second.id), (WebhookId(0), WebhookId(1)));
+    assert_eq!(first.created_at, clock.now());
+    assert_eq!(repo.webhooks().await.unwrap(), vec![first.clone(), second.clone()], "Secrets are kept");
+
+    assert_eq!(repo.remove_webhook(first.id).await.unwrap(), Some(first.clone()));
+    assert!(repo.remove_webhook(first.id).await.unwrap().is_none());
+
+    let third = repo.add_webhook(WebhookDraft::with("http://localhost:9001/hook", "third").unwrap()).await.unwrap();
+    assert_eq!(third.id, WebhookId(2), "Ids are never reused");
+    assert_eq!(repo.webhooks().await.unwrap(), vec![second, third]);
+
+    let draft = WebhookDraft { url: "not a url".into(), secret: "s3cret".into() };
+    assert!(matches!(repo.add_webhook(draft).await, Err(Error::InvalidWebhook(_))));
+}
+
+async fn check_if_atomic_batches_apply_all_or_nothing(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Existing", "Already here.")).await.unwrap();
+
+    let create = |title| BatchOp::Create(create_draft(title, "Batched."));
+    let start = |version| BatchOp::Patch(TicketPatch {
+        id,
+        status: Some(Status::InProgress),
+        version: Some(version),
+        ..Default::default()
+    });
+
+    let outcomes = repo.batch(Batch { mode: BatchMode::Atomic, ops: vec![create("One"), start(1), create("Two")] })
+        .await
+        .unwrap();
+
+    let summary = outcomes.iter().map(|outcome| match outcome {
+        BatchOutcome::Created(ticket) => ("created", ticket.id, ticket.version),
+        BatchOutcome::Patched(ticket) => ("patched", ticket.id, ticket.version),
+        BatchOutcome::Failed(problem) => panic!("Nothing should fail, got {problem:?}"),
+    }).collect::<Vec<_>>();
+    assert_eq!(summary, vec![("created", TicketId(1), 1), ("patched", id, 2), ("created", TicketId(2), 1)]);
+
+    // Stale version on the second item, the first one mustn't stick either.
+    match repo.batch(Batch { mode: BatchMode::Atomic, ops: vec![create("Three"), start(1)] }).await {
+        Err(Error::BatchFailed { index: 1, error }) => assert!(matches!(*error, Error::VersionMismatch { .. })),
+        other => panic!("Expected the batch to fail on its second item, got {other:?}"),
+    }
+
+    // Fails after a patch and a create went through, both have to be taken back.
+    let renamed = TicketTitle::try_from("Renamed".to_string()).unwrap();
+    let rename = BatchOp::Patch(TicketPatch { id, title: Some(renamed), version: Some(2), ..Default::default() });
+    let missing = BatchOp::Patch(TicketPatch { id: TicketId(42), ..Default::default() });
+    match repo.batch(Batch { mode: BatchMode::Atomic, ops: vec![rename, create("Three"), missing] }).await {
+        Err(Error::BatchFailed { index: 2, error }) => assert!(matches!(*error, Error::NotFound(TicketId(42)))),
+        other => panic!("Expected the batch to fail on its third item, got {other:?}"),
+    }
+    let ticket = repo.get(id).await.unwrap().unwrap();
+    assert_eq!((ticket.title.to_string(), ticket.version), ("Existing".to_string(), 2));
+
+    let blank: TicketTitle = serde_json::from_str(r#""""#).unwrap();
+    let rename = BatchOp::Patch(TicketPatch { id, title: Some(blank), ..Default::default() });
+    match repo.batch(Batch { mode: BatchMode::Atomic, ops: vec![rename] }).await {
+        Err(Error::BatchFailed { index: 0, error }) => assert!(matches!(*error, Error::Title(_))),
+        other => panic!("Expected the batch to fail validation, got {other:?}"),
+    }
+
+    assert_eq!(ids(&*repo, TicketQuery::default()).await, vec![TicketId(0), TicketId(1), TicketId(2)]);
+    assert_eq!(repo.history(id).await.unwrap().unwrap().len(), 2);
+    assert_eq!(repo.create(create_draft("Three", "After.")).await.unwrap(), TicketId(3), "Ids are rolled back too");
+}
+
+async fn check_if_per_item_batches_report_every_outcome(repo: Arc<impl TicketRepository>) {
+    let id = repo.create(create_draft("Existing", "Already here.")).await.unwrap();
+
+    let ops = vec![
+        BatchOp::Create(create_draft("New", "Batched.")),
+        BatchOp::Patch(TicketPatch { id: TicketId(42), status: Some(Status::Done), ..Default::default() }),
+        BatchOp::Patch(TicketPatch { id, status: Some(Status::Done), ..Default::default() }),
+    ];
+    let outcomes = repo.batch(Batch { mode: BatchMode::PerItem, ops }).await.unwrap();
+
+    assert!(matches!(&outcomes[0], BatchOutcome::Created(ticket) if ticket.id == TicketId(1)));
+    assert_eq!(outcomes[1], BatchOutcome::Failed(Error::NotFound(TicketId(42)).problem()));
+    assert!(matches!(outcomes[1].clone().into_result(), Err(Error::NotFound(TicketId(42)))));
+    assert!(matches!(&outcomes[2], BatchOutcome::Patched(ticket) if ticket.status == Status::Done));
+
+    assert_eq!(ids(&*repo, TicketQuery::default()).await, vec![TicketId(0), TicketId(1)]);
+}
+
+async fn seed(repo: &impl TicketRepository) {
+    for (title, description, status) in [
+        ("Cats", "The musical.", Status::Done),
+        ("Dogs", "Not a musical.", Status::ToDo),
+        ("Birds", "About CATS, somehow.", Status::InProgress),
+        ("Ants", "Tiny.", Status::ToDo),
+        ("Cats", "The remake.", Status::ToDo),
+    ] {
+        let id = repo.create(create_draft(title, description)).await.unwrap();
+        repo.patch(TicketPatch { id, status: Some(status), ..Default::default() }).await.unwrap();
+    }
+}
+
+async fn check_if_list_filters_by_status_and_text(repo: Arc<impl TicketRepository>) {
+    seed(&*repo).await;
+
+    let query = TicketQuery { status: Some(Status::ToDo), ..Default::default() };
+    assert_eq!(ids(&*repo, query).await, vec![TicketId(1), TicketId(3), TicketId(4)]);
+
+    let query = TicketQuery { text: Some("CaTs".into()), ..Default::default() };
+    assert_eq!(ids(&*repo, query).await, vec![TicketId(0), TicketId(2), TicketId(4)]);
+
+    let query = TicketQuery { status: Some(Status::ToDo), text: Some("musical".into()), ..Default::default() };
+    assert_eq!(ids(&*repo, query).await, vec![TicketId(1)]);
+}
+
+async fn check_if_list_sorts_with_ties_broken_by_id(repo: Arc<impl TicketRepository>) {
+    seed(&*repo).await;
+
+    let query = TicketQuery { sort: SortBy::Title, ..Default::default() };
+    assert_eq!(
+        ids(&*repo, query).await,
+        vec![TicketId(3), TicketId(2), TicketId(0), TicketId(4), TicketId(1)]
+    );
+
+    let query = TicketQuery { sort: SortBy::Status, ..Default::default() };
+    assert_eq!(
+        ids(&*repo, query).await,
+        vec![TicketId(1), TicketId(3), TicketId(4), TicketId(2), TicketId(0)]
+    );
+}
+
+async fn check_if_cursor_pagination_visits_every_ticket_once(repo: Arc<impl TicketRepository>) {
+    seed(&*repo).await;
+
+    for sort in [SortBy::Id, SortBy::Title, SortBy::Status] {
+        let mut query = TicketQuery { sort, limit: Some(2), ..Default::default() };
+        let mut pages = Vec::new();
+
+        loop {
+            let page = repo.list(query.clone()).await.unwrap();
+            assert!(page.tickets.len() <= 2);
+            pages.push(page.tickets.into_iter().map(|t| t.id).collect::<Vec<_>>());
+
+            match page.next_cursor {
+                Some(cursor) => query.after = Some(cursor),
+                None => break,
+            }
+        }
+
+        let everything = ids(&*repo, TicketQuery { sort, ..Default::default() }).await;
+        assert_eq!(pages.concat(), everything, "Paging by {sort:?} went wrong: {pages:?}");
+    }
+}
+
+async fn check_if_deleted_cursor_only_works_when_sorting_by_id(repo: Arc<impl TicketRepository>) {
+    seed(&*repo).await;
+    repo.delete(TicketId(1)).await.unwrap();
+
+    let query = TicketQuery { after: Some(TicketId(1)), ..Default::default() };
+    assert_eq!(ids(&*repo, query).await, vec![TicketId(2), TicketId(3), TicketId(4)]);
+
+    let query = TicketQuery { after: Some(TicketId(1)), sort: SortBy::Title, ..Default::default() };
+    assert!(matches!(repo.list(query).await, Err(Error::InvalidCursor(TicketId(1)))));
+}
+
+async fn check_if_multiple_tasks_can_read_concurrently(repo: Arc<impl TicketRepository + 'static>) {
+    let id = repo.create(create_draft("The Parallel", "A check on RwLock.")).await.unwrap();
+    let (a, b) = (repo.clone(), repo.clone());
+
+    let (r1, r2) = tokio::join!(
+        task::spawn(async move {
+            let t = a.get(id).await.unwrap().unwrap();
+            (t.id, t.status)
+        }),
+        task::spawn(async move {
+            let t = b.get(id).await.unwrap().unwrap();
+            (t.id, t.status)
+        }),
+    );
+
+    let (id1, status1) = r1.unwrap();
+    let (id2, status2) = r2.unwrap();
+
+    assert_eq!(id1, id);
+    assert_eq!(id2, id);
+    assert_eq!(status1, Status::ToDo);
+    assert_eq!(status2, Status::ToDo);
+}
+
+macro_rules! conformance_suite {
+    ($implementation:ident, $fixture:path) => {
+        mod $implementation {
+            conformance_suite!(@tests $fixture;
+                check_if_create_returns_incrementing_ids_starting_from_zero,
+                check_if_get_returns_inserted_ticket_with_default_status,
+                check_if_get_unknown_id_returns_none,
+                check_if_list_returns_every_ticket_ordered_by_id,
+                check_if_patch_allows_status_update,
+                check_if_patch_unknown_id_returns_none,
+                check_if_delete_removes_ticket_without_reusing_its_id,
+                check_if_archived_tickets_are_left_out_of_list_until_restored,
+                check_if_archive_and_restore_unknown_id_return_none,
+                check_if_every_change_bumps_the_version,
+                check_if_patch_with_stale_version_is_rejected,
+                check_if_every_change_is_kept_in_history,
+                check_if_history_records_who_made_each_change,
+                check_if_history_outlives_a_deleted_ticket,
+                check_if_patch_cannot_leave_done,
+                check_if_reopen_moves_done_tickets_back,
+                check_if_reopen_is_refused_unless_done,
+                check_if_comments_are_scoped_to_their_ticket,
+                check_if_labels_can_be_added_and_removed,
+                check_if_list_filters_by_label,
+                check_if_priority_and_due_date_can_be_set_and_cleared,
+                check_if_links_can_be_added_listed_and_removed,
+                check_if_blocking_and_parent_cycles_are_refused,
+                check_if_done_waits_for_blockers_and_sub_tasks,
+                check_if_atomic_batches_apply_all_or_nothing,
+                check_if_per_item_batches_report_every_outcome,
+                check_if_list_filters_by_status_and_text,
+                check_if_list_sorts_with_ties_broken_by_id,
+                check_if_cursor_pagination_visits_every_ticket_once,
+                check_if_deleted_cursor_only_works_when_sorting_by_id,
+                check_if_multiple_tasks_can_read_concurrently,
+            );
+            conformance_suite!(@clocked $fixture;
+                check_if_timestamps_follow_the_clock,
+                check_if_comments_can_be_added_edited_and_deleted,
+                check_if_overdue_lists_open_late_tickets_by_priority,
+                check_if_webhooks_can_be_registered_and_removed,
+            );
+        }
+    };
+    (@tests $fixture:path; $($test:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $test() {
+                let fixture = $fixture();
+                super::$test(fixture.repo.clone()).await;
+            }
+        )*
+    };
+    (@clocked $fixture:path; $($test:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $test() {
+                let fixture = $fixture();
+                super::$test(fixture.repo.clone(), fixture.clock.clone()).await;
+            }
+        )*
+    };
+}
+
+conformance_suite!(in_memory, super::in_memory);
+conformance_suite!(journaled, super::journaled);
+conformance_suite!(sqlite, super::sqlite);
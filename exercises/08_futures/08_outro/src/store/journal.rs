@@ -0,0 +1,562 @@
+// An append-only write-ahead log with periodic snapshots.
+//
+// Every mutation is appended to `journal.log` as one JSON record per line and
+// flushed to disk before the in-memory store is touched. Once the log grows past
+// `snapshot_every` records the store writes what it holds to `snapshot.json` (a
+// temporary file renamed over the old one) and the log is truncated. On startup the
+// snapshot is loaded and the log replayed on top of it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Result,
+    data::{Comment, CommentId, HistoryEntry, Link, Ticket, TicketId, Webhook, WebhookId},
+};
+
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+pub const LOG_FILE: &str = "journal.log";
+pub const DEFAULT_SNAPSHOT_EVERY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Record {
+    // The ticket's history stays, with the entry saying it was deleted.
+    Removed(TicketId, Box<HistoryEntry>),
+    Changed(Ticket, Box<HistoryEntry>),
+    // Posted or edited.
+    Commented(Comment),
+    CommentRemoved(TicketId, CommentId),
+    Linked(Link),
+    Unlinked(Link),
+    WebhookAdded(Webhook),
+    WebhookRemoved(WebhookId),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct State {
+    pub counter: u64,
+    pub tickets: BTreeMap<TicketId, Ticket>,
+    pub history: BTreeMap<TicketId, Vec<HistoryEntry>>,
+    pub comment_counter: u64,
+    pub comments: BTreeMap<TicketId, BTreeMap<CommentId, Comment>>,
+    pub links: BTreeSet<Link>,
+    pub webhook_counter: u64,
+    pub webhooks: BTreeMap<WebhookId, Webhook>,
+}
+
+impl State {
+    pub fn apply(&mut self, record: Record) {
+        match record {
+            Record::Removed(id, entry) => {
+                self.tickets.remove(&id);
+                self.comments.remove(&id);
+                self.links.retain(|link| !link.touches(id));
+                self.push_history(id, *entry);
+            }
+            Record::Changed(ticket, entry) => {
+                self.push_history(ticket.id, *entry);
+                self.upsert(ticket);
+            }
+            Record::Commented(comment) => self.upsert_comment(comment),
+            Record::CommentRemoved(ticket, id) => {
+                if let Some(comments) = self.comments.get_mut(&ticket) {
+                    comments.remove(&id);
+                }
+            }
+            Record::Linked(link) => {
+                self.links.insert(link);
+            }
+            Record::Unlinked(link) => {
+                self.links.remove(&link);
+            }
+            Record::WebhookAdded(webhook) => self.upsert_webhook(webhook),
+            Record::WebhookRemoved(id) => {
+                self.webhooks.remove(&id);
+            }
+        }
+    }
+
+    fn push_history(&mut self, id: TicketId, entry: HistoryEntry) {
+        let history = self.history.entry(id).or_default();
+        // Replaying a record that's already in the snapshot mustn't duplicate it.
+        if history.last().is_none_or(|last| last.version < entry.version) {
+            history.push(entry);
+        }
+    }
+
+    fn upsert(&mut self, ticket: Ticket) {
+        // Ids are never handed out twice, even if the snapshot holding the counter
+        // was lost and only the log survived.
+        self.counter = self.counter.max(ticket.id.0 + 1);
+        self.tickets.insert(ticket.id, ticket);
+    }
+
+    fn upsert_comment(&mut self, comment: Comment) {
+        self.comment_counter = self.comment_counter.max(comment.id.0 + 1);
+        self.comments.entry(comment.ticket_id).or_default().insert(comment.id, comment);
+    }
+
+    fn upsert_webhook(&mut self, webhook: Webhook) {
+        self.webhook_counter = self.webhook_counter.max(webhook.id.0 + 1);
+        self.webhooks.insert(webhook.id, webhook);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    counter: u64,
+    tickets: Vec<Ticket>,
+    history: Vec<(TicketId, Vec<HistoryEntry>)>,
+    comment_counter: u64,
+    comments: Vec<Comment>,
+    links: Vec<Link>,
+    webhook_counter: u64,
+    webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    log: File,
+    entries: usize,
+    snapshot_every: usize,
+}
+
+impl Journal {
+    pub fn open(dir: impl AsRef<Path>) -> Result<(Self, State)> {
+        Self::open_with(dir, DEFAULT_SNAPSHOT_EVERY)
+    }
+
+    pub fn open_with(dir: impl AsRef<Path>, snapshot_every: usize) -> Result<(Self, State)> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let (state, entries, valid_len) = load(&dir)?;
+
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE))?;
+
+        if log.metadata()?.len() > valid_len {
+            log.set_len(valid_len)?;
+            log.sync_all()?;
+        }
+
+        let journal = Self { dir, log, entries, snapshot_every: snapshot_every.max(1) };
+
+        Ok((journal, state))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn append(&mut self, record: &Record) -> Result<()> {
+        self.append_all(std::slice::from_ref(record))
+    }
+
+    // One write and one sync for all of them. A crash can still leave only the
+    // first few on disk, every complete line is replayed.
+    pub fn append_all(&mut self, records: &[Record]) -> Result<()> {
+        self.append_with(records, |log, lines| {
+            log.write_all(lines)?;
+            log.sync_data()
+        })
+    }
+
+    // A write that fails halfway is cut off again, or the next append would
+    // leave the torn line in the middle of the log, where `load` won't have it.
+    fn append_with(&mut self, records: &[Record], write: impl FnOnce(&mut File, &[u8]) -> io::Result<()>)
+        -> Result<()>
+    {
+        if records.is_empty() {
+            return Ok(())
+        }
+
+        let mut lines = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut lines, record)?;
+            lines.push(b'\n');
+        }
+
+        let len = self.log.metadata()?.len();
+        if let Err(error) = write(&mut self.log, &lines) {
+            self.log.set_len(len)?;
+            self.log.seek(SeekFrom::Start(len))?;
+            return Err(error.into())
+        }
+        self.entries += records.len();
+
+        Ok(())
+    }
+
+    // Whether the log has grown enough to be folded into a snapshot.
+    pub fn is_due(&self) -> bool {
+        self.entries >= self.snapshot_every
+    }
+
+    // `state` has to hold every record in the log, they're gone afterwards.
+    pub fn snapshot(&mut self, state: &State) -> Result<()> {
+        let snapshot = Snapshot {
+            counter: state.counter,
+            tickets: state.tickets.values().cloned().collect(),
+            history: state.history.iter().map(|(id, history)| (*id, history.clone())).collect(),
+            comment_counter: state.comment_counter,
+            comments: state.comments.values().flat_map(BTreeMap::values).cloned().collect(),
+            links: state.links.iter().copied().collect(),
+            webhook_counter: state.webhook_counter,
+            webhooks: state.webhooks.values().cloned().collect(),
+        };
+
+        let tmp = self.dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+        {
+            let mut file = File::create(&tmp)?;
+            serde_json::to_writer(&mut file, &snapshot)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE))?;
+        sync_dir(&self.dir)?;
+
+        // A crash before this point leaves records in the log that are already in
+        // the snapshot. That's harmless, replaying a record is idempotent.
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        self.entries = 0;
+
+        Ok(())
+    }
+}
+
+fn load(dir: &Path) -> Result<(State, usize, u64)> {
+    let mut state = State::default();
+
+    match File::open(dir.join(SNAPSHOT_FILE)) {
+        Ok(file) => {
+            let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+            state.counter = snapshot.counter;
+            for ticket in snapshot.tickets {
+                state.upsert(ticket);
+            }
+            state.history.extend(snapshot.history);
+            state.comment_counter = snapshot.comment_counter;
+            for comment in snapshot.comments {
+                state.upsert_comment(comment);
+            }
+            state.links.extend(snapshot.links);
+            state.webhook_counter = snapshot.webhook_counter;
+            for webhook in snapshot.webhooks {
+                state.upsert_webhook(webhook);
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let log = match fs::read(dir.join(LOG_FILE)) {
+        Ok(log) => log,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((state, 0, 0)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = 0;
+    let mut valid_len = 0;
+
+    for (n, line) in log.split_inclusive(|b| *b == b'\n').enumerate() {
+        // A record only counts once its trailing newline made it to disk. Anything
+        // else is a torn write from a crash, and that can only be the last line.
+        if !line.ends_with(b"\n") {
+            break;
+        }
+
+        if !line.trim_ascii().is_empty() {
+            let record = serde_json::from_slice::<Record>(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Corrupt journal record on line {}: {e}", n + 1),
+                )
+            })?;
+            state.apply(record);
+            entries += 1;
+        }
+
+        valid_len += line.len() as u64;
+    }
+
+    Ok((state, entries, valid_len))
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{history::Action, Status};
+
+    fn ticket(id: u64, title: &str) -> Ticket {
+        Ticket::with(id.into(), title, "Journaled.", Status::ToDo).unwrap()
+    }
+
+    fn created(ticket: Ticket) -> Record {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        Record::Changed(ticket.clone(), Box::new(HistoryEntry::between(None, &ticket, at)))
+    }
+
+    fn removed(ticket: Ticket) -> Record {
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        Record::Removed(ticket.id, Box::new(HistoryEntry::deleted(&ticket, at)))
+    }
+
+    // Same as the store, which keeps `state` in memory and snapshots it when asked to.
+    fn append(journal: &mut Journal, state: &mut State, record: Record) {
+        journal.append(&record).unwrap();
+        state.apply(record);
+
+        if journal.is_due() {
+            journal.snapshot(state).unwrap();
+        }
+    }
+
+    #[test]
+    fn check_if_records_are_replayed_after_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, state) = Journal::open(dir.path()).unwrap();
+            assert_eq!(state, State::default());
+
+            journal.append(&created(ticket(0, "First"))).unwrap();
+            journal.append(&created(ticket(1, "Second"))).unwrap();
+            journal.append(&created(ticket(0, "First, renamed"))).unwrap();
+        }
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+
+        assert_eq!(state.counter, 2);
+        assert_eq!(state.tickets.len(), 2);
+        assert_eq!(state.tickets[&TicketId(0)].title.to_string(), "First, renamed");
+    }
+
+    #[test]
+    fn check_if_removed_tickets_stay_removed_without_freeing_their_id() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, _) = Journal::open(dir.path()).unwrap();
+            journal.append(&created(ticket(0, "First"))).unwrap();
+            journal.append(&created(ticket(1, "Second"))).unwrap();
+            journal.append(&removed(ticket(1, "Second"))).unwrap();
+        }
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+
+        assert_eq!(state.counter, 2);
+        assert_eq!(state.tickets.keys().copied().collect::<Vec<_>>(), vec![TicketId(0)]);
+
+        let actions = state.history[&TicketId(1)].iter().map(|entry| entry.action).collect::<Vec<_>>();
+        assert_eq!(actions, vec![Action::Created, Action::Deleted], "The history outlives the ticket");
+    }
+
+    #[test]
+    fn check_if_snapshot_truncates_the_log_and_keeps_the_state() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, mut state) = Journal::open_with(dir.path(), 2).unwrap();
+            append(&mut journal, &mut state, created(ticket(0, "First")));
+            append(&mut journal, &mut state, created(ticket(1, "Second")));
+            append(&mut journal, &mut state, created(ticket(2, "Third")));
+        }
+
+        let log = fs::read_to_string(dir.path().join(LOG_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 1, "Only the record after the snapshot should remain");
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+        assert_eq!(state.counter, 3);
+        assert_eq!(state.tickets.len(), 3);
+    }
+
+    #[test]
+    fn check_if_snapshots_hold_the_state_they_are_given() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, mut state) = Journal::open(dir.path()).unwrap();
+            journal.append(&created(ticket(0, "Logged"))).unwrap();
+
+            // Not from the log, so only the snapshot can bring it back.
+            state.apply(created(ticket(1, "Only in memory")));
+            journal.snapshot(&state).unwrap();
+        }
+
+        let (journal, state) = Journal::open(dir.path()).unwrap();
+        assert!(!journal.is_due());
+        assert_eq!(state.counter, 2);
+        assert_eq!(state.tickets.keys().copied().collect::<Vec<_>>(), vec![TicketId(1)]);
+    }
+
+    #[test]
+    fn check_if_history_survives_snapshots_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap();
+
+        let first = ticket(0, "First");
+        let renamed = Ticket { version: 2, ..ticket(0, "Renamed") };
+
+        {
+            let (mut journal, mut state) = Journal::open_with(dir.path(), 2).unwrap();
+            append(&mut journal, &mut state, Record::Changed(first.clone(), Box::new(HistoryEntry::between(None, &first, at))));
+            append(&mut journal, &mut state, Record::Changed(renamed.clone(), Box::new(HistoryEntry::between(Some(&first), &renamed, at))));
+        }
+
+        // As if the process died between writing the snapshot and truncating the log.
+        fs::write(
+            dir.path().join(LOG_FILE),
+            format!(
+                "{}\n",
+                serde_json::to_string(
+                    &Record::Changed(renamed.clone(), Box::new(HistoryEntry::between(Some(&first), &renamed, at)))
+                ).unwrap()
+            ),
+        ).unwrap();
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+        let versions: Vec<_> = state.history[&TicketId(0)].iter().map(|e| e.version).collect();
+        assert_eq!(versions, vec![1, 2]);
+    }
+
+    #[test]
+    fn check_if_a_torn_last_record_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, _) = Journal::open(dir.path()).unwrap();
+            journal.append(&created(ticket(0, "First"))).unwrap();
+        }
+
+        let mut log = OpenOptions::new().append(true).open(dir.path().join(LOG_FILE)).unwrap();
+        log.write_all(br#"{"Changed":[{"id":1,"ti"#).unwrap();
+
+        let (mut journal, state) = Journal::open(dir.path()).unwrap();
+        assert_eq!(state.counter, 1);
+        assert_eq!(state.tickets.len(), 1);
+
+        journal.append(&created(ticket(1, "Second"))).unwrap();
+        drop(journal);
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+        assert_eq!(state.tickets.len(), 2, "The torn record should've been cut off");
+    }
+
+    #[test]
+    fn check_if_a_failed_append_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (mut journal, _) = Journal::open(dir.path()).unwrap();
+            journal.append(&created(ticket(0, "First"))).unwrap();
+
+            // Half a line makes it to disk before the write gives up.
+            let failed = journal.append_with(&[created(ticket(1, "Torn"))], |log, lines| {
+                log.write_all(&lines[..lines.len() / 2])?;
+                Err(io::Error::other("Disk full"))
+            });
+            assert!(failed.is_err());
+
+            journal.append(&created(ticket(2, "Third"))).unwrap();
+        }
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+        assert_eq!(state.tickets.keys().copied().collect::<Vec<_>>(), vec![TicketId(0), TicketId(2)]);
+    }
+
+    #[test]
+    fn check_if_a_corrupt_record_in_the_middle_errors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::write(
+            dir.path().join(LOG_FILE),
+            format!(
+                "{}\nnot json\n{}\n",
+                serde_json::to_string(&created(ticket(0, "First"))).unwrap(),
+                serde_json::to_string(&created(ticket(1, "Second"))).unwrap(),
+            ),
+        ).unwrap();
+
+        let err = Journal::open(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "Unexpected error: {err}");
+    }
+
+    #[test]
+    fn check_if_comments_and_links_survive_snapshots_and_go_with_their_ticket() {
+        use crate::data::{CommentBody, CommentId};
+
+        let dir = tempfile::tempdir().unwrap();
+        let at = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let comment = |id: u64, ticket: u64, body: &str| Comment {
+            id: CommentId(id),
+            ticket_id: TicketId(ticket),
+            body: CommentBody::try_from(body).unwrap(),
+            created_at: at,
+            updated_at: at,
+        };
+
+        {
+            let (mut journal, mut state) = Journal::open_with(dir.path(), 4).unwrap();
+            append(&mut journal, &mut state, created(ticket(0, "First")));
+            append(&mut journal, &mut state, created(ticket(1, "Second")));
+            append(&mut journal, &mut state, Record::Commented(comment(0, 0, "Hello")));
+            append(&mut journal, &mut state, Record::Commented(comment(1, 1, "Doomed")));
+            append(&mut journal, &mut state, Record::Commented(comment(0, 0, "Hello, edited")));
+            append(&mut journal, &mut state, Record::Linked(crate::data::Link {
+                kind: crate::data::LinkKind::Blocks,
+                from: TicketId(1),
+                to: TicketId(0),
+            }));
+            append(&mut journal, &mut state, removed(ticket(1, "Second")));
+        }
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+
+        assert!(state.links.is_empty(), "Links go with their tickets too");
+        assert_eq!(state.comment_counter, 2);
+        assert_eq!(state.comments.len(), 1);
+        assert_eq!(state.comments[&TicketId(0)][&CommentId(0)].body.to_string(), "Hello, edited");
+    }
+
+    #[test]
+    fn check_if_webhooks_survive_snapshots_without_reusing_ids() {
+        use crate::data::{Webhook, WebhookId};
+
+        let dir = tempfile::tempdir().unwrap();
+        let webhook = |id: u64| Webhook {
+            id: WebhookId(id),
+            url: format!("http://localhost/{id}"),
+            secret: "s3cret".into(),
+            created_at: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+        };
+
+        {
+            let (mut journal, mut state) = Journal::open_with(dir.path(), 2).unwrap();
+            append(&mut journal, &mut state, Record::WebhookAdded(webhook(0)));
+            append(&mut journal, &mut state, Record::WebhookAdded(webhook(1)));
+            append(&mut journal, &mut state, Record::WebhookRemoved(WebhookId(1)));
+        }
+
+        let (_, state) = Journal::open(dir.path()).unwrap();
+
+        assert_eq!(state.webhook_counter, 2);
+        assert_eq!(state.webhooks.into_values().collect::<Vec<_>>(), vec![webhook(0)]);
+    }
+}
@@ -0,0 +1,850 @@
+pub mod actor;
+pub mod clock;
+pub mod journal;
+pub mod repository;
+pub mod sqlite;
+#[cfg(test)]
+mod conformance;
+
+use std::collections::{BTreeMap, BTreeSet, btree_map::Values};
+use std::fmt::{Display, Formatter};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::{RwLock};
+
+use crate::{
+    error::{Error, Result},
+    data::{
+        Batch, BatchMode, BatchOp, BatchOutcome, Comment, CommentDraft, CommentId, HistoryEntry, Label, Link,
+        LinkKind, Status, TicketId, Ticket, TicketDraft, TicketPatch, Webhook, WebhookDraft, WebhookId, Workflow,
+    },
+    data::link::check_done,
+    data::history::Action,
+};
+use journal::{Journal, Record};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use repository::TicketRepository;
+pub use sqlite::SqliteStore;
+
+impl Display for TicketId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug)]
+pub struct TicketStore {
+    tickets: BTreeMap<TicketId, Arc<RwLock<Ticket>>>,
+    history: BTreeMap<TicketId, Vec<HistoryEntry>>,
+    // Which tickets carry each label, so filtering by label doesn't look at the rest.
+    by_label: BTreeMap<Label, BTreeSet<TicketId>>,
+    counter: u64,
+    comments: BTreeMap<TicketId, BTreeMap<CommentId, Comment>>,
+    comment_counter: u64,
+    links: BTreeSet<Link>,
+    webhooks: BTreeMap<WebhookId, Webhook>,
+    webhook_counter: u64,
+    workflow: Workflow,
+    clock: Arc<dyn Clock>,
+    journal: Option<Journal>,
+    // Records held back from the journal until a batch goes through as a whole.
+    pending: Option<Vec<Record>>,
+}
+
+// How to take back one item of an atomic batch.
+#[derive(Debug)]
+enum Undo {
+    Create(TicketId),
+    // The ticket as it was before the patch.
+    Patch(Ticket),
+    // The item failed without changing anything.
+    Nothing,
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TicketStore {
+    pub fn new() -> Self {
+        Self {
+            tickets: BTreeMap::new(),
+            history: BTreeMap::new(),
+            by_label: BTreeMap::new(),
+            counter: 0,
+            comments: BTreeMap::new(),
+            comment_counter: 0,
+            links: BTreeSet::new(),
+            webhooks: BTreeMap::new(),
+            webhook_counter: 0,
+            workflow: Workflow::default(),
+            clock: Arc::new(SystemClock),
+            journal: None,
+            pending: None,
+        }
+    }
+
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let (journal, state) = Journal::open(dir)?;
+        Ok(Self::with_journal(journal, state))
+    }
+
+    pub fn with_journal(journal: Journal, state: journal::State) -> Self {
+        let mut by_label: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for ticket in state.tickets.values() {
+            for label in &ticket.labels {
+                by_label.entry(label.clone()).or_default().insert(ticket.id);
+            }
+        }
+
+        Self {
+            tickets: state.tickets
+                .into_iter()
+                .map(|(id, ticket)| (id, Arc::new(RwLock::new(ticket))))
+                .collect(),
+            history: state.history,
+            by_label,
+            counter: state.counter,
+            comments: state.comments,
+            comment_counter: state.comment_counter,
+            links: state.links,
+            webhooks: state.webhooks,
+            webhook_counter: state.webhook_counter,
+            workflow: Workflow::default(),
+            clock: Arc::new(SystemClock),
+            journal: Some(journal),
+            pending: None,
+        }
+    }
+
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = workflow;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn add_ticket(&mut self, ticket: TicketDraft) -> Result<TicketId> {
+        let id = TicketId(self.counter);
+        let now = self.clock.now();
+        let ticket = Ticket {
+            id,
+            title: ticket.title,
+            description: ticket.description,
+            status: Status::ToDo,
+            version: 1,
+            archived: false,
+            labels: BTreeSet::new(),
+            priority: ticket.priority,
+            due_date: ticket.due_date,
+            created_at: now,
+            updated_at: now,
+            status_changed_at: now,
+        };
+        self.record(HistoryEntry::between(None, &ticket, now), &ticket)?;
+
+        self.counter += 1;
+        let ticket = Arc::new(RwLock::new(ticket));
+        self.tickets.insert(id, ticket);
+        Ok(id)
+    }
+
+    pub async fn patch(&mut self, patch: TicketPatch) -> Result<Option<Ticket>> {
+        let Some(ticket) = self.tickets.get(&patch.id).cloned() else {
+            return Ok(None)
+        };
+
+        let mut ticket = ticket.write().await;
+        patch.check_version(&ticket)?;
+
+        if let Some(status) = patch.status {
+            self.workflow.check_move(&ticket, status)?;
+
+            if status == Status::Done && ticket.status != Status::Done {
+                self.check_dependencies(ticket.id).await?;
+            }
+        }
+
+        let mut patched = ticket.clone();
+        patched.version += 1;
+
+        if let Some(title) = patch.title {
+            patched.title = title;
+        }
+
+        if let Some(description) = patch.description {
+            patched.description = description;
+        }
+
+        if let Some(status) = patch.status {
+            patched.status = status;
+        }
+
+        if let Some(priority) = patch.priority {
+            patched.priority = priority;
+        }
+
+        if let Some(due_date) = patch.due_date {
+            patched.due_date = due_date;
+        }
+
+        let now = self.clock.now();
+        patched.touch(&ticket, now);
+
+        self.record(HistoryEntry::between(Some(&ticket), &patched, now), &patched)?;
+        *ticket = patched.clone();
+
+        Ok(Some(patched))
+    }
+
+    pub async fn reopen(&mut self, id: TicketId, version: Option<u64>) -> Result<Option<Ticket>> {
+        let Some(ticket) = self.tickets.get(&id).cloned() else {
+            return Ok(None)
+        };
+
+        let mut ticket = ticket.write().await;
+        TicketPatch { id, version, ..Default::default() }.check_version(&ticket)?;
+
+        let status = self.workflow.reopen_target(&ticket)?;
+        let mut reopened = Ticket { status, version: ticket.version + 1, ..ticket.clone() };
+
+        let now = self.clock.now();
+        reopened.touch(&ticket, now);
+
+        let entry = HistoryEntry {
+            action: Action::Reopened,
+            ..HistoryEntry::between(Some(&ticket), &reopened, now)
+        };
+        self.record(entry, &reopened)?;
+        *ticket = reopened.clone();
+
+        Ok(Some(reopened))
+    }
+
+    pub async fn archive(&mut self, id: TicketId) -> Result<Option<Ticket>> {
+        self.set_archived(id, true).await
+    }
+
+    pub async fn restore(&mut self, id: TicketId) -> Result<Option<Ticket>> {
+        self.set_archived(id, false).await
+    }
+
+    async fn set_archived(&mut self, id: TicketId, archived: bool) -> Result<Option<Ticket>> {
+        let Some(ticket) = self.tickets.get(&id).cloned() else {
+            return Ok(None)
+        };
+
+        let mut ticket = ticket.write().await;
+
+        if ticket.archived != archived {
+            let mut updated = Ticket { archived, version: ticket.version + 1, ..ticket.clone() };
+
+            let now = self.clock.now();
+            updated.touch(&ticket, now);
+
+            self.record(HistoryEntry::between(Some(&ticket), &updated, now), &updated)?;
+            *ticket = updated;
+        }
+
+        Ok(Some(ticket.clone()))
+    }
+
+    pub async fn add_label(&mut self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.set_label(id, label, version, true).await
+    }
+
+    pub async fn remove_label(&mut self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.set_label(id, label, version, false).await
+    }
+
+    // Adding a label that's already there, or removing one that isn't, changes nothing.
+    async fn set_label(&mut self, id: TicketId, label: Label, version: Option<u64>, present: bool)
+        -> Result<Option<Ticket>>
+    {
+        let Some(ticket) = self.tickets.get(&id).cloned() else {
+            return Ok(None)
+        };
+
+        let mut ticket = ticket.write().await;
+        TicketPatch { id, version, ..Default::default() }.check_version(&ticket)?;
+
+        if ticket.labels.contains(&label) == present {
+            return Ok(Some(ticket.clone()))
+        }
+
+        let mut updated = Ticket { version: ticket.version + 1, ..ticket.clone() };
+        if present {
+            updated.labels.insert(label.clone());
+        } else {
+            updated.labels.remove(&label);
+        }
+
+        let now = self.clock.now();
+        updated.touch(&ticket, now);
+
+        self.record(HistoryEntry::between(Some(&ticket), &updated, now), &updated)?;
+        *ticket = updated.clone();
+
+        if present {
+            self.by_label.entry(label).or_default().insert(id);
+        } else {
+            self.unindex(&label, id);
+        }
+
+        Ok(Some(updated))
+    }
+
+    // The ticket's history is kept, ending with its deletion.
+    pub async fn remove(&mut self, id: TicketId) -> Result<Option<Ticket>> {
+        let Some(ticket) = self.tickets.get(&id).cloned() else {
+            return Ok(None)
+        };
+        let ticket = ticket.read().await.clone();
+
+        let entry = HistoryEntry { actor: actor::current(), ..HistoryEntry::deleted(&ticket, self.clock.now()) };
+        self.log(&Record::Removed(id, Box::new(entry.clone())))?;
+
+        self.history.entry(id).or_default().push(entry);
+        self.comments.remove(&id);
+        self.links.retain(|link| !link.touches(id));
+        self.tickets.remove(&id);
+
+        for label in &ticket.labels {
+            self.unindex(label, id);
+        }
+
+        Ok(Some(ticket))
+    }
+
+    pub fn get(&self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
+        self.tickets.get(&id).cloned()
+    }
+
+    // Oldest first. Deleted tickets keep theirs.
+    pub fn history(&self, id: TicketId) -> Option<&[HistoryEntry]> {
+        self.history.get(&id).map(Vec::as_slice)
+    }
+
+    pub fn get_mut(&mut self, id: TicketId) -> Option<&mut Arc<RwLock<Ticket>>> {
+        self.tickets.get_mut(&id)
+    }
+
+    pub fn get_all(&self) ->  Values<'_, TicketId, Arc<RwLock<Ticket>>> {
+         self.tickets.values()
+    }
+
+    pub fn labelled(&self, label: &Label) -> impl Iterator<Item = &Arc<RwLock<Ticket>>> {
+        self.by_label.get(label).into_iter().flatten().filter_map(|id| self.tickets.get(id))
+    }
+
+    // Tickets with the label, from `from` onwards in id order.
+    pub fn labelled_from(&self, label: &Label, from: Bound<TicketId>) -> impl Iterator<Item = &Arc<RwLock<Ticket>>> {
+        self.by_label
+            .get(label)
+            .into_iter()
+            .flat_map(move |ids| ids.range((from, Bound::Unbounded)))
+            .filter_map(|id| self.tickets.get(id))
+    }
+
+    fn unindex(&mut self, label: &Label, id: TicketId) {
+        if let Some(ids) = self.by_label.get_mut(label) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.by_label.remove(label);
+            }
+        }
+    }
+
+    pub fn add_comment(&mut self, ticket: TicketId, draft: CommentDraft) -> Result<Option<Comment>> {
+        draft.validate()?;
+
+        if !self.tickets.contains_key(&ticket) {
+            return Ok(None)
+        }
+
+        let now = self.clock.now();
+        let comment = Comment {
+            id: CommentId(self.comment_counter),
+            ticket_id: ticket,
+            body: draft.body,
+            created_at: now,
+            updated_at: now,
+        };
+        self.log(&Record::Commented(comment.clone()))?;
+
+        self.comment_counter += 1;
+        self.comments.entry(ticket).or_default().insert(comment.id, comment.clone());
+        Ok(Some(comment))
+    }
+
+    // Oldest first.
+    pub fn comments(&self, ticket: TicketId) -> Option<Vec<Comment>> {
+        if !self.tickets.contains_key(&ticket) {
+            return None
+        }
+        Some(self.comments.get(&ticket).into_iter().flat_map(BTreeMap::values).cloned().collect())
+    }
+
+    pub fn edit_comment(&mut self, ticket: TicketId, id: CommentId, draft: CommentDraft) -> Result<Option<Comment>> {
+        draft.validate()?;
+
+        let Some(comment) = self.comments.get(&ticket).and_then(|comments| comments.get(&id)) else {
+            return Ok(None)
+        };
+
+        let edited = Comment { body: draft.body, updated_at: self.clock.now(), ..comment.clone() };
+        self.log(&Record::Commented(edited.clone()))?;
+
+        self.comments.entry(ticket).or_default().insert(id, edited.clone());
+        Ok(Some(edited))
+    }
+
+    pub fn remove_comment(&mut self, ticket: TicketId, id: CommentId) -> Result<Option<Comment>> {
+        if !self.comments.get(&ticket).is_some_and(|comments| comments.contains_key(&id)) {
+            return Ok(None)
+        }
+
+        self.log(&Record::CommentRemoved(ticket, id))?;
+
+        Ok(self.comments.get_mut(&ticket).and_then(|comments| comments.remove(&id)))
+    }
+
+    // Linking twice changes nothing. `None` if `from` doesn't exist.
+    pub fn link(&mut self, link: Link) -> Result<Option<Link>> {
+        if !self.tickets.contains_key(&link.from) {
+            return Ok(None)
+        }
+
+        if !self.tickets.contains_key(&link.to) {
+            return Err(Error::InvalidLink(format!("Ticket {} doesn't exist.", link.to)))
+        }
+
+        link.check(&self.links)?;
+
+        if !self.links.contains(&link) {
+            self.log(&Record::Linked(link))?;
+            self.links.insert(link);
+        }
+
+        Ok(Some(link))
+    }
+
+    pub fn unlink(&mut self, link: Link) -> Result<Option<Link>> {
+        if !self.links.contains(&link) {
+            return Ok(None)
+        }
+
+        self.log(&Record::Unlinked(link))?;
+        self.links.remove(&link);
+
+        Ok(Some(link))
+    }
+
+    // Both ways, e.g. what a ticket blocks and what blocks it.
+    pub fn links(&self, id: TicketId) -> Option<Vec<Link>> {
+        if !self.tickets.contains_key(&id) {
+            return None
+        }
+        Some(self.links.iter().filter(|link| link.touches(id)).copied().collect())
+    }
+
+    pub fn add_webhook(&mut self, draft: WebhookDraft) -> Result<Webhook> {
+        draft.validate()?;
+
+        let webhook = Webhook {
+            id: WebhookId(self.webhook_counter),
+            url: draft.url,
+            secret: draft.secret,
+            created_at: self.clock.now(),
+        };
+        self.log(&Record::WebhookAdded(webhook.clone()))?;
+
+        self.webhook_counter += 1;
+        self.webhooks.insert(webhook.id, webhook.clone());
+        Ok(webhook)
+    }
+
+    pub fn webhooks(&self) -> Vec<Webhook> {
+        self.webhooks.values().cloned().collect()
+    }
+
+    pub fn remove_webhook(&mut self, id: WebhookId) -> Result<Option<Webhook>> {
+        if !self.webhooks.contains_key(&id) {
+            return Ok(None)
+        }
+
+        self.log(&Record::WebhookRemoved(id))?;
+
+        Ok(self.webhooks.remove(&id))
+    }
+
+    // In `Atomic` mode every item is applied in place, with its records held back
+    // from the journal and a note of how to take it back. The first failing item
+    // undoes the ones before it, the journal never hears of any of them.
+    pub async fn batch(&mut self, batch: Batch) -> Result<Vec<BatchOutcome>> {
+        if batch.mode == BatchMode::PerItem {
+            let mut outcomes = Vec::with_capacity(batch.ops.len());
+            for op in batch.ops {
+                outcomes.push(self.apply(op).await.unwrap_or_else(|error| BatchOutcome::Failed(error.problem())));
+            }
+            return Ok(outcomes)
+        }
+
+        // What fails on its own fails before anything is touched.
+        for (index, op) in batch.ops.iter().enumerate() {
+            op.validate().map_err(|error| Error::BatchFailed { index, error: Box::new(error) })?;
+        }
+
+        self.pending = self.journal.is_some().then(Vec::new);
+        let mut undo = Vec::with_capacity(batch.ops.len());
+        let mut outcomes = Vec::with_capacity(batch.ops.len());
+
+        for (index, op) in batch.ops.into_iter().enumerate() {
+            let taken_back = match &op {
+                BatchOp::Create(_) => Undo::Create(TicketId(self.counter)),
+                BatchOp::Patch(patch) => match self.tickets.get(&patch.id) {
+                    Some(ticket) => Undo::Patch(ticket.read().await.clone()),
+                    None => Undo::Nothing,
+                },
+            };
+
+            match self.apply(op).await {
+                Ok(outcome) => {
+                    undo.push(taken_back);
+                    outcomes.push(outcome);
+                }
+                Err(error) => {
+                    self.pending = None;
+                    self.undo(undo).await;
+                    return Err(Error::BatchFailed { index, error: Box::new(error) })
+                }
+            }
+        }
+
+        let records = self.pending.take().unwrap_or_default();
+        if let Err(error) = self.journal.as_mut().map_or(Ok(()), |journal| journal.append_all(&records)) {
+            self.undo(undo).await;
+            return Err(error)
+        }
+        // Too late to fail now, the batch is in the journal. See `log`.
+        let _ = self.compact(&records);
+
+        Ok(outcomes)
+    }
+
+    async fn apply(&mut self, op: BatchOp) -> Result<BatchOutcome> {
+        op.validate()?;
+
+        match op {
+            BatchOp::Create(draft) => {
+                let id = self.add_ticket(draft)?;
+                let ticket = self.tickets[&id].read().await.clone();
+                Ok(BatchOutcome::Created(ticket))
+            }
+            BatchOp::Patch(patch) => {
+                let id = patch.id;
+                self.patch(patch).await?.map(BatchOutcome::Patched).ok_or(Error::NotFound(id))
+            }
+        }
+    }
+
+    // Last applied, first taken back. Creating and patching only ever add to the
+    // ticket's history, so dropping its last entry is enough there.
+    async fn undo(&mut self, undo: Vec<Undo>) {
+        for undo in undo.into_iter().rev() {
+            match undo {
+                Undo::Create(id) => {
+                    self.tickets.remove(&id);
+                    self.history.remove(&id);
+                    self.counter = id.0;
+                }
+                Undo::Patch(ticket) => {
+                    if let Some(history) = self.history.get_mut(&ticket.id) {
+                        history.pop();
+                    }
+                    if let Some(current) = self.tickets.get(&ticket.id) {
+                        *current.write().await = ticket;
+                    }
+                }
+                Undo::Nothing => {}
+            }
+        }
+    }
+
+    async fn check_dependencies(&self, id: TicketId) -> Result<()> {
+        let mut blockers = Vec::new();
+        let mut children = Vec::new();
+
+        for link in &self.links {
+            let (other, open) = match link.kind {
+                LinkKind::Blocks if link.to == id => (link.from, &mut blockers),
+                LinkKind::ParentOf if link.from == id => (link.to, &mut children),
+                _ => continue,
+            };
+
+            if let Some(other_ticket) = self.tickets.get(&other) {
+                if other_ticket.read().await.status != Status::Done {
+                    open.push(other);
+                }
+            }
+        }
+
+        check_done(id, blockers, children)
+    }
+
+    fn record(&mut self, entry: HistoryEntry, new: &Ticket) -> Result<()> {
+        let entry = HistoryEntry { actor: actor::current(), ..entry };
+        self.log(&Record::Changed(new.clone(), Box::new(entry.clone())))?;
+        self.history.entry(new.id).or_default().push(entry);
+        Ok(())
+    }
+
+    // Nothing is kept in memory unless it made it to the journal first, or is part
+    // of a batch that can still be undone.
+    fn log(&mut self, record: &Record) -> Result<()> {
+        if let Some(pending) = self.pending.as_mut() {
+            pending.push(record.clone());
+            return Ok(())
+        }
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.append(record)?,
+            None => return Ok(()),
+        }
+
+        // The record is safe in the log, so the change goes ahead either way. A
+        // snapshot that fails leaves the log due, it's tried again after the next one.
+        let _ = self.compact(std::slice::from_ref(record));
+        Ok(())
+    }
+
+    // Swaps the log for a snapshot of what's in memory once `records`, the ones
+    // just logged, are in too.
+    fn compact(&mut self, records: &[Record]) -> Result<()> {
+        if !self.journal.as_ref().is_some_and(Journal::is_due) {
+            return Ok(())
+        }
+
+        let Some(state) = self.state(records) else {
+            return Ok(())
+        };
+
+        match self.journal.as_mut() {
+            Some(journal) => journal.snapshot(&state),
+            None => Ok(()),
+        }
+    }
+
+    // `None` while someone else holds on to one of the tickets, the snapshot can
+    // wait for the next change then. The ticket being changed is held by the
+    // change itself, its record brings the new version along.
+    fn state(&self, records: &[Record]) -> Option<journal::State> {
+        let changing = |id| records.iter().any(|record| matches!(record, Record::Changed(ticket, _) if ticket.id == id));
+
+        let mut tickets = BTreeMap::new();
+        for (&id, ticket) in &self.tickets {
+            match ticket.try_read() {
+                Ok(ticket) => {
+                    tickets.insert(id, ticket.clone());
+                }
+                Err(_) if changing(id) => {}
+                Err(_) => return None,
+            }
+        }
+
+        let mut state = journal::State {
+            counter: self.counter,
+            tickets,
+            history: self.history.clone(),
+            comment_counter: self.comment_counter,
+            comments: self.comments.clone(),
+            links: self.links.clone(),
+            webhook_counter: self.webhook_counter,
+            webhooks: self.webhooks.clone(),
+        };
+
+        // Applying a record that's already in memory changes nothing.
+        for record in records {
+            state.apply(record.clone());
+        }
+
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Status, TicketDescription, TicketDraft, TicketTitle};
+
+    fn create_draft(title: &str, description: &str) -> TicketDraft {
+        TicketDraft {
+            title: TicketTitle::try_from(title).unwrap(),
+            description: TicketDescription::try_from(description).unwrap(),
+            priority: None,
+            due_date: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn check_if_get_mut_provides_mutable_arc_and_allows_status_update() {
+        let mut store = TicketStore::new();
+
+        let id = store.add_ticket(create_draft("The Science", "A documentary about science.")).unwrap();
+        let ticket = store.get_mut(id).unwrap();
+
+        {
+            let mut write_guard = ticket.write().await;
+            write_guard.status = Status::InProgress;
+        }
+
+        let another_ticket = store.get(id).unwrap();
+        let read_guard = another_ticket.read().await;
+        assert_eq!(read_guard.status, Status::InProgress);
+    }
+
+    #[tokio::test]
+    async fn check_if_reopened_store_keeps_tickets_and_never_reuses_ids() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let mut store = TicketStore::open(dir.path()).unwrap();
+            store.add_ticket(create_draft("First", "First ticket")).unwrap();
+            let id = store.add_ticket(create_draft("Second", "Second ticket")).unwrap();
+            store.patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() })
+                .await.unwrap();
+            store.add_label(id, Label::try_from("docs").unwrap(), None).await.unwrap();
+        }
+
+        let mut store = TicketStore::open(dir.path()).unwrap();
+
+        assert_eq!(store.get_all().count(), 2);
+        assert_eq!(store.get(TicketId(1)).unwrap().read().await.status, Status::InProgress);
+        assert_eq!(store.history(TicketId(1)).unwrap().len(), 3);
+        assert_eq!(store.labelled(&Label::try_from("docs").unwrap()).count(), 1, "The label index is rebuilt");
+
+        let id = store.add_ticket(create_draft("Third", "Third ticket")).unwrap();
+        assert_eq!(id, TicketId(2));
+    }
+
+    #[tokio::test]
+    async fn check_if_snapshots_are_taken_from_what_the_store_holds() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let (journal, state) = Journal::open_with(dir.path(), 2).unwrap();
+            let mut store = TicketStore::with_journal(journal, state);
+
+            let id = store.add_ticket(create_draft("First", "First ticket")).unwrap();
+            store.patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() }).await.unwrap();
+            store.add_ticket(create_draft("Second", "Second ticket")).unwrap();
+        }
+
+        let log = std::fs::read_to_string(dir.path().join(journal::LOG_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 1, "Only the record after the snapshot should remain");
+
+        let store = TicketStore::open(dir.path()).unwrap();
+        assert_eq!(store.get_all().count(), 2);
+        assert_eq!(store.get(TicketId(0)).unwrap().read().await.status, Status::InProgress);
+        assert_eq!(store.history(TicketId(0)).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn check_if_a_failed_snapshot_doesnt_fail_the_change() {
+        let dir = tempfile::tempdir().unwrap();
+        // Nothing can be written where the snapshot goes first.
+        let tmp = dir.path().join(format!("{}.tmp", journal::SNAPSHOT_FILE));
+        std::fs::create_dir(&tmp).unwrap();
+
+        {
+            let (journal, state) = Journal::open_with(dir.path(), 1).unwrap();
+            let mut store = TicketStore::with_journal(journal, state);
+
+            assert_eq!(store.add_ticket(create_draft("First", "First ticket")).unwrap(), TicketId(0));
+            assert_eq!(store.add_ticket(create_draft("Second", "Second ticket")).unwrap(), TicketId(1));
+        }
+
+        let log = std::fs::read_to_string(dir.path().join(journal::LOG_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 2, "Both records should still be in the log");
+
+        std::fs::remove_dir(&tmp).unwrap();
+        let (journal, state) = Journal::open_with(dir.path(), 1).unwrap();
+        let mut store = TicketStore::with_journal(journal, state);
+
+        assert_eq!(store.get_all().count(), 2);
+        for id in [TicketId(0), TicketId(1)] {
+            assert_eq!(store.history(id).unwrap().len(), 1);
+        }
+
+        // The snapshot goes through once it can.
+        store.add_ticket(create_draft("Third", "Third ticket")).unwrap();
+        let log = std::fs::read_to_string(dir.path().join(journal::LOG_FILE)).unwrap();
+        assert_eq!(log.lines().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn check_if_custom_workflow_is_enforced_on_patch_and_reopen() {
+        use crate::error::Error;
+
+        let workflow = Workflow::empty()
+            .allow(Status::ToDo, Status::InProgress)
+            .allow(Status::InProgress, Status::Done)
+            .allow_reopen(Status::Done, Status::InProgress);
+        let mut store = TicketStore::new().with_workflow(workflow);
+
+        let id = store.add_ticket(create_draft("Strict", "One step at a time.")).unwrap();
+
+        let skip = TicketPatch { id, status: Some(Status::Done), ..Default::default() };
+        assert!(matches!(store.patch(skip).await, Err(Error::InvalidTransition { .. })));
+
+        for status in [Status::InProgress, Status::Done] {
+            store.patch(TicketPatch { id, status: Some(status), ..Default::default() }).await.unwrap();
+        }
+
+        let ticket = store.reopen(id, None).await.unwrap().unwrap();
+        assert_eq!(ticket.status, Status::InProgress);
+    }
+
+    #[tokio::test]
+    async fn check_if_only_whole_batches_reach_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let mut store = TicketStore::open(dir.path()).unwrap();
+            let create = |title| BatchOp::Create(create_draft(title, "Batched."));
+
+            store.batch(Batch { mode: BatchMode::Atomic, ops: vec![create("One"), create("Two")] }).await.unwrap();
+
+            let missing = BatchOp::Patch(TicketPatch { id: TicketId(42), ..Default::default() });
+            let failed = store.batch(Batch { mode: BatchMode::Atomic, ops: vec![create("Three"), missing] }).await;
+            assert!(matches!(failed, Err(Error::BatchFailed { index: 1, .. })));
+        }
+
+        let mut store = TicketStore::open(dir.path()).unwrap();
+
+        assert_eq!(store.get_all().count(), 2);
+        assert_eq!(store.add_ticket(create_draft("Three", "Not batched.")).unwrap(), TicketId(2));
+    }
+    #[tokio::test]
+    async fn check_if_a_failed_snapshot_doesnt_fail_the_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(format!("{}.tmp", journal::SNAPSHOT_FILE))).unwrap();
+
+        let (journal, state) = Journal::open_with(dir.path(), 1).unwrap();
+        let mut store = TicketStore::with_journal(journal, state);
+        let create = |title| BatchOp::Create(create_draft(title, "Batched."));
+
+        let outcomes = store.batch(Batch { mode: BatchMode::Atomic, ops: vec![create("One"), create("Two")] }).await;
+        assert_eq!(outcomes.unwrap().len(), 2);
+        drop(store);
+
+        let store = TicketStore::open(dir.path()).unwrap();
+        assert_eq!(store.get_all().count(), 2);
+    }
+}
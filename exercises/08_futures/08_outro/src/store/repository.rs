@@ -0,0 +1,234 @@
+use std::ops::Bound;
+use std::sync::Arc;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::{
+    data::overdue,
+    error::Result,
+    data::{
+        Batch, BatchOutcome, Comment, CommentDraft, CommentId, HistoryEntry, Label, Link, Page, SortBy, TicketId,
+        Ticket, TicketDraft, TicketPatch, TicketQuery, Webhook, WebhookDraft, WebhookId,
+    },
+    store::TicketStore,
+};
+
+// Everything the server needs from a storage backend. Implementations take care
+// of their own locking, so they can be shared between handlers as they are.
+#[async_trait]
+pub trait TicketRepository: Send + Sync {
+    async fn create(&self, draft: TicketDraft) -> Result<TicketId>;
+
+    async fn get(&self, id: TicketId) -> Result<Option<Ticket>>;
+
+    // Has to agree with `TicketQuery::paginate`, which is what the in-memory
+    // store uses as is.
+    async fn list(&self, query: TicketQuery) -> Result<Page>;
+
+    // Has to agree with `data::overdue`, as of the store's clock.
+    async fn overdue(&self) -> Result<Vec<Ticket>>;
+
+    // Status changes have to be allowed by the store's `Workflow`, and nothing goes
+    // to done while it has open blockers or sub-tasks.
+    async fn patch(&self, patch: TicketPatch) -> Result<Option<Ticket>>;
+
+    // The only way out of a status the workflow otherwise keeps tickets in.
+    async fn reopen(&self, id: TicketId, version: Option<u64>) -> Result<Option<Ticket>>;
+
+    async fn delete(&self, id: TicketId) -> Result<Option<Ticket>>;
+
+    async fn archive(&self, id: TicketId) -> Result<Option<Ticket>>;
+
+    async fn restore(&self, id: TicketId) -> Result<Option<Ticket>>;
+
+    // Both are no-ops, version included, if the label is already there or gone.
+    async fn add_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>>;
+
+    async fn remove_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>>;
+
+    // Every change made to a ticket, oldest first. It outlives the ticket, ending
+    // with its deletion.
+    async fn history(&self, id: TicketId) -> Result<Option<Vec<HistoryEntry>>>;
+
+    // `None` if the ticket doesn't exist. Comments go away with their ticket.
+    async fn add_comment(&self, ticket: TicketId, draft: CommentDraft) -> Result<Option<Comment>>;
+
+    // Oldest first.
+    async fn comments(&self, ticket: TicketId) -> Result<Option<Vec<Comment>>>;
+
+    // `None` if there's no such comment on that ticket.
+    async fn edit_comment(&self, ticket: TicketId, id: CommentId, draft: CommentDraft)
+        -> Result<Option<Comment>>;
+
+    async fn delete_comment(&self, ticket: TicketId, id: CommentId) -> Result<Option<Comment>>;
+
+    // `None` if `link.from` doesn't exist. Links go away with either ticket.
+    async fn link(&self, link: Link) -> Result<Option<Link>>;
+
+    // `None` if there was no such link.
+    async fn unlink(&self, link: Link) -> Result<Option<Link>>;
+
+    // Every link to or from the ticket, ordered by kind, then `from`, then `to`.
+    async fn links(&self, id: TicketId) -> Result<Option<Vec<Link>>>;
+
+    // Applies every item in one go, holding the store for the whole batch. In `Atomic`
+    // mode the first failure rolls everything back and comes back as `BatchFailed`.
+    async fn batch(&self, batch: Batch) -> Result<Vec<BatchOutcome>>;
+
+    async fn add_webhook(&self, draft: WebhookDraft) -> Result<Webhook>;
+
+    // Oldest first, secrets included.
+    async fn webhooks(&self) -> Result<Vec<Webhook>>;
+
+    async fn remove_webhook(&self, id: WebhookId) -> Result<Option<Webhook>>;
+}
+
+// In-memory, or file-backed when the store was opened with a journal.
+#[async_trait]
+impl TicketRepository for RwLock<TicketStore> {
+    async fn create(&self, draft: TicketDraft) -> Result<TicketId> {
+        self.write().await.add_ticket(draft)
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Option<Ticket>> {
+        let Some(ticket) = self.read().await.get(id) else {
+            return Ok(None)
+        };
+        let ticket = ticket.read().await.clone();
+        Ok(Some(ticket))
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<Page> {
+        let store = self.read().await;
+
+        // Ids come out of the store in order, so a page only has to look at tickets
+        // past the cursor until it has one more than fits.
+        if query.sort == SortBy::Id {
+            let from = query.after.map_or(Bound::Unbounded, Bound::Excluded);
+            let candidates: Box<dyn Iterator<Item = &Arc<RwLock<Ticket>>> + Send> = match &query.label {
+                Some(label) => Box::new(store.labelled_from(label, from)),
+                None => Box::new(store.tickets.range((from, Bound::Unbounded)).map(|(_, ticket)| ticket)),
+            };
+
+            let mut tickets = Vec::new();
+
+            for ticket in candidates {
+                let ticket = ticket.read().await;
+
+                if query.matches(&ticket) {
+                    tickets.push(ticket.clone());
+
+                    if tickets.len() > query.page_size() {
+                        break
+                    }
+                }
+            }
+
+            return Ok(query.page(tickets))
+        }
+
+        // A label narrows things down to its tickets, plus the cursor, which
+        // `paginate` still needs to find even if it doesn't carry the label.
+        let candidates: Vec<_> = match &query.label {
+            Some(label) => store.labelled(label).chain(query.after.and_then(|id| store.tickets.get(&id))).collect(),
+            None => store.get_all().collect(),
+        };
+
+        let mut tickets = Vec::new();
+
+        for ticket in candidates {
+            tickets.push(ticket.read().await);
+        }
+
+        query.paginate(tickets.iter().map(|ticket| &**ticket))
+    }
+
+    async fn overdue(&self) -> Result<Vec<Ticket>> {
+        let store = self.read().await;
+        let mut tickets = Vec::new();
+
+        for ticket in store.get_all() {
+            tickets.push(ticket.read().await.clone());
+        }
+
+        Ok(overdue(tickets, store.clock.now().date_naive()))
+    }
+
+    async fn patch(&self, patch: TicketPatch) -> Result<Option<Ticket>> {
+        self.write().await.patch(patch).await
+    }
+
+    async fn reopen(&self, id: TicketId, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.write().await.reopen(id, version).await
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.write().await.remove(id).await
+    }
+
+    async fn archive(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.write().await.archive(id).await
+    }
+
+    async fn restore(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.write().await.restore(id).await
+    }
+
+    async fn add_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.write().await.add_label(id, label, version).await
+    }
+
+    async fn remove_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.write().await.remove_label(id, label, version).await
+    }
+
+    async fn history(&self, id: TicketId) -> Result<Option<Vec<HistoryEntry>>> {
+        Ok(self.read().await.history(id).map(<[_]>::to_vec))
+    }
+
+    async fn add_comment(&self, ticket: TicketId, draft: CommentDraft) -> Result<Option<Comment>> {
+        self.write().await.add_comment(ticket, draft)
+    }
+
+    async fn comments(&self, ticket: TicketId) -> Result<Option<Vec<Comment>>> {
+        Ok(self.read().await.comments(ticket))
+    }
+
+    async fn edit_comment(&self, ticket: TicketId, id: CommentId, draft: CommentDraft)
+        -> Result<Option<Comment>>
+    {
+        self.write().await.edit_comment(ticket, id, draft)
+    }
+
+    async fn delete_comment(&self, ticket: TicketId, id: CommentId) -> Result<Option<Comment>> {
+        self.write().await.remove_comment(ticket, id)
+    }
+
+    async fn link(&self, link: Link) -> Result<Option<Link>> {
+        self.write().await.link(link)
+    }
+
+    async fn unlink(&self, link: Link) -> Result<Option<Link>> {
+        self.write().await.unlink(link)
+    }
+
+    async fn links(&self, id: TicketId) -> Result<Option<Vec<Link>>> {
+        Ok(self.read().await.links(id))
+    }
+
+    async fn batch(&self, batch: Batch) -> Result<Vec<BatchOutcome>> {
+        self.write().await.batch(batch).await
+    }
+
+    async fn add_webhook(&self, draft: WebhookDraft) -> Result<Webhook> {
+        self.write().await.add_webhook(draft)
+    }
+
+    async fn webhooks(&self) -> Result<Vec<Webhook>> {
+        Ok(self.read().await.webhooks())
+    }
+
+    async fn remove_webhook(&self, id: WebhookId) -> Result<Option<Webhook>> {
+        self.write().await.remove_webhook(id)
+    }
+}
@@ -0,0 +1,1087 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use rusqlite::{params, params_from_iter, types::Value, Connection, OptionalExtension, Row};
+use tokio::task;
+
+use crate::{
+    error::{Error, Result},
+    data::{
+        Batch, BatchMode, BatchOp, BatchOutcome, Comment, CommentBody, CommentDraft, CommentId, HistoryEntry, Label,
+        Link, LinkKind, Page, Priority, SortBy, Status, TicketId, Ticket, TicketDraft, TicketPatch, TicketQuery,
+        TicketTitle, TicketDescription, Webhook, WebhookDraft, WebhookId, Workflow,
+    },
+    data::link::check_done,
+    data::history::Action,
+    store::{actor, Clock, SystemClock, TicketRepository},
+};
+
+// Applied in order, each one exactly once. The number of migrations already run
+// is tracked in SQLite's `user_version` pragma, so never edit or reorder these,
+// only append new ones.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE tickets (
+        id                INTEGER PRIMARY KEY,
+        title             TEXT NOT NULL,
+        description       TEXT NOT NULL,
+        status            TEXT NOT NULL CHECK (status IN ('To-do', 'In progress', 'Done')),
+        archived          INTEGER NOT NULL DEFAULT 0,
+        version           INTEGER NOT NULL DEFAULT 1,
+        created_at        TEXT NOT NULL,
+        updated_at        TEXT NOT NULL,
+        status_changed_at TEXT NOT NULL,
+        priority          TEXT CHECK (priority IN ('low', 'medium', 'high', 'critical')),
+        due_date          TEXT
+    );
+    CREATE INDEX tickets_by_status ON tickets (status);
+    CREATE INDEX tickets_by_due_date ON tickets (due_date) WHERE due_date IS NOT NULL;
+    CREATE TABLE ticket_counter (next_id INTEGER NOT NULL);
+    INSERT INTO ticket_counter (next_id) VALUES (0);
+    CREATE TABLE ticket_history (
+        ticket_id INTEGER NOT NULL,
+        version   INTEGER NOT NULL,
+        at        TEXT NOT NULL,
+        entry     TEXT NOT NULL,
+        PRIMARY KEY (ticket_id, version)
+    );
+    CREATE TRIGGER ticket_history_is_append_only BEFORE UPDATE ON ticket_history
+    BEGIN
+        SELECT RAISE(ABORT, 'ticket history is append-only');
+    END;
+    CREATE TRIGGER ticket_history_is_kept BEFORE DELETE ON ticket_history
+    BEGIN
+        SELECT RAISE(ABORT, 'ticket history is append-only');
+    END;
+    CREATE TABLE ticket_comments (
+        id         INTEGER PRIMARY KEY,
+        ticket_id  INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        body       TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE INDEX ticket_comments_by_ticket ON ticket_comments (ticket_id, id);
+    CREATE TABLE comment_counter (next_id INTEGER NOT NULL);
+    INSERT INTO comment_counter (next_id) VALUES (0);
+    CREATE TABLE ticket_labels (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        label     TEXT NOT NULL,
+        PRIMARY KEY (ticket_id, label)
+    );
+    CREATE INDEX ticket_labels_by_label ON ticket_labels (label, ticket_id);
+    CREATE TABLE ticket_links (
+        kind    TEXT NOT NULL CHECK (kind IN ('blocks', 'duplicates', 'parent_of')),
+        from_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        to_id   INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        PRIMARY KEY (kind, from_id, to_id),
+        CHECK (from_id != to_id)
+    );
+    CREATE INDEX ticket_links_by_source ON ticket_links (from_id);
+    CREATE INDEX ticket_links_by_target ON ticket_links (to_id);
+    CREATE TABLE webhooks (
+        id         INTEGER PRIMARY KEY,
+        url        TEXT NOT NULL,
+        secret     TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE webhook_counter (next_id INTEGER NOT NULL);
+    INSERT INTO webhook_counter (next_id) VALUES (0);",
+];
+
+// Labels can't contain commas, so they come back joined by one.
+const SELECT_TICKET: &str = "SELECT id, title, description, status, archived, version, \
+    created_at, updated_at, status_changed_at, \
+    (SELECT group_concat(label, ',') FROM ticket_labels WHERE ticket_id = tickets.id), \
+    priority, due_date \
+    FROM tickets";
+
+const SELECT_LINK: &str = "SELECT kind, from_id, to_id FROM ticket_links";
+
+const SELECT_COMMENT: &str = "SELECT id, ticket_id, body, created_at, updated_at FROM ticket_comments";
+
+const SELECT_WEBHOOK: &str = "SELECT id, url, secret, created_at FROM webhooks";
+
+// Statuses sort in workflow order, not alphabetically.
+const STATUS_RANK: &str = "CASE status WHEN 'To-do' THEN 0 WHEN 'In progress' THEN 1 ELSE 2 END";
+
+// Most urgent first, tickets without a priority last.
+const PRIORITY_RANK: &str =
+    "CASE priority WHEN 'critical' THEN 0 WHEN 'high' THEN 1 WHEN 'medium' THEN 2 WHEN 'low' THEN 3 ELSE 4 END";
+
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    conn: Arc<Mutex<Connection>>,
+    workflow: Arc<Workflow>,
+    clock: Arc<dyn Clock>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+        Ok(Self { conn: Arc::new(Mutex::new(conn)), workflow: Arc::default(), clock: Arc::new(SystemClock) })
+    }
+
+    pub fn with_workflow(mut self, workflow: Workflow) -> Self {
+        self.workflow = Arc::new(workflow);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // `rusqlite` is blocking, so every query runs on tokio's blocking thread pool.
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let actor = actor::current();
+
+        task::spawn_blocking(move || {
+            // A panic mid-query can't leave a transaction half applied, it's rolled
+            // back when dropped, so a poisoned connection is still safe to use.
+            let mut conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            actor::sync_scope(actor, || f(&mut conn))
+        }).await?
+    }
+
+    async fn set_label(&self, id: TicketId, label: Label, version: Option<u64>, present: bool)
+        -> Result<Option<Ticket>>
+    {
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(ticket) = select(&tx, id)? else {
+                return Ok(None)
+            };
+            TicketPatch { id, version, ..Default::default() }.check_version(&ticket)?;
+
+            if ticket.labels.contains(&label) == present {
+                return Ok(Some(ticket))
+            }
+
+            let mut updated = Ticket { version: ticket.version + 1, ..ticket.clone() };
+            if present {
+                tx.execute(
+                    "INSERT INTO ticket_labels (ticket_id, label) VALUES (?1, ?2)",
+                    params![id.0 as i64, label.to_string()],
+                )?;
+                updated.labels.insert(label);
+            } else {
+                tx.execute(
+                    "DELETE FROM ticket_labels WHERE ticket_id = ?1 AND label = ?2",
+                    params![id.0 as i64, label.to_string()],
+                )?;
+                updated.labels.remove(&label);
+            }
+
+            let now = clock.now();
+            updated.touch(&ticket, now);
+
+            tx.execute(
+                "UPDATE tickets SET version = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id.0 as i64, updated.version as i64, timestamp(now)],
+            )?;
+            record(&tx, HistoryEntry::between(Some(&ticket), &updated, now), &updated)?;
+
+            tx.commit()?;
+            Ok(Some(updated))
+        }).await
+    }
+
+    async fn set_archived(&self, id: TicketId, archived: bool) -> Result<Option<Ticket>> {
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(ticket) = select(&tx, id)? else {
+                return Ok(None)
+            };
+
+            if ticket.archived == archived {
+                return Ok(Some(ticket))
+            }
+
+            let mut updated = Ticket { archived, version: ticket.version + 1, ..ticket.clone() };
+
+            let now = clock.now();
+            updated.touch(&ticket, now);
+
+            tx.execute(
+                "UPDATE tickets SET archived = ?2, version = ?3, updated_at = ?4 WHERE id = ?1",
+                params![id.0 as i64, archived, updated.version as i64, timestamp(now)],
+            )?;
+            record(&tx, HistoryEntry::between(Some(&ticket), &updated, now), &updated)?;
+
+            tx.commit()?;
+            Ok(Some(updated))
+        }).await
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+
+    let version: i64 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let version = version as usize;
+
+    if version > MIGRATIONS.len() {
+        return Err(Error::Storage(format!(
+            "Database schema version {version} is newer than the latest known version {}.",
+            MIGRATIONS.len()
+        )))
+    }
+
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", applied as i64 + 1)?;
+    }
+
+    Ok(tx.commit()?)
+}
+
+// Whatever ends up in the database, or comes back out of it, goes through the same
+// validation as the rest of the crate.
+fn validate(title: &TicketTitle, description: &TicketDescription) -> Result<()> {
+    TicketTitle::try_from(title.to_string())?;
+    TicketDescription::try_from(description.to_string())?;
+    Ok(())
+}
+
+// A ticket exactly as it's stored, before any validation.
+struct TicketRow {
+    id: i64,
+    title: String,
+    description: String,
+    status: String,
+    archived: bool,
+    version: i64,
+    created_at: String,
+    updated_at: String,
+    status_changed_at: String,
+    labels: Option<String>,
+    priority: Option<String>,
+    due_date: Option<String>,
+}
+
+fn read_ticket(row: &Row) -> rusqlite::Result<TicketRow> {
+    Ok(TicketRow {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        status: row.get(3)?,
+        archived: row.get(4)?,
+        version: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        status_changed_at: row.get(8)?,
+        labels: row.get(9)?,
+        priority: row.get(10)?,
+        due_date: row.get(11)?,
+    })
+}
+
+fn into_ticket(row: TicketRow) -> Result<Ticket> {
+    Ok(Ticket {
+        archived: row.archived,
+        version: row.version as u64,
+        created_at: parse_timestamp(&row.created_at)?,
+        updated_at: parse_timestamp(&row.updated_at)?,
+        status_changed_at: parse_timestamp(&row.status_changed_at)?,
+        labels: row.labels
+            .iter()
+            .flat_map(|labels| labels.split(','))
+            .map(Label::try_from)
+            .collect::<std::result::Result<BTreeSet<_>, _>>()?,
+        priority: row.priority.map(Priority::try_from).transpose()?,
+        due_date: row.due_date.map(|due| due.parse::<NaiveDate>()).transpose()?,
+        ..Ticket::with(TicketId(row.id as u64), row.title, row.description, Status::try_from(row.status)?)?
+    })
+}
+
+// Fixed width, so timestamps sort the same as text and as time.
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+fn parse_timestamp(at: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(at)
+        .map(|at| at.to_utc())
+        .map_err(|e| Error::Storage(format!("Invalid timestamp {at:?}: {e}")))
+}
+
+fn select(tx: &Connection, id: TicketId) -> Result<Option<Ticket>> {
+    tx.query_row(&format!("{SELECT_TICKET} WHERE id = ?1"), [id.0 as i64], read_ticket)
+        .optional()?
+        .map(into_ticket)
+        .transpose()
+}
+
+// A comment exactly as it's stored, before any validation.
+struct CommentRow {
+    id: i64,
+    ticket_id: i64,
+    body: String,
+    created_at: String,
+    updated_at: String,
+}
+
+fn read_comment(row: &Row) -> rusqlite::Result<CommentRow> {
+    Ok(CommentRow {
+        id: row.get(0)?,
+        ticket_id: row.get(1)?,
+        body: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+    })
+}
+
+fn into_comment(row: CommentRow) -> Result<Comment> {
+    Ok(Comment {
+        id: CommentId(row.id as u64),
+        ticket_id: TicketId(row.ticket_id as u64),
+        body: CommentBody::try_from(row.body)?,
+        created_at: parse_timestamp(&row.created_at)?,
+        updated_at: parse_timestamp(&row.updated_at)?,
+    })
+}
+
+fn select_comment(tx: &Connection, ticket: TicketId, id: CommentId) -> Result<Option<Comment>> {
+    tx.query_row(
+        &format!("{SELECT_COMMENT} WHERE ticket_id = ?1 AND id = ?2"),
+        [ticket.0 as i64, id.0 as i64],
+        read_comment,
+    )
+        .optional()?
+        .map(into_comment)
+        .transpose()
+}
+
+fn read_webhook(row: &Row) -> rusqlite::Result<(i64, String, String, String)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn into_webhook((id, url, secret, created_at): (i64, String, String, String)) -> Result<Webhook> {
+    Ok(Webhook { id: WebhookId(id as u64), url, secret, created_at: parse_timestamp(&created_at)? })
+}
+
+fn read_link(row: &Row) -> rusqlite::Result<(String, i64, i64)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+}
+
+fn into_link((kind, from, to): (String, i64, i64)) -> Result<Link> {
+    Ok(Link { kind: LinkKind::try_from(kind.as_str())?, from: TicketId(from as u64), to: TicketId(to as u64) })
+}
+
+// The other end of every `kind` link matching `filter`, that isn't done yet.
+fn open_links(tx: &Connection, id: TicketId, kind: LinkKind, filter: &str, other: &str) -> Result<Vec<TicketId>> {
+    tx.prepare(&format!(
+        "SELECT {other} FROM ticket_links JOIN tickets ON tickets.id = {other}
+        WHERE kind = ?1 AND {filter} = ?2 AND status != 'Done'
+        ORDER BY {other}"
+    ))?
+        .query_map(params![kind.to_string(), id.0 as i64], |row| row.get::<_, i64>(0))?
+        .map(|id| Ok(TicketId(id? as u64)))
+        .collect()
+}
+
+fn insert(conn: &Connection, clock: &dyn Clock, draft: TicketDraft) -> Result<Ticket> {
+    let now = clock.now();
+
+    let id: i64 = conn.query_row("SELECT next_id FROM ticket_counter", [], |row| row.get(0))?;
+    let ticket = Ticket {
+        id: TicketId(id as u64),
+        title: draft.title,
+        description: draft.description,
+        status: Status::ToDo,
+        version: 1,
+        archived: false,
+        labels: BTreeSet::new(),
+        priority: draft.priority,
+        due_date: draft.due_date,
+        created_at: now,
+        updated_at: now,
+        status_changed_at: now,
+    };
+
+    conn.execute(
+        "INSERT INTO tickets (
+            id, title, description, status, version, created_at, updated_at, status_changed_at,
+            priority, due_date
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6, ?6, ?7, ?8)",
+        params![
+            id,
+            ticket.title.to_string(),
+            ticket.description.to_string(),
+            ticket.status.to_string(),
+            ticket.version as i64,
+            timestamp(now),
+            ticket.priority.map(|p| p.to_string()),
+            ticket.due_date.map(|d| d.to_string())
+        ],
+    )?;
+    conn.execute("UPDATE ticket_counter SET next_id = ?1", [id + 1])?;
+    record(conn, HistoryEntry::between(None, &ticket, now), &ticket)?;
+
+    Ok(ticket)
+}
+
+// `None` if there's no such ticket.
+fn update(conn: &Connection, workflow: &Workflow, clock: &dyn Clock, patch: TicketPatch) -> Result<Option<Ticket>> {
+    let Some(old) = select(conn, patch.id)? else {
+        return Ok(None)
+    };
+    patch.check_version(&old)?;
+
+    if let Some(status) = patch.status {
+        workflow.check_move(&old, status)?;
+
+        if status == Status::Done && old.status != Status::Done {
+            check_done(
+                old.id,
+                open_links(conn, old.id, LinkKind::Blocks, "to_id", "from_id")?,
+                open_links(conn, old.id, LinkKind::ParentOf, "from_id", "to_id")?,
+            )?;
+        }
+    }
+
+    let mut ticket = old.clone();
+    ticket.version += 1;
+
+    if let Some(title) = patch.title {
+        ticket.title = title;
+    }
+
+    if let Some(description) = patch.description {
+        ticket.description = description;
+    }
+
+    if let Some(status) = patch.status {
+        ticket.status = status;
+    }
+
+    if let Some(priority) = patch.priority {
+        ticket.priority = priority;
+    }
+
+    if let Some(due_date) = patch.due_date {
+        ticket.due_date = due_date;
+    }
+
+    let now = clock.now();
+    ticket.touch(&old, now);
+
+    conn.execute(
+        "UPDATE tickets SET title = ?2, description = ?3, status = ?4, version = ?5,
+            updated_at = ?6, status_changed_at = ?7, priority = ?8, due_date = ?9
+        WHERE id = ?1",
+        params![
+            ticket.id.0 as i64,
+            ticket.title.to_string(),
+            ticket.description.to_string(),
+            ticket.status.to_string(),
+            ticket.version as i64,
+            timestamp(ticket.updated_at),
+            timestamp(ticket.status_changed_at),
+            ticket.priority.map(|p| p.to_string()),
+            ticket.due_date.map(|d| d.to_string())
+        ],
+    )?;
+    record(conn, HistoryEntry::between(Some(&old), &ticket, now), &ticket)?;
+
+    Ok(Some(ticket))
+}
+
+fn apply(conn: &Connection, workflow: &Workflow, clock: &dyn Clock, op: BatchOp) -> Result<BatchOutcome> {
+    op.validate()?;
+
+    match op {
+        BatchOp::Create(draft) => Ok(BatchOutcome::Created(insert(conn, clock, draft)?)),
+        BatchOp::Patch(patch) => {
+            let id = patch.id;
+            update(conn, workflow, clock, patch)?.map(BatchOutcome::Patched).ok_or(Error::NotFound(id))
+        }
+    }
+}
+
+fn record(tx: &Connection, entry: HistoryEntry, new: &Ticket) -> Result<()> {
+    let entry = HistoryEntry { actor: actor::current(), ..entry };
+
+    tx.execute(
+        "INSERT INTO ticket_history (ticket_id, version, at, entry) VALUES (?1, ?2, ?3, ?4)",
+        params![new.id.0 as i64, new.version as i64, timestamp(entry.at), serde_json::to_string(&entry)?],
+    )?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl TicketRepository for SqliteStore {
+    async fn create(&self, draft: TicketDraft) -> Result<TicketId> {
+        validate(&draft.title, &draft.description)?;
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let ticket = insert(&tx, clock.as_ref(), draft)?;
+
+            tx.commit()?;
+            Ok(ticket.id)
+        }).await
+    }
+
+    async fn get(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.run(move |conn| select(conn, id)).await
+    }
+
+    async fn list(&self, query: TicketQuery) -> Result<Page> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let key = match query.sort {
+                SortBy::Id => "id",
+                SortBy::Title => "title",
+                SortBy::Status => STATUS_RANK,
+            };
+
+            let mut filters = Vec::new();
+            let mut values = Vec::new();
+
+            if !query.archived {
+                filters.push("NOT archived".to_string());
+            }
+
+            if let Some(status) = query.status {
+                filters.push("status = ?".into());
+                values.push(Value::Text(status.to_string()));
+            }
+
+            if let Some(label) = &query.label {
+                filters.push("id IN (SELECT ticket_id FROM ticket_labels WHERE label = ?)".into());
+                values.push(Value::Text(label.to_string()));
+            }
+
+            if let Some(text) = &query.text {
+                filters.push("(instr(lower(title), ?) OR instr(lower(description), ?))".into());
+                values.push(Value::Text(text.to_ascii_lowercase()));
+                values.push(Value::Text(text.to_ascii_lowercase()));
+            }
+
+            if let Some(after) = query.after {
+                if query.sort == SortBy::Id {
+                    filters.push("id > ?".into());
+                } else {
+                    let cursor: Value = tx
+                        .query_row(&format!("SELECT {key} FROM tickets WHERE id = ?1"), [after.0 as i64], |row| row.get(0))
+                        .optional()?
+                        .ok_or(Error::InvalidCursor(after))?;
+
+                    filters.push(format!("({key}, id) > (?, ?)"));
+                    values.push(cursor);
+                }
+                values.push(Value::Integer(after.0 as i64));
+            }
+
+            let filters = if filters.is_empty() {
+                String::new()
+            } else {
+                format!("WHERE {}", filters.join(" AND "))
+            };
+            values.push(Value::Integer(query.page_size() as i64 + 1));
+
+            let tickets = tx
+                .prepare(&format!("{SELECT_TICKET} {filters} ORDER BY {key}, id LIMIT ?"))?
+                .query_map(params_from_iter(values), read_ticket)?
+                .map(|row| into_ticket(row?))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(query.page(tickets))
+        }).await
+    }
+
+    async fn overdue(&self) -> Result<Vec<Ticket>> {
+        let today = self.clock.now().date_naive();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            // Dates are stored as `YYYY-MM-DD`, so comparing them as text works.
+            let tickets = tx
+                .prepare(&format!(
+                    "{SELECT_TICKET} WHERE NOT archived AND status != 'Done' AND due_date < ?1
+                    ORDER BY {PRIORITY_RANK}, due_date, id"
+                ))?
+                .query_map([today.to_string()], read_ticket)?
+                .map(|row| into_ticket(row?))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(tickets)
+        }).await
+    }
+
+    async fn patch(&self, patch: TicketPatch) -> Result<Option<Ticket>> {
+        if let Some(title) = &patch.title {
+            TicketTitle::try_from(title.to_string())?;
+        }
+        if let Some(description) = &patch.description {
+            TicketDescription::try_from(description.to_string())?;
+        }
+
+        let workflow = self.workflow.clone();
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            let ticket = update(&tx, &workflow, clock.as_ref(), patch)?;
+
+            tx.commit()?;
+            Ok(ticket)
+        }).await
+    }
+
+    async fn reopen(&self, id: TicketId, version: Option<u64>) -> Result<Option<Ticket>> {
+        let workflow = self.workflow.clone();
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(old) = select(&tx, id)? else {
+                return Ok(None)
+            };
+            TicketPatch { id, version, ..Default::default() }.check_version(&old)?;
+
+            let status = workflow.reopen_target(&old)?;
+            let mut ticket = Ticket { status, version: old.version + 1, ..old.clone() };
+
+            let now = clock.now();
+            ticket.touch(&old, now);
+
+            tx.execute(
+                "UPDATE tickets SET status = ?2, version = ?3, updated_at = ?4, status_changed_at = ?5 WHERE id = ?1",
+                params![
+                    id.0 as i64,
+                    ticket.status.to_string(),
+                    ticket.version as i64,
+                    timestamp(ticket.updated_at),
+                    timestamp(ticket.status_changed_at)
+                ],
+            )?;
+
+            let entry = HistoryEntry {
+                action: Action::Reopened,
+                ..HistoryEntry::between(Some(&old), &ticket, now)
+            };
+            record(&tx, entry, &ticket)?;
+
+            tx.commit()?;
+            Ok(Some(ticket))
+        }).await
+    }
+
+    async fn delete(&self, id: TicketId) -> Result<Option<Ticket>> {
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(ticket) = select(&tx, id)? else {
+                return Ok(None)
+            };
+            tx.execute("DELETE FROM tickets WHERE id = ?1", [id.0 as i64])?;
+
+            // The history stays behind, ending with the deletion.
+            let gone = Ticket { version: ticket.version + 1, ..ticket.clone() };
+            record(&tx, HistoryEntry::deleted(&ticket, clock.now()), &gone)?;
+
+            tx.commit()?;
+            Ok(Some(ticket))
+        }).await
+    }
+
+    async fn archive(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.set_archived(id, true).await
+    }
+
+    async fn restore(&self, id: TicketId) -> Result<Option<Ticket>> {
+        self.set_archived(id, false).await
+    }
+
+    async fn add_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.set_label(id, label, version, true).await
+    }
+
+    async fn remove_label(&self, id: TicketId, label: Label, version: Option<u64>) -> Result<Option<Ticket>> {
+        self.set_label(id, label, version, false).await
+    }
+
+    // Every ticket starts with an entry, so none means there never was one.
+    async fn history(&self, id: TicketId) -> Result<Option<Vec<HistoryEntry>>> {
+        self.run(move |conn| {
+            let entries = conn
+                .prepare("SELECT entry FROM ticket_history WHERE ticket_id = ?1 ORDER BY version")?
+                .query_map([id.0 as i64], |row| row.get::<_, String>(0))?
+                .map(|entry| Ok(serde_json::from_str(&entry?)?))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok((!entries.is_empty()).then_some(entries))
+        }).await
+    }
+
+    async fn add_comment(&self, ticket: TicketId, draft: CommentDraft) -> Result<Option<Comment>> {
+        draft.validate()?;
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            if select(&tx, ticket)?.is_none() {
+                return Ok(None)
+            }
+
+            let id: i64 = tx.query_row("SELECT next_id FROM comment_counter", [], |row| row.get(0))?;
+            let now = clock.now();
+            let comment = Comment {
+                id: CommentId(id as u64),
+                ticket_id: ticket,
+                body: draft.body,
+                created_at: now,
+                updated_at: now,
+            };
+
+            tx.execute(
+                "INSERT INTO ticket_comments (id, ticket_id, body, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)",
+                params![id, ticket.0 as i64, comment.body.to_string(), timestamp(now)],
+            )?;
+            tx.execute("UPDATE comment_counter SET next_id = ?1", [id + 1])?;
+
+            tx.commit()?;
+            Ok(Some(comment))
+        }).await
+    }
+
+    async fn comments(&self, ticket: TicketId) -> Result<Option<Vec<Comment>>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            if select(&tx, ticket)?.is_none() {
+                return Ok(None)
+            }
+
+            let comments = tx
+                .prepare(&format!("{SELECT_COMMENT} WHERE ticket_id = ?1 ORDER BY id"))?
+                .query_map([ticket.0 as i64], read_comment)?
+                .map(|row| into_comment(row?))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Some(comments))
+        }).await
+    }
+
+    async fn edit_comment(&self, ticket: TicketId, id: CommentId, draft: CommentDraft)
+        -> Result<Option<Comment>>
+    {
+        draft.validate()?;
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(comment) = select_comment(&tx, ticket, id)? else {
+                return Ok(None)
+            };
+
+            let edited = Comment { body: draft.body, updated_at: clock.now(), ..comment };
+            tx.execute(
+                "UPDATE ticket_comments SET body = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id.0 as i64, edited.body.to_string(), timestamp(edited.updated_at)],
+            )?;
+
+            tx.commit()?;
+            Ok(Some(edited))
+        }).await
+    }
+
+    async fn link(&self, link: Link) -> Result<Option<Link>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            if select(&tx, link.from)?.is_none() {
+                return Ok(None)
+            }
+
+            if select(&tx, link.to)?.is_none() {
+                return Err(Error::InvalidLink(format!("Ticket {} doesn't exist.", link.to)))
+            }
+
+            let existing = tx
+                .prepare(&format!("{SELECT_LINK} WHERE kind = ?1"))?
+                .query_map([link.kind.to_string()], read_link)?
+                .map(|row| into_link(row?))
+                .collect::<Result<Vec<_>>>()?;
+            link.check(&existing)?;
+
+            tx.execute(
+                "INSERT OR IGNORE INTO ticket_links (kind, from_id, to_id) VALUES (?1, ?2, ?3)",
+                params![link.kind.to_string(), link.from.0 as i64, link.to.0 as i64],
+            )?;
+
+            tx.commit()?;
+            Ok(Some(link))
+        }).await
+    }
+
+    async fn unlink(&self, link: Link) -> Result<Option<Link>> {
+        self.run(move |conn| {
+            let removed = conn.execute(
+                "DELETE FROM ticket_links WHERE kind = ?1 AND from_id = ?2 AND to_id = ?3",
+                params![link.kind.to_string(), link.from.0 as i64, link.to.0 as i64],
+            )?;
+
+            Ok((removed > 0).then_some(link))
+        }).await
+    }
+
+    async fn links(&self, id: TicketId) -> Result<Option<Vec<Link>>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            if select(&tx, id)?.is_none() {
+                return Ok(None)
+            }
+
+            let links = tx
+                .prepare(&format!("{SELECT_LINK} WHERE from_id = ?1 OR to_id = ?1 ORDER BY kind, from_id, to_id"))?
+                .query_map([id.0 as i64], read_link)?
+                .map(|row| into_link(row?))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(Some(links))
+        }).await
+    }
+
+    async fn delete_comment(&self, ticket: TicketId, id: CommentId) -> Result<Option<Comment>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(comment) = select_comment(&tx, ticket, id)? else {
+                return Ok(None)
+            };
+            tx.execute("DELETE FROM ticket_comments WHERE id = ?1", [id.0 as i64])?;
+
+            tx.commit()?;
+            Ok(Some(comment))
+        }).await
+    }
+
+    async fn batch(&self, batch: Batch) -> Result<Vec<BatchOutcome>> {
+        let workflow = self.workflow.clone();
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let mut tx = conn.transaction()?;
+            let mut outcomes = Vec::with_capacity(batch.ops.len());
+
+            // Every item gets a savepoint of its own, so a failed one leaves nothing behind.
+            for (index, op) in batch.ops.into_iter().enumerate() {
+                let savepoint = tx.savepoint()?;
+
+                match apply(&savepoint, &workflow, clock.as_ref(), op) {
+                    Ok(outcome) => {
+                        savepoint.commit()?;
+                        outcomes.push(outcome);
+                    }
+                    Err(error) if batch.mode == BatchMode::PerItem => outcomes.push(BatchOutcome::Failed(error.problem())),
+                    Err(error) => return Err(Error::BatchFailed { index, error: Box::new(error) }),
+                }
+            }
+
+            tx.commit()?;
+            Ok(outcomes)
+        }).await
+    }
+
+    async fn add_webhook(&self, draft: WebhookDraft) -> Result<Webhook> {
+        draft.validate()?;
+        let clock = self.clock.clone();
+
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let id: i64 = tx.query_row("SELECT next_id FROM webhook_counter", [], |row| row.get(0))?;
+            let webhook = Webhook { id: WebhookId(id as u64), url: draft.url, secret: draft.secret, created_at: clock.now() };
+
+            tx.execute(
+                "INSERT INTO webhooks (id, url, secret, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![id, webhook.url, webhook.secret, timestamp(webhook.created_at)],
+            )?;
+            tx.execute("UPDATE webhook_counter SET next_id = ?1", [id + 1])?;
+
+            tx.commit()?;
+            Ok(webhook)
+        }).await
+    }
+
+    async fn webhooks(&self) -> Result<Vec<Webhook>> {
+        self.run(|conn| {
+            conn.prepare(&format!("{SELECT_WEBHOOK} ORDER BY id"))?
+                .query_map([], read_webhook)?
+                .map(|row| into_webhook(row?))
+                .collect()
+        }).await
+    }
+
+    async fn remove_webhook(&self, id: WebhookId) -> Result<Option<Webhook>> {
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+
+            let Some(webhook) = tx
+                .query_row(&format!("{SELECT_WEBHOOK} WHERE id = ?1"), [id.0 as i64], read_webhook)
+                .optional()?
+                .map(into_webhook)
+                .transpose()? else {
+                return Ok(None)
+            };
+            tx.execute("DELETE FROM webhooks WHERE id = ?1", [id.0 as i64])?;
+
+            tx.commit()?;
+            Ok(Some(webhook))
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn check_if_migrations_run_once_and_data_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tickets.db");
+
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            store.create(TicketDraft::with("First", "Stored in SQLite.").unwrap()).await.unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+
+        let version: i64 = store.run(|conn| {
+            Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+        }).await.unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(version as usize, MIGRATIONS.len());
+
+        assert_eq!(store.list(TicketQuery::default()).await.unwrap().tickets.len(), 1);
+        assert_eq!(
+            store.create(TicketDraft::with("Second", "Stored in SQLite.").unwrap()).await.unwrap(),
+            TicketId(1)
+        );
+    }
+
+    // Every step of the plan, one per line.
+    async fn query_plan(store: &SqliteStore, filters: &'static str) -> String {
+        store.run(move |conn| {
+            Ok(conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {SELECT_TICKET} WHERE {filters} ORDER BY id"))?
+                .query_map([], |row| row.get::<_, String>(3))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .join("\n"))
+        }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn check_if_status_lookups_use_the_index() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let plan = query_plan(&store, "NOT archived AND status = 'Done'").await;
+        assert!(plan.contains("tickets_by_status"), "Unexpected query plan: {plan}");
+    }
+
+    #[tokio::test]
+    async fn check_if_label_lookups_use_the_index() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let plan = query_plan(&store, "id IN (SELECT ticket_id FROM ticket_labels WHERE label = 'docs')").await;
+        assert!(plan.contains("ticket_labels_by_label"), "Unexpected query plan: {plan}");
+        assert!(!plan.contains("SCAN tickets"), "Unexpected query plan: {plan}");
+    }
+
+    #[tokio::test]
+    async fn check_if_invalid_rows_are_rejected_on_read() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let long_title: String = (0..=crate::data::title::MAX_TITLE_LEN).map(|_| "a").collect();
+        store.run(move |conn| {
+            conn.execute(
+                "INSERT INTO tickets (id, title, description, status, created_at, updated_at, status_changed_at)
+                VALUES (0, ?1, 'Fine.', 'Done', ?2, ?2, ?2)",
+                params![long_title, "2024-03-01T09:00:00.000000000Z"],
+            )?;
+            Ok(())
+        }).await.unwrap();
+
+        assert!(matches!(store.get(TicketId(0)).await, Err(Error::Title(_))));
+        let query = TicketQuery { archived: true, ..Default::default() };
+        assert!(matches!(store.list(query).await, Err(Error::Title(_))));
+    }
+
+    #[tokio::test]
+    async fn check_if_invalid_status_is_rejected_on_write() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        let result = store.run(|conn| {
+            conn.execute(
+                "INSERT INTO tickets (id, title, description, status, created_at, updated_at, status_changed_at)
+                VALUES (0, 'Fine', 'Fine.', 'Nope', ?1, ?1, ?1)",
+                ["2024-03-01T09:00:00.000000000Z"],
+            )?;
+            Ok(())
+        }).await;
+
+        assert!(matches!(result, Err(Error::Sqlite(_))));
+    }
+
+    #[tokio::test]
+    async fn check_if_history_cannot_be_rewritten() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store.create(TicketDraft::with("First", "Stored in SQLite.").unwrap()).await.unwrap();
+
+        for statement in ["UPDATE ticket_history SET at = 'yesterday'", "DELETE FROM ticket_history"] {
+            let result = store.run(move |conn| {
+                conn.execute(statement, [])?;
+                Ok(())
+            }).await;
+
+            assert!(matches!(result, Err(Error::Sqlite(_))), "{statement} went through");
+        }
+    }
+
+    #[tokio::test]
+    async fn check_if_newer_schema_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tickets.db");
+
+        Connection::open(&path).unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() as i64 + 1).unwrap();
+
+        assert!(matches!(SqliteStore::open(&path), Err(Error::Storage(_))));
+    }
+}
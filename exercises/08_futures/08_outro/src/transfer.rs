@@ -0,0 +1,553 @@
+// Moving tickets in and out of the store in bulk, as CSV, a JSON array or NDJSON.
+//
+// Exports carry every field of every ticket, archived ones included. Imports only
+// read what a new ticket can start with: title, description, status, priority,
+// due date and labels. Anything else, like the ids of an export, is ignored, so an
+// export can be imported again as it is.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::Arc;
+use chrono::NaiveDate;
+use futures_util::{stream, Stream};
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::{
+    data::query::MAX_PAGE_SIZE,
+    data::{Label, Priority, Status, Ticket, TicketDescription, TicketDraft, TicketId, TicketPatch, TicketQuery, TicketTitle},
+    error::Result,
+    store::TicketRepository,
+};
+
+pub const CSV_COLUMNS: [&str; 12] = [
+    "id", "title", "description", "status", "version", "archived", "labels", "priority", "due_date",
+    "created_at", "updated_at", "status_changed_at",
+];
+
+// Labels can't contain it, so a CSV cell holds all of them.
+pub const LABEL_SEPARATOR: char = ';';
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    #[default]
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn header(&self) -> Result<Vec<u8>> {
+        match self {
+            Self::Csv => csv_line(CSV_COLUMNS),
+            Self::Json => Ok(b"[".to_vec()),
+            Self::Ndjson => Ok(Vec::new()),
+        }
+    }
+
+    fn footer(&self) -> &'static [u8] {
+        match self {
+            Self::Json => b"]\n",
+            Self::Csv | Self::Ndjson => b"",
+        }
+    }
+
+    fn row(&self, out: &mut Vec<u8>, ticket: &Ticket, first: bool) -> Result<()> {
+        match self {
+            Self::Csv => out.extend(csv_line(csv_fields(ticket))?),
+            Self::Json => {
+                if !first {
+                    out.push(b',');
+                }
+                serde_json::to_writer(&mut *out, ticket)?;
+            }
+            Self::Ndjson => {
+                serde_json::to_writer(&mut *out, ticket)?;
+                out.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+}
+
+fn csv_fields(ticket: &Ticket) -> [String; 12] {
+    [
+        ticket.id.to_string(),
+        ticket.title.to_string(),
+        ticket.description.to_string(),
+        ticket.status.to_string(),
+        ticket.version.to_string(),
+        ticket.archived.to_string(),
+        ticket.labels.iter().map(Label::to_string).collect::<Vec<_>>().join(&LABEL_SEPARATOR.to_string()),
+        ticket.priority.map(|p| p.to_string()).unwrap_or_default(),
+        ticket.due_date.map(|d| d.to_string()).unwrap_or_default(),
+        ticket.created_at.to_rfc3339(),
+        ticket.updated_at.to_rfc3339(),
+        ticket.status_changed_at.to_rfc3339(),
+    ]
+}
+
+fn csv_line<I, T>(fields: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+// Writes the tickets out in one go.
+pub fn write<'a>(format: Format, tickets: impl IntoIterator<Item = &'a Ticket>, mut writer: impl Write) -> Result<()> {
+    let mut out = format.header()?;
+    for (n, ticket) in tickets.into_iter().enumerate() {
+        format.row(&mut out, ticket, n == 0)?;
+    }
+    out.extend(format.footer());
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Every ticket in the store, fetched and sent a page at a time so they're never
+// all held at once. Tickets changed halfway through may or may not make it in.
+pub fn export(repository: Arc<dyn TicketRepository>, format: Format)
+    -> impl Stream<Item = Result<Vec<u8>>> + Send + 'static
+{
+    struct Export {
+        repository: Arc<dyn TicketRepository>,
+        after: Option<TicketId>,
+        first: bool,
+    }
+
+    let start = Export { repository, after: None, first: true };
+
+    stream::try_unfold(Some(start), move |export| async move {
+        let Some(mut export) = export else {
+            return Ok(None)
+        };
+
+        let query = TicketQuery { after: export.after, limit: Some(MAX_PAGE_SIZE), archived: true, ..Default::default() };
+        let page = export.repository.list(query).await?;
+
+        let mut chunk = if export.after.is_none() { format.header()? } else { Vec::new() };
+        for ticket in &page.tickets {
+            format.row(&mut chunk, ticket, export.first)?;
+            export.first = false;
+        }
+
+        match page.next_cursor {
+            Some(cursor) => {
+                export.after = Some(cursor);
+                Ok(Some((chunk, Some(export))))
+            }
+            None => {
+                chunk.extend(format.footer());
+                Ok(Some((chunk, None)))
+            }
+        }
+    })
+}
+
+// Where an imported row went wrong. For CSV and NDJSON `line` is the line in the
+// input, for a JSON array it's the position of the item, both counting from 1.
+// `column` is left out when the row as a whole couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportError {
+    pub line: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl ImportError {
+    fn at(line: usize, column: &str, message: impl Display) -> Self {
+        Self { line, column: Some(column.into()), message: message.to_string() }
+    }
+
+    fn row(line: usize, message: impl Display) -> Self {
+        Self { line, column: None, message: message.to_string() }
+    }
+}
+
+// Rows with errors are skipped, the rest are imported.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub imported: Vec<TicketId>,
+    pub errors: Vec<ImportError>,
+}
+
+// A row that passed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRow {
+    pub line: usize,
+    pub draft: TicketDraft,
+    pub status: Status,
+    pub labels: BTreeSet<Label>,
+}
+
+// A cell as it was read, before validation. `Err` if it had the wrong type.
+type Cell = std::result::Result<Option<String>, String>;
+
+#[derive(Debug)]
+struct Fields {
+    title: Cell,
+    description: Cell,
+    status: Cell,
+    priority: Cell,
+    due_date: Cell,
+    labels: std::result::Result<Vec<String>, String>,
+}
+
+// Checks every row, collecting every error in it instead of stopping at the first.
+pub fn parse(format: Format, input: &str) -> (Vec<ImportRow>, Vec<ImportError>) {
+    let mut errors = Vec::new();
+
+    let rows = match format {
+        Format::Csv => read_csv(input, &mut errors),
+        Format::Json => read_json(input, &mut errors),
+        Format::Ndjson => read_ndjson(input, &mut errors),
+    };
+
+    let rows = rows.into_iter().filter_map(|(line, fields)| validate(line, fields, &mut errors)).collect();
+    errors.sort_by_key(|error| error.line);
+
+    (rows, errors)
+}
+
+pub async fn import(repository: &dyn TicketRepository, format: Format, input: &str) -> Result<ImportReport> {
+    let (rows, mut errors) = parse(format, input);
+    let mut imported = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        match insert(repository, &row).await {
+            Ok(id) => imported.push(id),
+            Err(error) => errors.push(error),
+        }
+    }
+    errors.sort_by_key(|error| error.line);
+
+    Ok(ImportReport { imported, errors })
+}
+
+// Tickets always start out as to-do without labels, so both are added afterwards.
+// If any of that fails the ticket is taken out again, a row goes in whole or not at all.
+async fn insert(repository: &dyn TicketRepository, row: &ImportRow) -> std::result::Result<TicketId, ImportError> {
+    let store_error = |column: Option<&str>, error: crate::error::Error| ImportError {
+        line: row.line,
+        column: column.map(Into::into),
+        message: error.to_string(),
+    };
+
+    let id = repository.create(row.draft.clone()).await.map_err(|e| store_error(None, e))?;
+
+    if let Err((column, error)) = complete(repository, id, row).await {
+        let _ = repository.delete(id).await;
+        return Err(store_error(Some(column), error))
+    }
+
+    Ok(id)
+}
+
+async fn complete(repository: &dyn TicketRepository, id: TicketId, row: &ImportRow)
+    -> std::result::Result<(), (&'static str, crate::error::Error)>
+{
+    for label in &row.labels {
+        repository.add_label(id, label.clone(), None).await.map_err(|e| ("labels", e))?;
+    }
+
+    if row.status != Status::ToDo {
+        let patch = TicketPatch { id, status: Some(row.status), ..Default::default() };
+        repository.patch(patch).await.map_err(|e| ("status", e))?;
+    }
+
+    Ok(())
+}
+
+fn validate(line: usize, fields: Fields, errors: &mut Vec<ImportError>) -> Option<ImportRow> {
+    let before = errors.len();
+
+    let required = |column: &str, text: Option<String>| text.ok_or_else(|| format!("Missing {column}."));
+    let given = |text: Option<String>| text.filter(|text| !text.trim().is_empty());
+
+    let title = fields.title
+        .and_then(|title| required("title", title))
+        .and_then(|title| TicketTitle::try_from(title).map_err(|e| e.to_string()));
+    let description = fields.description
+        .and_then(|description| required("description", description))
+        .and_then(|description| TicketDescription::try_from(description).map_err(|e| e.to_string()));
+    let status = fields.status.map(given).and_then(|status| match status {
+        Some(status) => Status::try_from(status).map_err(|e| e.to_string()),
+        None => Ok(Status::ToDo),
+    });
+    let priority = fields.priority.map(given).and_then(|priority| {
+        priority.map(Priority::try_from).transpose().map_err(|e| e.to_string())
+    });
+    let due_date = fields.due_date.map(given).and_then(|due_date| {
+        due_date.map(|d| d.trim().parse::<NaiveDate>().map_err(|e| format!("{e}: {d:?}"))).transpose()
+    });
+    let labels = fields.labels.and_then(|labels| {
+        labels.into_iter()
+            .map(|label| Label::try_from(label).map_err(|e| e.to_string()))
+            .collect::<std::result::Result<BTreeSet<_>, _>>()
+    });
+
+    let title = report(line, "title", title, errors);
+    let description = report(line, "description", description, errors);
+    let status = report(line, "status", status, errors);
+    let priority = report(line, "priority", priority, errors);
+    let due_date = report(line, "due_date", due_date, errors);
+    let labels = report(line, "labels", labels, errors);
+
+    if errors.len() > before {
+        return None
+    }
+
+    Some(ImportRow {
+        line,
+        draft: TicketDraft { title: title?, description: description?, priority: priority?, due_date: due_date? },
+        status: status?,
+        labels: labels?,
+    })
+}
+
+fn report<T>(line: usize, column: &str, result: std::result::Result<T, String>, errors: &mut Vec<ImportError>) -> Option<T> {
+    result.map_err(|message| errors.push(ImportError::at(line, column, message))).ok()
+}
+
+fn read_csv(input: &str, errors: &mut Vec<ImportError>) -> Vec<(usize, Fields)> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(input.as_bytes());
+
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(error) => {
+            errors.push(ImportError::row(1, error));
+            return Vec::new()
+        }
+    };
+    let column = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name));
+
+    let missing = ["title", "description"].into_iter().filter(|name| column(name).is_none()).collect::<Vec<_>>();
+    if !missing.is_empty() {
+        for name in missing {
+            errors.push(ImportError::at(1, name, format!("Missing {name} column.")));
+        }
+        return Vec::new()
+    }
+
+    let mut rows = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map_or(0, |position| position.line() as usize);
+                errors.push(ImportError::row(line, error));
+                continue
+            }
+        };
+
+        let line = record.position().map_or(0, |position| position.line() as usize);
+        let cell = |name: &str| column(name).and_then(|i| record.get(i)).map(str::to_string);
+
+        rows.push((line, Fields {
+            title: Ok(cell("title")),
+            description: Ok(cell("description")),
+            status: Ok(cell("status")),
+            priority: Ok(cell("priority")),
+            due_date: Ok(cell("due_date")),
+            labels: Ok(cell("labels")
+                .unwrap_or_default()
+                .split(LABEL_SEPARATOR)
+                .filter(|label| !label.trim().is_empty())
+                .map(str::to_string)
+                .collect()),
+        }));
+    }
+
+    rows
+}
+
+fn read_json(input: &str, errors: &mut Vec<ImportError>) -> Vec<(usize, Fields)> {
+    match serde_json::from_str::<Vec<Value>>(input) {
+        Ok(items) => items.into_iter()
+            .enumerate()
+            .filter_map(|(n, item)| read_object(n + 1, item, errors))
+            .collect(),
+        Err(error) => {
+            errors.push(ImportError::row(error.line(), error));
+            Vec::new()
+        }
+    }
+}
+
+fn read_ndjson(input: &str, errors: &mut Vec<ImportError>) -> Vec<(usize, Fields)> {
+    let mut rows = Vec::new();
+
+    for (n, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue
+        }
+
+        match serde_json::from_str::<Value>(line) {
+            Ok(item) => rows.extend(read_object(n + 1, item, errors)),
+            Err(error) => errors.push(ImportError::row(n + 1, error)),
+        }
+    }
+
+    rows
+}
+
+fn read_object(line: usize, item: Value, errors: &mut Vec<ImportError>) -> Option<(usize, Fields)> {
+    let Value::Object(object) = item else {
+        errors.push(ImportError::row(line, format!("Expected a JSON object, got {item}.")));
+        return None
+    };
+
+    let text = |name: &str| match object.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text.clone())),
+        Some(other) => Err(format!("Expected a string, got {other}.")),
+    };
+
+    Some((line, Fields {
+        title: text("title"),
+        description: text("description"),
+        status: text("status"),
+        priority: text("priority"),
+        due_date: text("due_date"),
+        labels: read_labels(&object),
+    }))
+}
+
+fn read_labels(object: &Map<String, Value>) -> std::result::Result<Vec<String>, String> {
+    match object.get("labels") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(labels)) if labels.iter().all(Value::is_string) => {
+            Ok(labels.iter().filter_map(Value::as_str).map(str::to_string).collect())
+        }
+        Some(other) => Err(format!("Expected a list of strings, got {other}.")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::RwLock;
+    use crate::store::TicketStore;
+    use super::*;
+
+    #[tokio::test]
+    async fn check_if_exports_can_be_imported_again_in_every_format() -> Result<()> {
+        let source = RwLock::new(TicketStore::new());
+
+        let mut draft = TicketDraft::with("Quotes, \"commas\"", "Spans\ntwo lines; and more.")?;
+        draft.priority = Some(Priority::High);
+        draft.due_date = NaiveDate::from_ymd_opt(2030, 1, 31);
+        let id = source.create(draft).await?;
+        source.add_label(id, Label::try_from("backend")?, None).await?;
+        source.add_label(id, Label::try_from("urgent")?, None).await?;
+        source.patch(TicketPatch { id, status: Some(Status::InProgress), ..Default::default() }).await?;
+        source.create(TicketDraft::with("Plain", "Nothing special.")?).await?;
+
+        let tickets = source.list(TicketQuery { archived: true, ..Default::default() }).await?.tickets;
+        let fields = |t: &Ticket| (t.title.clone(), t.description.clone(), t.status, t.priority, t.due_date, t.labels.clone());
+
+        for format in [Format::Csv, Format::Json, Format::Ndjson] {
+            let mut out = Vec::new();
+            write(format, &tickets, &mut out)?;
+
+            let target = RwLock::new(TicketStore::new());
+            let report = import(&target, format, std::str::from_utf8(&out).unwrap()).await?;
+            assert_eq!(report.errors, vec![], "{format:?} import of:\n{}", String::from_utf8_lossy(&out));
+
+            let imported = target.list(TicketQuery::default()).await?.tickets;
+            assert_eq!(
+                imported.iter().map(fields).collect::<Vec<_>>(),
+                tickets.iter().map(fields).collect::<Vec<_>>(),
+                "{format:?}",
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_if_rows_that_fail_halfway_are_taken_out_again() -> Result<()> {
+        use crate::store::sqlite::SqliteStore;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tickets.db");
+        drop(SqliteStore::open(&path)?);
+
+        // Labels can't be stored, so the ticket is created but can't be finished.
+        rusqlite::Connection::open(&path)?.execute_batch(
+            "CREATE TRIGGER no_labels BEFORE INSERT ON ticket_labels BEGIN SELECT RAISE(ABORT, 'No labels'); END;",
+        )?;
+        let store = SqliteStore::open(&path)?;
+
+        let csv = "title,description,status,priority,due_date,labels\n\
+                   Labelled,Never makes it.,ToDo,,,backend\n\
+                   Plain,Goes in.,ToDo,,,\n";
+        let report = import(&store, Format::Csv, csv).await?;
+
+        assert_eq!(report.imported, vec![TicketId(1)]);
+        let errors = report.errors.iter().map(|e| (e.line, e.column.as_deref())).collect::<Vec<_>>();
+        assert_eq!(errors, vec![(2, Some("labels"))]);
+
+        let titles = store.list(TicketQuery { archived: true, ..Default::default() }).await?.tickets;
+        assert_eq!(titles.iter().map(|t| t.title.to_string()).collect::<Vec<_>>(), vec!["Plain"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_if_every_bad_cell_is_reported_with_its_line_and_column() {
+        let error = |line, column: Option<&str>| (line, column.map(str::to_string));
+        let at = |errors: Vec<ImportError>| errors.into_iter().map(|e| (e.line, e.column)).collect::<Vec<_>>();
+
+        let csv = "title,description,status,priority,labels\n\
+                   Fine,All good.,done,low,a;b\n\
+                   ,Untitled.,sideways,,\n\
+                   Late,Bad date.,,urgent,\n";
+        let (rows, errors) = parse(Format::Csv, csv);
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].line, rows[0].status, rows[0].labels.len()), (2, Status::Done, 2));
+        assert_eq!(at(errors), vec![error(3, Some("title")), error(3, Some("status")), error(4, Some("priority"))]);
+
+        let (rows, errors) = parse(Format::Csv, "name,description\nSomething,Else\n");
+        assert_eq!((rows.len(), at(errors)), (0, vec![error(1, Some("title"))]));
+
+        let ndjson = r#"{"title": "Fine", "description": "All good."}
+
+{"title": 42, "description": "Numbered.", "labels": "one"}
+{not json
+"#;
+        let (rows, errors) = parse(Format::Ndjson, ndjson);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(at(errors), vec![error(3, Some("title")), error(3, Some("labels")), error(4, None)]);
+
+        let json = r#"[{"title": "Fine", "description": "All good.", "due_date": "2030-02-30"}, "stray"]"#;
+        let (rows, errors) = parse(Format::Json, json);
+        assert_eq!(rows.len(), 0);
+        assert_eq!(at(errors), vec![error(1, Some("due_date")), error(2, None)]);
+    }
+}